@@ -0,0 +1,48 @@
+-- Back to the untyped varchar schema (constraints and indexes are lost)
+alter table users rename to users_old;
+alter table ledgers rename to ledgers_old;
+alter table chats rename to chats_old;
+alter table transactions rename to transactions_old;
+
+create table users
+(
+    id          varchar not null primary key,
+    user_id     varchar not null,
+    name        varchar not null
+);
+
+create table ledgers
+(
+    id          varchar not null primary key,
+    borrower      varchar not null,
+    owes     varchar not null,
+    sum         int not null
+);
+
+create table chats
+(
+    id          varchar not null primary key,
+    user_id     varchar not null,
+    chat_id     varchar not null
+);
+
+create table transactions
+(
+    id                  varchar not null primary key,
+    transaction_type    varchar not null,
+    initiator           varchar not null,
+    reciever            varchar not null,
+    sum                 int not null,
+    description         varchar not null,
+    created             varchar not null
+);
+
+insert into users select id, cast(user_id as text), name from users_old;
+insert into ledgers select id, cast(borrower as text), cast(owes as text), sum from ledgers_old;
+insert into chats select id, cast(user_id as text), cast(chat_id as text) from chats_old;
+insert into transactions select id, transaction_type, cast(initiator as text), cast(reciever as text), sum, description, created from transactions_old;
+
+drop table transactions_old;
+drop table chats_old;
+drop table ledgers_old;
+drop table users_old;
@@ -0,0 +1,88 @@
+-- Typed schema: integer telegram ids, real timestamps, foreign keys and
+-- indexes. Existing rows are converted in place; ids used to be stored as
+-- (possibly JSON quoted) strings.
+alter table users rename to users_old;
+alter table ledgers rename to ledgers_old;
+alter table chats rename to chats_old;
+alter table transactions rename to transactions_old;
+
+create table users
+(
+    id          varchar not null primary key,
+    user_id     bigint not null unique,
+    name        varchar not null
+);
+
+create table ledgers
+(
+    id          varchar not null primary key,
+    borrower    bigint not null references users (user_id),
+    owes        bigint not null references users (user_id),
+    sum         int not null,
+    unique (borrower, owes)
+);
+
+create table chats
+(
+    id          varchar not null primary key,
+    user_id     bigint not null references users (user_id),
+    chat_id     bigint not null,
+    -- also serves lookups by chat_id
+    unique (chat_id, user_id)
+);
+
+create table transactions
+(
+    id                  varchar not null primary key,
+    transaction_type    varchar not null,
+    initiator           bigint not null references users (user_id),
+    reciever            bigint not null references users (user_id),
+    sum                 int not null,
+    description         varchar not null,
+    created             timestamp not null
+);
+
+create index chats_user_id on chats (user_id);
+create index ledgers_owes on ledgers (owes);
+create index transactions_initiator on transactions (initiator);
+create index transactions_reciever on transactions (reciever);
+create index transactions_created on transactions (created);
+
+-- one user per telegram id (keep the first registration)
+insert into users (id, user_id, name)
+select id, cast(trim(user_id, '"') as integer), name
+from users_old
+where rowid in (select min(rowid) from users_old group by cast(trim(user_id, '"') as integer));
+
+-- users referenced by ledgers, chats or transactions but never registered
+-- get a placeholder row so the foreign keys hold
+insert into users (id, user_id, name)
+select lower(hex(randomblob(16))), uid, ''
+from (
+    select cast(trim(borrower, '"') as integer) as uid from ledgers_old
+    union select cast(trim(owes, '"') as integer) from ledgers_old
+    union select cast(trim(user_id, '"') as integer) from chats_old
+    union select cast(trim(initiator, '"') as integer) from transactions_old
+    union select cast(trim(reciever, '"') as integer) from transactions_old
+)
+where uid not in (select user_id from users);
+
+-- one ledger per pair (merge duplicates by adding up their sums)
+insert into ledgers (id, borrower, owes, sum)
+select min(id), cast(trim(borrower, '"') as integer), cast(trim(owes, '"') as integer), sum(sum)
+from ledgers_old
+group by cast(trim(borrower, '"') as integer), cast(trim(owes, '"') as integer);
+
+insert into chats (id, user_id, chat_id)
+select min(id), cast(trim(user_id, '"') as integer), cast(trim(chat_id, '"') as integer)
+from chats_old
+group by cast(trim(user_id, '"') as integer), cast(trim(chat_id, '"') as integer);
+
+insert into transactions (id, transaction_type, initiator, reciever, sum, description, created)
+select id, transaction_type, cast(trim(initiator, '"') as integer), cast(trim(reciever, '"') as integer), sum, description, created
+from transactions_old;
+
+drop table users_old;
+drop table ledgers_old;
+drop table chats_old;
+drop table transactions_old;
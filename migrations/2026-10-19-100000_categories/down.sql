@@ -0,0 +1,3 @@
+drop table transaction_categories;
+drop table category_aliases;
+drop table categories;
@@ -0,0 +1,28 @@
+-- Categories are per chat, hashtags in loans refer to them by name or alias
+create table categories
+(
+    id          varchar not null primary key,
+    chat_id     bigint not null,
+    name        varchar not null,
+    unique (chat_id, name)
+);
+
+create table category_aliases
+(
+    id          varchar not null primary key,
+    category_id varchar not null references categories (id) on delete cascade,
+    chat_id     bigint not null,
+    alias       varchar not null,
+    unique (chat_id, alias)
+);
+
+-- position keeps the order of the hashtags, the first one is the main category
+create table transaction_categories
+(
+    transaction_id  varchar not null references transactions (id) on delete cascade,
+    category_id     varchar not null references categories (id) on delete cascade,
+    position        integer not null,
+    primary key (transaction_id, category_id)
+);
+
+create index transaction_categories_category on transaction_categories (category_id);
@@ -0,0 +1,19 @@
+-- Loans repeated on a schedule. "loan" is the message text after the
+-- schedule ("45.00 @ana @luka #internet") and is replayed as /loan.
+create table recurring
+(
+    id          varchar not null primary key,
+    chat_id     bigint not null,
+    creator     bigint not null references users (user_id),
+    -- daily, weekly or monthly
+    frequency   varchar not null,
+    -- weekday (1 = monday) or day of month, 0 for daily
+    day         integer not null,
+    loan        varchar not null,
+    paused      boolean not null default 0,
+    next_run    timestamp not null,
+    created     timestamp not null
+);
+
+create index recurring_chat_id on recurring (chat_id);
+create index recurring_next_run on recurring (next_run);
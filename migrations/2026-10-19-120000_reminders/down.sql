@@ -0,0 +1,3 @@
+drop table reminder_thresholds;
+drop table reminder_preferences;
+drop table reminders;
@@ -0,0 +1,29 @@
+-- Reminders about debt between two people of a chat, either because the
+-- loan is past its due date or because it grew over the chat's threshold
+create table reminders
+(
+    id          varchar not null primary key,
+    chat_id     bigint not null,
+    borrower    bigint not null references users (user_id),
+    owes        bigint not null references users (user_id),
+    due         timestamp,
+    last_sent   timestamp,
+    unique (chat_id, borrower, owes)
+);
+
+create index reminders_due on reminders (due);
+
+-- users that do not want reminders (for now or at all)
+create table reminder_preferences
+(
+    user_id         bigint not null primary key references users (user_id),
+    opted_out       boolean not null default 0,
+    snoozed_until   timestamp
+);
+
+-- debt (in cents) a chat tolerates before reminding
+create table reminder_thresholds
+(
+    chat_id     bigint not null primary key,
+    threshold   int not null
+);
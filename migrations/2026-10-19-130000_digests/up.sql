@@ -0,0 +1,18 @@
+-- Periodic balance summaries, at most one schedule per chat
+create table digests
+(
+    chat_id     bigint not null primary key,
+    -- daily, weekly or monthly (like recurring loans)
+    frequency   varchar not null,
+    day         integer not null,
+    -- local time of day, in minutes after midnight
+    minute      integer not null,
+    -- IANA name, e.g. Europe/Ljubljana
+    timezone    varchar not null,
+    -- UTC
+    next_run    timestamp not null,
+    last_sent   timestamp,
+    created     timestamp not null
+);
+
+create index digests_next_run on digests (next_run);
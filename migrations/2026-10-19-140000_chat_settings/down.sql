@@ -0,0 +1,2 @@
+drop table approvals;
+drop table chat_settings;
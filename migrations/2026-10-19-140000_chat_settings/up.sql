@@ -0,0 +1,28 @@
+-- Per-chat options, chats without a row use the defaults
+create table chat_settings
+(
+    chat_id             bigint not null primary key,
+    -- ISO 4217 code, e.g. EUR
+    currency            varchar not null default 'EUR',
+    -- IANA name, e.g. Europe/Ljubljana
+    timezone            varchar not null default 'UTC',
+    language            varchar not null default 'en',
+    auto_simplify       boolean not null default 1,
+    approval_required   boolean not null default 0,
+    -- auto, private or off
+    reminders           varchar not null default 'auto',
+    admin_only          boolean not null default 0
+);
+
+-- Loans and payments waiting for the counterparty to approve them
+create table approvals
+(
+    id          varchar not null primary key,
+    chat_id     bigint not null,
+    sender      bigint not null references users (user_id),
+    -- the command as it was sent
+    text        varchar not null,
+    created     timestamp not null
+);
+
+create index approvals_chat_id on approvals (chat_id);
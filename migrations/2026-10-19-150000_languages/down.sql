@@ -0,0 +1,2 @@
+update chat_settings set language = 'en' where language = 'auto';
+drop table user_settings;
@@ -0,0 +1,10 @@
+-- Language a user picked with /language, overrides the one of the chat
+create table user_settings
+(
+    user_id     bigint not null primary key references users (user_id),
+    -- ISO 639-1 code, e.g. sl
+    language    varchar not null
+);
+
+-- English was the only choice so far, let chats follow their users instead
+update chat_settings set language = 'auto' where language = 'en';
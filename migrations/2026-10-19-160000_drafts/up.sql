@@ -0,0 +1,11 @@
+-- Loans and payments with several numbers that could be the amount, waiting
+-- for their sender to pick one
+create table drafts
+(
+    id          varchar not null primary key,
+    chat_id     bigint not null,
+    sender      bigint not null references users (user_id),
+    -- the command as it was sent
+    text        varchar not null,
+    created     timestamp not null
+);
@@ -0,0 +1,24 @@
+-- debt of events is dropped, only the everyday debt is kept
+alter table ledgers rename to ledgers_old;
+
+create table ledgers
+(
+    id          varchar not null primary key,
+    borrower    bigint not null references users (user_id),
+    owes        bigint not null references users (user_id),
+    sum         int not null,
+    unique (borrower, owes)
+);
+
+insert into ledgers (id, borrower, owes, sum)
+select id, borrower, owes, sum
+from ledgers_old
+where event_id is null;
+
+drop table ledgers_old;
+
+create index ledgers_owes on ledgers (owes);
+
+delete from transactions where event_id is not null;
+alter table transactions drop column event_id;
+drop table events;
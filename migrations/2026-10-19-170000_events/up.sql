@@ -0,0 +1,39 @@
+-- Trips, parties and the like: debt of their own, kept apart from the
+-- everyday debt of the chat
+create table events
+(
+    id          varchar not null primary key,
+    chat_id     bigint not null,
+    name        varchar not null,
+    created     timestamp not null,
+    -- set once the event is settled and archived
+    closed      timestamp,
+    -- new transactions of the chat go to its active event
+    active      boolean not null default 0
+);
+
+create index events_chat_id on events (chat_id);
+
+-- null for everyday transactions
+alter table transactions add column event_id varchar references events (id);
+
+-- a pair of users has a ledger for everyday debt and one per event
+alter table ledgers rename to ledgers_old;
+
+create table ledgers
+(
+    id          varchar not null primary key,
+    borrower    bigint not null references users (user_id),
+    owes        bigint not null references users (user_id),
+    sum         int not null,
+    event_id    varchar references events (id)
+);
+
+insert into ledgers (id, borrower, owes, sum)
+select id, borrower, owes, sum
+from ledgers_old;
+
+drop table ledgers_old;
+
+create unique index ledgers_pair on ledgers (borrower, owes, coalesce(event_id, ''));
+create index ledgers_owes on ledgers (owes);
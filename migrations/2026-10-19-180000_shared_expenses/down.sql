@@ -0,0 +1,3 @@
+alter table transactions drop column expense_id;
+drop table expense_shares;
+drop table shared_expenses;
@@ -0,0 +1,26 @@
+-- Bills paid by several people and shared by several people, recorded as
+-- the loans that even them out
+create table shared_expenses
+(
+    id          varchar not null primary key,
+    chat_id     bigint not null,
+    description varchar not null,
+    created     timestamp not null
+);
+
+-- who paid how much of a shared expense and what their share of it is
+create table expense_shares
+(
+    expense_id  varchar not null references shared_expenses (id),
+    user_id     bigint not null references users (user_id),
+    paid        int not null,
+    share       int not null,
+    -- order in which people were mentioned
+    position    int not null,
+    primary key (expense_id, user_id)
+);
+
+create index shared_expenses_chat_id on shared_expenses (chat_id);
+
+-- the loans of a shared expense point to it, null for everything else
+alter table transactions add column expense_id varchar references shared_expenses (id);
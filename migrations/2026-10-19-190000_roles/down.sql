@@ -0,0 +1,2 @@
+alter table chat_settings drop column record_for_others;
+alter table chats drop column role;
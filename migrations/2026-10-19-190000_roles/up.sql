@@ -0,0 +1,6 @@
+-- What a member may do in a chat: owner, admin, member or readonly.
+-- Telegram's owner and admins of a group get their role on their next message.
+alter table chats add column role varchar not null default 'member';
+
+-- Members may record transactions they don't take part in
+alter table chat_settings add column record_for_others boolean not null default 0;
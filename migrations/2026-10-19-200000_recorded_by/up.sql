@@ -0,0 +1,3 @@
+-- Who entered the transaction, when it is not the payer ("/loan 30 @luka by:@ana").
+-- Unknown for transactions recorded before this was kept.
+alter table transactions add column recorded_by bigint references users (user_id);
@@ -0,0 +1,7 @@
+-- Tokens the HTTP API accepts, one per chat, issued with /apitoken
+create table api_tokens
+(
+    chat_id     bigint not null primary key,
+    token       varchar not null unique,
+    issued      timestamp not null
+);
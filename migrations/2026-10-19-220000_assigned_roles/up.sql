@@ -0,0 +1,3 @@
+-- Roles given with /role stay as they are, the ones taken over from
+-- Telegram's owner and admins go with them
+alter table chats add column role_assigned boolean not null default 0;
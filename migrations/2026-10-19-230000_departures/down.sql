@@ -0,0 +1,2 @@
+drop table forgotten_users;
+alter table chats drop column has_left;
@@ -0,0 +1,10 @@
+-- People who left a chat keep their row in it, so writing there doesn't
+-- register them again. Only /register or joining the group brings them back.
+alter table chats add column has_left boolean not null default 0;
+
+-- The same for people who asked to be forgotten: nothing but their
+-- Telegram id is kept, and nothing refers to it
+create table forgotten_users
+(
+    user_id     bigint not null primary key
+);
@@ -0,0 +1,7 @@
+drop table api_tokens;
+create table api_tokens
+(
+    chat_id     bigint not null primary key,
+    token       varchar not null unique,
+    issued      timestamp not null
+);
@@ -0,0 +1,10 @@
+-- Only the SHA-256 hash of a token is kept, the token itself is shown once
+-- when it is issued. Tokens stored so far can't be hashed here, they have
+-- to be issued again with /apitoken.
+drop table api_tokens;
+create table api_tokens
+(
+    chat_id     bigint not null primary key,
+    token_hash  varchar not null unique,
+    issued      timestamp not null
+);
@@ -1,162 +1,1073 @@
 
 pub mod ledger_operations {
-    use diesel::{QueryDsl, result::Error, insert_into};
+    use diesel::{QueryDsl, insert_into};
     use diesel::prelude::*;
+    use diesel::SqliteConnection;
     use teloxide::types::{UserId};
     use crate::types::user::User;
     use crate::types::{schema::ledgers::dsl::*, ledger::Ledger};
-    use crate::helpers::data_handler::sqlite_operations::establish_connection;
+    use crate::types::schema::events;
+    use crate::types::error::TrackerError;
     use crate::types::ledger::{SqliteLedger, NewLedger};
 
-    pub fn get_ledger(bor: &UserId, owe: &UserId) -> Result<Vec<Ledger>, Error> {
-        let connection = establish_connection();
-        
-        let bow_string = serde_json::to_string(&bor).unwrap();
-        let owe_string = serde_json::to_string(&owe).unwrap();
-        
-        let ledger = ledgers
-            .filter(borrower.eq(bow_string))
-            .filter(owes.eq(owe_string))
-            .load::<SqliteLedger>(&connection)?;
-        Ok(ledger.into_iter().map(Ledger::from).collect())
+    /// Debt of `bor` to `owe` in the event, or the everyday one
+    pub fn get_ledger(conn: &SqliteConnection, bor: &UserId, owe: &UserId, event: Option<&str>) -> Result<Vec<Ledger>, TrackerError> {
+        let query = ledgers
+            .filter(borrower.eq(bor.0 as i64))
+            .filter(owes.eq(owe.0 as i64));
+        let ledger = match event {
+            Some(event) => query.filter(event_id.eq(event)).load::<SqliteLedger>(conn)?,
+            None => query.filter(event_id.is_null()).load::<SqliteLedger>(conn)?,
+        };
+        ledger.into_iter().map(Ledger::try_from).collect()
     }
     
-    pub fn update_ledger(ledger: Ledger) -> Result<Ledger, Error> {
+    pub fn update_ledger(conn: &SqliteConnection, ledger: Ledger) -> Result<Ledger, TrackerError> {
         let sqlite_ledger = SqliteLedger::from(ledger);
-        let conn = establish_connection();
-        let updated_ledger: SqliteLedger = sqlite_ledger.save_changes::<SqliteLedger>(&conn)?;
-        Ok(Ledger::from(updated_ledger))
+        let updated_ledger: SqliteLedger = sqlite_ledger.save_changes::<SqliteLedger>(conn)?;
+        Ledger::try_from(updated_ledger)
     }
     
-    pub fn insert_ledger(new_ledger: NewLedger) -> Result<Ledger, Error> {
+    pub fn insert_ledger(conn: &SqliteConnection, new_ledger: NewLedger) -> Result<Ledger, TrackerError> {
         let sqlite_ledger = SqliteLedger::from(new_ledger);
-        let conn = establish_connection();
         let _ = insert_into(ledgers)
             .values(&sqlite_ledger)
-            .execute(&conn)?;
-        Ok(Ledger::from(sqlite_ledger))
+            .execute(conn)?;
+        Ledger::try_from(sqlite_ledger)
     }
 
-    pub fn get_group_ledgers(group: &Vec<User>) -> Result<Vec<Ledger>, Error> {
-        let conn = establish_connection();
-        let user_ids: Vec<String> = group.into_iter().map(|u| u.user_id.to_string().clone()).collect();
+    /// Debt between members of the group in the event, or the everyday one
+    pub fn get_group_ledgers(conn: &SqliteConnection, group: &[User], event: Option<&str>) -> Result<Vec<Ledger>, TrackerError> {
+        let user_ids: Vec<i64> = group.iter().map(|u| u.user_id.0 as i64).collect();
+        let query = ledgers
+            .filter(borrower.eq_any(user_ids.clone()).and(owes.eq_any(user_ids)));
+        let sqlite_ledgers = match event {
+            Some(event) => query.filter(event_id.eq(event)).load::<SqliteLedger>(conn)?,
+            None => query.filter(event_id.is_null()).load::<SqliteLedger>(conn)?,
+        };
+        sqlite_ledgers.into_iter().map(Ledger::try_from).collect()
+    }
+
+    /// Debt of the user and to the user, of every chat and of the events
+    /// still open (what was left of a closed event is everyday debt now)
+    pub fn get_user_ledgers(conn: &SqliteConnection, uid: &UserId) -> Result<Vec<Ledger>, TrackerError> {
+        let closed_events: Vec<String> = events::table
+            .filter(events::closed.is_not_null())
+            .select(events::id)
+            .load(conn)?;
         let sqlite_ledgers = ledgers
-            .filter(borrower.eq_any(user_ids.clone()).and(owes.eq_any(user_ids)))
-            .load::<SqliteLedger>(&conn)?;
-        Ok(sqlite_ledgers.into_iter().map(Ledger::from).collect())
+            .filter(borrower.eq(uid.0 as i64).or(owes.eq(uid.0 as i64)))
+            .load::<SqliteLedger>(conn)?;
+        sqlite_ledgers.into_iter()
+            .filter(|ledger| ledger.event_id.as_ref().is_none_or(|event| !closed_events.contains(event)))
+            .map(Ledger::try_from)
+            .collect()
     }
 }
     
 pub mod transaction_operations {
-    use diesel::{result::Error, insert_into};
+    use diesel::insert_into;
     use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::UserId;
     use crate::types::transaction::{NewTransaction, Transaction, SqliteTransaction};
-    use crate::helpers::data_handler::sqlite_operations::establish_connection;
+    use crate::types::error::TrackerError;
     use crate::types::schema::transactions::dsl::*;
+    use crate::types::user::User;
+    use super::category_operations::insert_transaction_categories;
     
-    pub fn insert_transaction(new_transaction: NewTransaction) -> Result<Transaction, Error>  {
+    pub fn insert_transaction(conn: &SqliteConnection, mut new_transaction: NewTransaction) -> Result<Transaction, TrackerError> {
+        let categories = std::mem::take(&mut new_transaction.categories);
         let sqlite_transaction = SqliteTransaction::from(new_transaction);
-        let conn = establish_connection();
         let _ = insert_into(transactions)
             .values(&sqlite_transaction)
-            .execute(&conn)?;
-        Ok(Transaction::from(sqlite_transaction))
+            .execute(conn)?;
+        insert_transaction_categories(conn, &sqlite_transaction.id, &categories)?;
+        Transaction::try_from(sqlite_transaction)
+    }
+
+    /// Transactions between members of the group, oldest first
+    pub fn get_group_transactions(conn: &SqliteConnection, group: &[User]) -> Result<Vec<Transaction>, TrackerError> {
+        let user_ids: Vec<i64> = group.iter().map(|u| u.user_id.0 as i64).collect();
+        let sqlite_transactions = transactions
+            .filter(initiator.eq_any(user_ids.clone()).and(reciever.eq_any(user_ids)))
+            .order(created.asc())
+            .load::<SqliteTransaction>(conn)?;
+        sqlite_transactions.into_iter().map(Transaction::try_from).collect()
+    }
+
+    /// Transactions the user sent or received, in any chat, oldest first
+    pub fn get_user_transactions(conn: &SqliteConnection, uid: &UserId) -> Result<Vec<Transaction>, TrackerError> {
+        let sqlite_transactions = transactions
+            .filter(initiator.eq(uid.0 as i64).or(reciever.eq(uid.0 as i64)))
+            .order(created.asc())
+            .load::<SqliteTransaction>(conn)?;
+        sqlite_transactions.into_iter().map(Transaction::try_from).collect()
     }
 }
 
 pub mod user_operations {
-    use diesel::{result::Error, insert_into};
+    use diesel::insert_into;
     use diesel::prelude::*;
+    use diesel::SqliteConnection;
     use teloxide::types::UserId;
-    use crate::helpers::data_handler::sqlite_operations::establish_connection;
+    use crate::types::error::TrackerError;
     use crate::types::schema::users::dsl::*;
-    use crate::types::user::{User, SqliteUser, NewUser};
+    use crate::types::schema::{
+        approvals,
+        chats,
+        drafts,
+        expense_shares,
+        forgotten_users,
+        ledgers,
+        recurring,
+        reminder_preferences,
+        reminders,
+        transactions,
+        user_settings
+    };
+    use crate::types::user::{User, SqliteUser, NewUser, FORGOTTEN_USER_IDS};
     
-    pub fn get_user_by_user_id(query_id: &UserId) -> Result<Vec<User>, Error> {
-        let conn = establish_connection();
+    pub fn get_user_by_user_id(conn: &SqliteConnection, query_id: &UserId) -> Result<Vec<User>, TrackerError> {
         let resp = users
-            .filter(user_id.eq(query_id.to_string()))
-            .load::<SqliteUser>(&conn)?;
-        Ok(resp.into_iter().map(User::from).collect())
+            .filter(user_id.eq(query_id.0 as i64))
+            .load::<SqliteUser>(conn)?;
+        resp.into_iter().map(User::try_from).collect()
+    }
+
+    /// Whether the user asked to be forgotten at some point
+    pub fn is_forgotten(conn: &SqliteConnection, query_id: &UserId) -> Result<bool, TrackerError> {
+        let resp = forgotten_users::table
+            .filter(forgotten_users::user_id.eq(query_id.0 as i64))
+            .count()
+            .get_result::<i64>(conn)?;
+        Ok(resp > 0)
+    }
+
+    /// Same as `get_user_by_user_id`, but the user has to exist (exactly once)
+    pub fn get_registered_user(conn: &SqliteConnection, query_id: &UserId) -> Result<User, TrackerError> {
+        let mut found = get_user_by_user_id(conn, query_id)?;
+        match found.len() {
+            0 => Err(TrackerError::NotRegistered),
+            1 => found.pop().ok_or(TrackerError::NotRegistered),
+            n => Err(TrackerError::Consistency(format!("{} registered users with id {}", n, query_id))),
+        }
     }
     
-    pub fn get_user_by_username(query_username: String) -> Result<Vec<User>, Error> {
-        let conn = establish_connection();
+    sql_function!(fn lower(text: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+    /// Telegram usernames don't depend on case, "@Luka" finds luka
+    pub fn get_user_by_username(conn: &SqliteConnection, query_username: String) -> Result<Vec<User>, TrackerError> {
         let resp = users
-            .filter(name.eq(query_username))
-            .load::<SqliteUser>(&conn)?;
-        Ok(resp.into_iter().map(User::from).collect())
+            .filter(lower(name).eq(query_username.to_lowercase()))
+            .load::<SqliteUser>(conn)?;
+        resp.into_iter().map(User::try_from).collect()
     }
     
-    pub fn insert_user(new_user: NewUser) -> Result<User, Error>  {
+    pub fn insert_user(conn: &SqliteConnection, new_user: NewUser) -> Result<User, TrackerError> {
         let sqlite_user = SqliteUser::from(new_user);
-        let conn = establish_connection();
         let _ = insert_into(users)
             .values(&sqlite_user)
-            .execute(&conn)?;
-        Ok(User::from(sqlite_user))
+            .execute(conn)?;
+        User::try_from(sqlite_user)
     }
     
-    pub fn update_user(user: User) -> Result<User, Error> {
+    pub fn update_user(conn: &SqliteConnection, user: User) -> Result<User, TrackerError> {
         let sqlite_user = SqliteUser::from(user);
-        let conn = establish_connection();
-        let updated_user: SqliteUser = sqlite_user.save_changes::<SqliteUser>(&conn)?;
-        Ok(User::from(updated_user))
+        let updated_user: SqliteUser = sqlite_user.save_changes::<SqliteUser>(conn)?;
+        User::try_from(updated_user)
+    }
+
+    /// Replaces the user with a nameless one under a new id in their debt,
+    /// transactions and shared expenses, everything else about them (chats,
+    /// settings, reminders, recurring loans, pending requests) is deleted.
+    /// Only their id is kept, as one of the forgotten.
+    pub fn forget_user(conn: &SqliteConnection, uid: &UserId) -> Result<User, TrackerError> {
+        let old = uid.0 as i64;
+        conn.transaction::<_, TrackerError, _>(|| {
+            let last: Option<i64> = users
+                .filter(user_id.ge(FORGOTTEN_USER_IDS))
+                .select(diesel::dsl::max(user_id))
+                .first(conn)?;
+            let anonymous = insert_user(conn, NewUser {
+                user_id: UserId(last.map_or(FORGOTTEN_USER_IDS, |last| last + 1) as u64),
+                username: "".to_string(),
+            })?;
+            let new = anonymous.user_id.0 as i64;
+            diesel::update(ledgers::table.filter(ledgers::borrower.eq(old))).set(ledgers::borrower.eq(new)).execute(conn)?;
+            diesel::update(ledgers::table.filter(ledgers::owes.eq(old))).set(ledgers::owes.eq(new)).execute(conn)?;
+            diesel::update(transactions::table.filter(transactions::initiator.eq(old)))
+                .set(transactions::initiator.eq(new))
+                .execute(conn)?;
+            diesel::update(transactions::table.filter(transactions::reciever.eq(old)))
+                .set(transactions::reciever.eq(new))
+                .execute(conn)?;
+            diesel::update(transactions::table.filter(transactions::recorded_by.eq(old)))
+                .set(transactions::recorded_by.eq(new))
+                .execute(conn)?;
+            diesel::update(expense_shares::table.filter(expense_shares::user_id.eq(old)))
+                .set(expense_shares::user_id.eq(new))
+                .execute(conn)?;
+            diesel::delete(chats::table.filter(chats::user_id.eq(old))).execute(conn)?;
+            // so writing in a chat doesn't register them again
+            diesel::replace_into(forgotten_users::table).values(forgotten_users::user_id.eq(old)).execute(conn)?;
+            diesel::delete(user_settings::table.filter(user_settings::user_id.eq(old))).execute(conn)?;
+            diesel::delete(reminder_preferences::table.filter(reminder_preferences::user_id.eq(old))).execute(conn)?;
+            diesel::delete(reminders::table.filter(reminders::borrower.eq(old).or(reminders::owes.eq(old)))).execute(conn)?;
+            diesel::delete(recurring::table.filter(recurring::creator.eq(old))).execute(conn)?;
+            diesel::delete(approvals::table.filter(approvals::sender.eq(old))).execute(conn)?;
+            diesel::delete(drafts::table.filter(drafts::sender.eq(old))).execute(conn)?;
+            // what others wrote keeps their @name, so it goes as well
+            let forgotten: String = users.filter(user_id.eq(old)).select(name).first(conn)?;
+            let mentioning = |rows: Vec<(String, String)>| -> Vec<String> {
+                rows.into_iter().filter(|(_, text)| mentions(text, &forgotten)).map(|(row, _)| row).collect()
+            };
+            let recurring_ids = mentioning(recurring::table.select((recurring::id, recurring::loan)).load(conn)?);
+            diesel::delete(recurring::table.filter(recurring::id.eq_any(recurring_ids))).execute(conn)?;
+            let approval_ids = mentioning(approvals::table.select((approvals::id, approvals::text)).load(conn)?);
+            diesel::delete(approvals::table.filter(approvals::id.eq_any(approval_ids))).execute(conn)?;
+            let draft_ids = mentioning(drafts::table.select((drafts::id, drafts::text)).load(conn)?);
+            diesel::delete(drafts::table.filter(drafts::id.eq_any(draft_ids))).execute(conn)?;
+            diesel::delete(users.filter(user_id.eq(old))).execute(conn)?;
+            Ok(anonymous)
+        })
+    }
+
+    /// Whether the text mentions the user, "@luka" or "by:@Luka" but not
+    /// "@lukas"
+    fn mentions(text: &str, username: &str) -> bool {
+        !username.is_empty() && text.split('@').skip(1).any(|rest| {
+            let mentioned: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            mentioned.eq_ignore_ascii_case(username)
+        })
     }
 }
 
 pub mod chat_operations {
-    use diesel::{result::Error, insert_into};
+    use diesel::insert_into;
     use diesel::prelude::*;
+    use diesel::SqliteConnection;
     use teloxide::types::{UserId, ChatId};
     use crate::types::chat::{Chat, NewChat, SqliteChat};
-    use crate::helpers::data_handler::sqlite_operations::establish_connection;
+    use crate::types::role::Role;
+    use crate::types::error::TrackerError;
     use crate::types::schema::chats::dsl::*;
     use crate::types::schema::users::dsl::user_id as uid;
     use crate::types::schema::users::dsl::users;
     use crate::types::user::{SqliteUser, User};
     
-    pub fn insert_user_into_room(reference_user_id: &UserId, cid: &ChatId) -> Result<Chat, Error> {
+    /// Makes the user a member of the chat, afresh if they left it before
+    pub fn insert_user_into_room(conn: &SqliteConnection, reference_user_id: &UserId, cid: &ChatId, member_role: Role) -> Result<Chat, TrackerError> {
         let sqlite_chat = SqliteChat::from(NewChat {
             user_id: *reference_user_id,
-            chat_id: *cid
+            chat_id: *cid,
+            role: member_role,
         });
-        let conn = establish_connection();
-        let _ = insert_into(chats)
-            .values(&sqlite_chat)
-            .execute(&conn)?;
-        Ok(Chat::from(sqlite_chat))
+        conn.transaction::<_, TrackerError, _>(|| {
+            diesel::delete(chats)
+                .filter(user_id.eq(reference_user_id.0 as i64))
+                .filter(chat_id.eq(cid.0))
+                .filter(has_left.eq(true))
+                .execute(conn)?;
+            let _ = insert_into(chats)
+                .values(&sqlite_chat)
+                .execute(conn)?;
+            Ok(())
+        })?;
+        Chat::try_from(sqlite_chat)
     }
 
-    pub fn is_user_in_chat(reference_user_id: UserId, cid: ChatId) -> Result<bool, Error> {
-        let conn = establish_connection();
+    pub fn is_user_in_chat(conn: &SqliteConnection, reference_user_id: UserId, cid: ChatId) -> Result<bool, TrackerError> {
         let resp = chats
-            .filter(user_id.eq(reference_user_id.to_string()))
-            .filter(chat_id.eq(cid.to_string()))
-            .load::<SqliteChat>(&conn)?;
+            .filter(user_id.eq(reference_user_id.0 as i64))
+            .filter(chat_id.eq(cid.0))
+            .filter(has_left.eq(false))
+            .load::<SqliteChat>(conn)?;
         Ok(!resp.is_empty())
     }
 
-    pub fn get_chat_users(cid: &ChatId) -> Result<Vec<User>, Error> {
-        let conn = establish_connection();
+    pub fn get_chat_users(conn: &SqliteConnection, cid: &ChatId) -> Result<Vec<User>, TrackerError> {
         let chat_rows = chats
-            .filter(chat_id.eq(cid.to_string()))
-            .load::<SqliteChat>(&conn)?;
-        let user_ids: Vec<String> = chat_rows.into_iter().map(|cr| cr.user_id).collect();
+            .filter(chat_id.eq(cid.0))
+            .filter(has_left.eq(false))
+            .load::<SqliteChat>(conn)?;
+        let user_ids: Vec<i64> = chat_rows.into_iter().map(|cr| cr.user_id).collect();
         let usrs = users
             .filter(uid.eq_any(user_ids))
-            .load::<SqliteUser>(&conn)?;
-        Ok(usrs.into_iter().map(User::from).collect())
+            .load::<SqliteUser>(conn)?;
+        usrs.into_iter().map(User::try_from).collect()
+    }
+
+    /// Membership of the user in the chat, with their role
+    pub fn get_chat_member(conn: &SqliteConnection, reference_user_id: &UserId, cid: &ChatId) -> Result<Option<Chat>, TrackerError> {
+        let resp = chats
+            .filter(user_id.eq(reference_user_id.0 as i64))
+            .filter(chat_id.eq(cid.0))
+            .filter(has_left.eq(false))
+            .load::<SqliteChat>(conn)?;
+        resp.into_iter().next().map(Chat::try_from).transpose()
+    }
+
+    /// Memberships of everybody in the chat
+    pub fn get_chat_members(conn: &SqliteConnection, cid: &ChatId) -> Result<Vec<Chat>, TrackerError> {
+        let resp = chats
+            .filter(chat_id.eq(cid.0))
+            .filter(has_left.eq(false))
+            .load::<SqliteChat>(conn)?;
+        resp.into_iter().map(Chat::try_from).collect()
+    }
+
+    pub fn update_chat_member(conn: &SqliteConnection, member: Chat) -> Result<Chat, TrackerError> {
+        let sqlite_chat = SqliteChat::from(member);
+        let updated: SqliteChat = sqlite_chat.save_changes::<SqliteChat>(conn)?;
+        Chat::try_from(updated)
+    }
+
+    /// Memberships of the user in every chat
+    pub fn get_user_chats(conn: &SqliteConnection, reference_user_id: &UserId) -> Result<Vec<Chat>, TrackerError> {
+        let resp = chats
+            .filter(user_id.eq(reference_user_id.0 as i64))
+            .filter(has_left.eq(false))
+            .load::<SqliteChat>(conn)?;
+        resp.into_iter().map(Chat::try_from).collect()
+    }
+
+    /// The user stops being a member, the row remembers that they left
+    pub fn remove_user_from_room(conn: &SqliteConnection, reference_user_id: &UserId, cid: &ChatId) -> Result<(), TrackerError> {
+        diesel::update(chats)
+            .filter(user_id.eq(reference_user_id.0 as i64))
+            .filter(chat_id.eq(cid.0))
+            .set(has_left.eq(true))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Chats the user left or was forgotten in
+    pub fn get_left_chats(conn: &SqliteConnection, reference_user_id: &UserId) -> Result<Vec<ChatId>, TrackerError> {
+        let resp = chats
+            .filter(user_id.eq(reference_user_id.0 as i64))
+            .filter(has_left.eq(true))
+            .select(chat_id)
+            .load::<i64>(conn)?;
+        Ok(resp.into_iter().map(ChatId).collect())
+    }
+}
+
+pub mod category_operations {
+    use std::collections::HashMap;
+    use diesel::insert_into;
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::ChatId;
+    use uuid::Uuid;
+    use crate::types::category::{
+        Category,
+        CategoryAlias,
+        NewCategory,
+        SqliteCategory,
+        SqliteCategoryAlias,
+        SqliteTransactionCategory
+    };
+    use crate::types::error::TrackerError;
+    use crate::types::schema::{categories, category_aliases, transaction_categories};
+
+    pub fn get_chat_categories(conn: &SqliteConnection, cid: &ChatId) -> Result<Vec<Category>, TrackerError> {
+        let resp = categories::table
+            .filter(categories::chat_id.eq(cid.0))
+            .order(categories::name.asc())
+            .load::<SqliteCategory>(conn)?;
+        Ok(resp.into_iter().map(Category::from).collect())
+    }
+
+    pub fn get_chat_aliases(conn: &SqliteConnection, cid: &ChatId) -> Result<Vec<CategoryAlias>, TrackerError> {
+        let resp = category_aliases::table
+            .filter(category_aliases::chat_id.eq(cid.0))
+            .order(category_aliases::alias.asc())
+            .load::<SqliteCategoryAlias>(conn)?;
+        Ok(resp.into_iter().map(CategoryAlias::from).collect())
+    }
+
+    /// Category of the chat with the given name or alias
+    pub fn find_category(conn: &SqliteConnection, cid: &ChatId, name_or_alias: &str) -> Result<Option<Category>, TrackerError> {
+        let by_name = categories::table
+            .filter(categories::chat_id.eq(cid.0))
+            .filter(categories::name.eq(name_or_alias))
+            .load::<SqliteCategory>(conn)?;
+        if let Some(category) = by_name.into_iter().next() {
+            return Ok(Some(category.into()));
+        }
+        let by_alias = category_aliases::table
+            .inner_join(categories::table)
+            .filter(category_aliases::chat_id.eq(cid.0))
+            .filter(category_aliases::alias.eq(name_or_alias))
+            .select(categories::all_columns)
+            .load::<SqliteCategory>(conn)?;
+        Ok(by_alias.into_iter().next().map(Category::from))
+    }
+
+    pub fn insert_category(conn: &SqliteConnection, new_category: NewCategory) -> Result<Category, TrackerError> {
+        let sqlite_category = SqliteCategory::from(new_category);
+        let _ = insert_into(categories::table)
+            .values(&sqlite_category)
+            .execute(conn)?;
+        Ok(sqlite_category.into())
+    }
+
+    pub fn insert_category_alias(conn: &SqliteConnection, category: &Category, alias: &str) -> Result<CategoryAlias, TrackerError> {
+        let sqlite_alias = SqliteCategoryAlias {
+            id: Uuid::new_v4().to_string(),
+            category_id: category.id.clone(),
+            chat_id: category.chat_id.0,
+            alias: alias.to_string(),
+        };
+        let _ = insert_into(category_aliases::table)
+            .values(&sqlite_alias)
+            .execute(conn)?;
+        Ok(sqlite_alias.into())
+    }
+
+    /// Resolves hashtags to categories of the chat, unknown ones are created.
+    /// Tags meaning the same category only count once.
+    pub fn get_or_insert_categories(conn: &SqliteConnection, cid: &ChatId, tags: &[String]) -> Result<Vec<Category>, TrackerError> {
+        let mut resolved: Vec<Category> = vec![];
+        for tag in tags.iter() {
+            let category = match find_category(conn, cid, tag)? {
+                Some(category) => category,
+                None => insert_category(conn, NewCategory { chat_id: *cid, name: tag.clone() })?,
+            };
+            if !resolved.contains(&category) {
+                resolved.push(category);
+            }
+        }
+        Ok(resolved)
+    }
+
+    pub fn insert_transaction_categories(conn: &SqliteConnection, transaction_id: &str, tagged: &[Category]) -> Result<(), TrackerError> {
+        let rows: Vec<SqliteTransactionCategory> = tagged.iter()
+            .enumerate()
+            .map(|(position, category)| SqliteTransactionCategory {
+                transaction_id: transaction_id.to_string(),
+                category_id: category.id.clone(),
+                position: position as i32,
+            })
+            .collect();
+        if !rows.is_empty() {
+            let _ = insert_into(transaction_categories::table)
+                .values(&rows)
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Categories of every tagged transaction in the chat (by transaction
+    /// id), main category first
+    pub fn get_transaction_categories(conn: &SqliteConnection, cid: &ChatId) -> Result<HashMap<String, Vec<Category>>, TrackerError> {
+        let rows = transaction_categories::table
+            .inner_join(categories::table)
+            .filter(categories::chat_id.eq(cid.0))
+            .order(transaction_categories::position.asc())
+            .load::<(SqliteTransactionCategory, SqliteCategory)>(conn)?;
+        let mut tagged: HashMap<String, Vec<Category>> = HashMap::new();
+        for (link, category) in rows.into_iter() {
+            tagged.entry(link.transaction_id).or_default().push(category.into());
+        }
+        Ok(tagged)
+    }
+}
+
+pub mod recurring_operations {
+    use chrono::NaiveDateTime;
+    use diesel::{delete, insert_into};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::ChatId;
+    use crate::types::error::TrackerError;
+    use crate::types::recurring::{NewRecurring, Recurring, SqliteRecurring};
+    use crate::types::schema::recurring::dsl::*;
+
+    pub fn insert_recurring(conn: &SqliteConnection, new_recurring: NewRecurring) -> Result<Recurring, TrackerError> {
+        let sqlite_recurring = SqliteRecurring::from(new_recurring);
+        let _ = insert_into(recurring)
+            .values(&sqlite_recurring)
+            .execute(conn)?;
+        Recurring::try_from(sqlite_recurring)
+    }
+
+    pub fn update_recurring(conn: &SqliteConnection, updated: Recurring) -> Result<Recurring, TrackerError> {
+        let sqlite_recurring = SqliteRecurring::from(updated);
+        let saved: SqliteRecurring = sqlite_recurring.save_changes::<SqliteRecurring>(conn)?;
+        Recurring::try_from(saved)
+    }
+
+    pub fn delete_recurring(conn: &SqliteConnection, recurring_id: &str) -> Result<(), TrackerError> {
+        let _ = delete(recurring.filter(id.eq(recurring_id))).execute(conn)?;
+        Ok(())
+    }
+
+    /// Recurring loans of the chat, oldest first
+    pub fn get_chat_recurring(conn: &SqliteConnection, cid: &ChatId) -> Result<Vec<Recurring>, TrackerError> {
+        let resp = recurring
+            .filter(chat_id.eq(cid.0))
+            .order(created.asc())
+            .load::<SqliteRecurring>(conn)?;
+        resp.into_iter().map(Recurring::try_from).collect()
+    }
+
+    /// Active recurring loans whose next run is not in the future
+    pub fn get_due_recurring(conn: &SqliteConnection, now: NaiveDateTime) -> Result<Vec<Recurring>, TrackerError> {
+        let resp = recurring
+            .filter(paused.eq(false))
+            .filter(next_run.le(now))
+            .order(next_run.asc())
+            .load::<SqliteRecurring>(conn)?;
+        resp.into_iter().map(Recurring::try_from).collect()
+    }
+}
+
+pub mod reminder_operations {
+    use chrono::NaiveDateTime;
+    use diesel::{delete, insert_into, replace_into};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::{ChatId, UserId};
+    use crate::types::error::TrackerError;
+    use crate::types::reminder::{
+        NewReminder,
+        Reminder,
+        ReminderPreference,
+        SqliteReminder,
+        SqliteReminderPreference,
+        SqliteReminderThreshold
+    };
+    use crate::types::schema::{reminder_preferences, reminder_thresholds, reminders};
+
+    pub fn get_reminder(conn: &SqliteConnection, cid: &ChatId, bor: &UserId, owe: &UserId) -> Result<Option<Reminder>, TrackerError> {
+        let resp = reminders::table
+            .filter(reminders::chat_id.eq(cid.0))
+            .filter(reminders::borrower.eq(bor.0 as i64))
+            .filter(reminders::owes.eq(owe.0 as i64))
+            .load::<SqliteReminder>(conn)?;
+        resp.into_iter().next().map(Reminder::try_from).transpose()
+    }
+
+    /// Reminders of loans whose due date has passed
+    pub fn get_overdue_reminders(conn: &SqliteConnection, now: NaiveDateTime) -> Result<Vec<Reminder>, TrackerError> {
+        let resp = reminders::table
+            .filter(reminders::due.le(now))
+            .order(reminders::due.asc())
+            .load::<SqliteReminder>(conn)?;
+        resp.into_iter().map(Reminder::try_from).collect()
+    }
+
+    pub fn insert_reminder(conn: &SqliteConnection, new_reminder: NewReminder) -> Result<Reminder, TrackerError> {
+        let sqlite_reminder = SqliteReminder::from(new_reminder);
+        let _ = insert_into(reminders::table)
+            .values(&sqlite_reminder)
+            .execute(conn)?;
+        Reminder::try_from(sqlite_reminder)
+    }
+
+    pub fn update_reminder(conn: &SqliteConnection, reminder: Reminder) -> Result<Reminder, TrackerError> {
+        let sqlite_reminder = SqliteReminder::from(reminder);
+        let updated: SqliteReminder = sqlite_reminder.save_changes::<SqliteReminder>(conn)?;
+        Reminder::try_from(updated)
+    }
+
+    /// Forgets about the debt of `bor` to `owe` in every chat (it was payed)
+    pub fn delete_reminders(conn: &SqliteConnection, bor: &UserId, owe: &UserId) -> Result<(), TrackerError> {
+        let _ = delete(reminders::table
+            .filter(reminders::borrower.eq(bor.0 as i64))
+            .filter(reminders::owes.eq(owe.0 as i64)))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    pub fn get_reminder_preference(conn: &SqliteConnection, uid: &UserId) -> Result<ReminderPreference, TrackerError> {
+        let resp = reminder_preferences::table
+            .filter(reminder_preferences::user_id.eq(uid.0 as i64))
+            .load::<SqliteReminderPreference>(conn)?;
+        match resp.into_iter().next() {
+            Some(preference) => ReminderPreference::try_from(preference),
+            None => Ok(ReminderPreference { user_id: *uid, opted_out: false, snoozed_until: None }),
+        }
+    }
+
+    pub fn save_reminder_preference(conn: &SqliteConnection, preference: ReminderPreference) -> Result<ReminderPreference, TrackerError> {
+        let sqlite_preference = SqliteReminderPreference::from(preference);
+        let _ = replace_into(reminder_preferences::table)
+            .values(&sqlite_preference)
+            .execute(conn)?;
+        ReminderPreference::try_from(sqlite_preference)
+    }
+
+    /// Thresholds (in cents) of all chats that set one
+    pub fn get_reminder_thresholds(conn: &SqliteConnection) -> Result<Vec<(ChatId, i32)>, TrackerError> {
+        let resp = reminder_thresholds::table
+            .load::<SqliteReminderThreshold>(conn)?;
+        Ok(resp.into_iter().map(|t| (ChatId(t.chat_id), t.threshold)).collect())
+    }
+
+    pub fn get_reminder_threshold(conn: &SqliteConnection, cid: &ChatId) -> Result<Option<i32>, TrackerError> {
+        let resp = reminder_thresholds::table
+            .filter(reminder_thresholds::chat_id.eq(cid.0))
+            .load::<SqliteReminderThreshold>(conn)?;
+        Ok(resp.into_iter().next().map(|t| t.threshold))
+    }
+
+    /// Sets the threshold of the chat, `None` turns threshold reminders off
+    pub fn set_reminder_threshold(conn: &SqliteConnection, cid: &ChatId, threshold: Option<i32>) -> Result<(), TrackerError> {
+        match threshold {
+            Some(threshold) => {
+                let _ = replace_into(reminder_thresholds::table)
+                    .values(&SqliteReminderThreshold { chat_id: cid.0, threshold })
+                    .execute(conn)?;
+            },
+            None => {
+                let _ = delete(reminder_thresholds::table.filter(reminder_thresholds::chat_id.eq(cid.0)))
+                    .execute(conn)?;
+            },
+        }
+        Ok(())
+    }
+}
+
+pub mod digest_operations {
+    use chrono::NaiveDateTime;
+    use diesel::{delete, replace_into};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::ChatId;
+    use crate::types::digest::{Digest, SqliteDigest};
+    use crate::types::error::TrackerError;
+    use crate::types::schema::digests::dsl::*;
+
+    pub fn get_digest(conn: &SqliteConnection, cid: &ChatId) -> Result<Option<Digest>, TrackerError> {
+        let resp = digests
+            .filter(chat_id.eq(cid.0))
+            .load::<SqliteDigest>(conn)?;
+        resp.into_iter().next().map(Digest::try_from).transpose()
+    }
+
+    /// Creates or replaces the digest schedule of the chat
+    pub fn save_digest(conn: &SqliteConnection, digest: Digest) -> Result<Digest, TrackerError> {
+        let sqlite_digest = SqliteDigest::from(digest);
+        let _ = replace_into(digests)
+            .values(&sqlite_digest)
+            .execute(conn)?;
+        Digest::try_from(sqlite_digest)
+    }
+
+    pub fn delete_digest(conn: &SqliteConnection, cid: &ChatId) -> Result<(), TrackerError> {
+        let _ = delete(digests.filter(chat_id.eq(cid.0))).execute(conn)?;
+        Ok(())
+    }
+
+    pub fn get_due_digests(conn: &SqliteConnection, now: NaiveDateTime) -> Result<Vec<Digest>, TrackerError> {
+        let resp = digests
+            .filter(next_run.le(now))
+            .order(next_run.asc())
+            .load::<SqliteDigest>(conn)?;
+        resp.into_iter().map(Digest::try_from).collect()
+    }
+}
+
+pub mod settings_operations {
+    use diesel::{delete, replace_into};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::{ChatId, UserId};
+    use crate::types::error::TrackerError;
+    use crate::types::settings::{ChatSettings, Language, SqliteChatSettings, SqliteUserSettings};
+    use crate::types::schema::chat_settings::dsl::*;
+    use crate::types::schema::user_settings;
+
+    /// Settings of the chat, the defaults if it never changed them
+    pub fn get_chat_settings(conn: &SqliteConnection, cid: &ChatId) -> Result<ChatSettings, TrackerError> {
+        let resp = chat_settings
+            .filter(chat_id.eq(cid.0))
+            .load::<SqliteChatSettings>(conn)?;
+        match resp.into_iter().next() {
+            Some(settings) => ChatSettings::try_from(settings),
+            None => Ok(ChatSettings::default_for(*cid)),
+        }
+    }
+
+    pub fn save_chat_settings(conn: &SqliteConnection, settings: ChatSettings) -> Result<ChatSettings, TrackerError> {
+        let sqlite_settings = SqliteChatSettings::from(settings);
+        let _ = replace_into(chat_settings)
+            .values(&sqlite_settings)
+            .execute(conn)?;
+        ChatSettings::try_from(sqlite_settings)
+    }
+
+    /// Language the user picked with /language, if any
+    pub fn get_user_language(conn: &SqliteConnection, uid: &UserId) -> Result<Option<Language>, TrackerError> {
+        let resp = user_settings::table
+            .filter(user_settings::user_id.eq(uid.0 as i64))
+            .load::<SqliteUserSettings>(conn)?;
+        match resp.into_iter().next() {
+            Some(settings) => match Language::parse(&settings.language) {
+                Some(picked) => Ok(Some(picked)),
+                None => Err(TrackerError::Consistency(format!(
+                    "invalid language {:?} of user {}",
+                    settings.language,
+                    settings.user_id
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Saves the language of the user, `None` goes back to the one of the chat
+    pub fn set_user_language(conn: &SqliteConnection, uid: &UserId, picked: Option<Language>) -> Result<(), TrackerError> {
+        let _ = match picked {
+            Some(picked) => replace_into(user_settings::table)
+                .values(&SqliteUserSettings { user_id: uid.0 as i64, language: picked.code().to_string() })
+                .execute(conn)?,
+            None => delete(user_settings::table.filter(user_settings::user_id.eq(uid.0 as i64)))
+                .execute(conn)?,
+        };
+        Ok(())
+    }
+}
+
+pub mod approval_operations {
+    use diesel::{delete, insert_into};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use crate::types::approval::{Approval, NewApproval, SqliteApproval};
+    use crate::types::error::TrackerError;
+    use crate::types::schema::approvals::dsl::*;
+
+    pub fn insert_approval(conn: &SqliteConnection, new_approval: NewApproval) -> Result<Approval, TrackerError> {
+        let sqlite_approval = SqliteApproval::from(new_approval);
+        let _ = insert_into(approvals)
+            .values(&sqlite_approval)
+            .execute(conn)?;
+        Approval::try_from(sqlite_approval)
+    }
+
+    pub fn get_approval(conn: &SqliteConnection, approval_id: &str) -> Result<Option<Approval>, TrackerError> {
+        let resp = approvals
+            .filter(id.eq(approval_id))
+            .load::<SqliteApproval>(conn)?;
+        resp.into_iter().next().map(Approval::try_from).transpose()
+    }
+
+    pub fn delete_approval(conn: &SqliteConnection, approval_id: &str) -> Result<(), TrackerError> {
+        let _ = delete(approvals.filter(id.eq(approval_id))).execute(conn)?;
+        Ok(())
+    }
+}
+
+pub mod draft_operations {
+    use diesel::{delete, insert_into};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use chrono::NaiveDateTime;
+    use crate::types::draft::{Draft, NewDraft, SqliteDraft};
+    use crate::types::error::TrackerError;
+    use crate::types::schema::drafts::dsl::*;
+
+    pub fn insert_draft(conn: &SqliteConnection, new_draft: NewDraft) -> Result<Draft, TrackerError> {
+        let sqlite_draft = SqliteDraft::from(new_draft);
+        let _ = insert_into(drafts)
+            .values(&sqlite_draft)
+            .execute(conn)?;
+        Draft::try_from(sqlite_draft)
+    }
+
+    pub fn get_draft(conn: &SqliteConnection, draft_id: &str) -> Result<Option<Draft>, TrackerError> {
+        let resp = drafts
+            .filter(id.eq(draft_id))
+            .load::<SqliteDraft>(conn)?;
+        resp.into_iter().next().map(Draft::try_from).transpose()
+    }
+
+    pub fn delete_draft(conn: &SqliteConnection, draft_id: &str) -> Result<(), TrackerError> {
+        let _ = delete(drafts.filter(id.eq(draft_id))).execute(conn)?;
+        Ok(())
+    }
+
+    /// Drops the drafts nobody answered since `before`
+    pub fn delete_drafts_before(conn: &SqliteConnection, before: NaiveDateTime) -> Result<(), TrackerError> {
+        let _ = delete(drafts.filter(created.lt(before))).execute(conn)?;
+        Ok(())
+    }
+}
+
+pub mod event_operations {
+    use diesel::{insert_into, update};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::ChatId;
+    use crate::types::error::TrackerError;
+    use crate::types::event::{Event, NewEvent, SqliteEvent};
+    use crate::types::schema::events::dsl::*;
+
+    /// Starts the event and makes it the active one of its chat
+    pub fn insert_event(conn: &SqliteConnection, new_event: NewEvent) -> Result<Event, TrackerError> {
+        let sqlite_event = SqliteEvent::from(new_event);
+        conn.transaction::<_, TrackerError, _>(|| {
+            update(events.filter(chat_id.eq(sqlite_event.chat_id)))
+                .set(active.eq(false))
+                .execute(conn)?;
+            insert_into(events)
+                .values(&sqlite_event)
+                .execute(conn)?;
+            Ok(())
+        })?;
+        Ok(Event::from(sqlite_event))
+    }
+
+    pub fn update_event(conn: &SqliteConnection, event: Event) -> Result<Event, TrackerError> {
+        let sqlite_event = SqliteEvent::from(event);
+        let updated: SqliteEvent = sqlite_event.save_changes::<SqliteEvent>(conn)?;
+        Ok(Event::from(updated))
+    }
+
+    /// Events of the chat, oldest first
+    pub fn get_chat_events(conn: &SqliteConnection, cid: &ChatId) -> Result<Vec<Event>, TrackerError> {
+        let resp = events
+            .filter(chat_id.eq(cid.0))
+            .order(created.asc())
+            .load::<SqliteEvent>(conn)?;
+        Ok(resp.into_iter().map(Event::from).collect())
+    }
+
+    /// Event new transactions of the chat go to, if any
+    pub fn get_active_event(conn: &SqliteConnection, cid: &ChatId) -> Result<Option<Event>, TrackerError> {
+        let resp = events
+            .filter(chat_id.eq(cid.0))
+            .filter(active.eq(true))
+            .load::<SqliteEvent>(conn)?;
+        Ok(resp.into_iter().next().map(Event::from))
+    }
+
+    /// Makes the event the active one of its chat, `None` goes back to the
+    /// everyday debt
+    pub fn activate_event(conn: &SqliteConnection, cid: &ChatId, event: Option<&str>) -> Result<(), TrackerError> {
+        conn.transaction::<_, TrackerError, _>(|| {
+            update(events.filter(chat_id.eq(cid.0)))
+                .set(active.eq(false))
+                .execute(conn)?;
+            if let Some(event) = event {
+                update(events.filter(id.eq(event)))
+                    .set(active.eq(true))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+pub mod expense_operations {
+    use std::collections::HashMap;
+    use diesel::insert_into;
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::ChatId;
+    use crate::types::error::TrackerError;
+    use crate::types::expense::{NewSharedExpense, SharedExpense, SqliteExpenseShare, SqliteSharedExpense};
+    use crate::types::schema::{expense_shares, shared_expenses};
+
+    pub fn insert_shared_expense(conn: &SqliteConnection, new_expense: &NewSharedExpense) -> Result<SharedExpense, TrackerError> {
+        let sqlite_expense = SqliteSharedExpense::from(new_expense);
+        let shares: Vec<SqliteExpenseShare> = new_expense.shares.iter()
+            .enumerate()
+            .map(|(position, share)| SqliteExpenseShare {
+                expense_id: sqlite_expense.id.clone(),
+                user_id: share.user_id.0 as i64,
+                paid: share.paid,
+                share: share.share,
+                position: position as i32,
+            })
+            .collect();
+        let _ = insert_into(shared_expenses::table)
+            .values(&sqlite_expense)
+            .execute(conn)?;
+        let _ = insert_into(expense_shares::table)
+            .values(&shares)
+            .execute(conn)?;
+        sqlite_expense.into_expense(shares)
+    }
+
+    /// Shared expenses of the chat by id
+    pub fn get_chat_shared_expenses(conn: &SqliteConnection, cid: &ChatId) -> Result<HashMap<String, SharedExpense>, TrackerError> {
+        let rows = expense_shares::table
+            .inner_join(shared_expenses::table)
+            .filter(shared_expenses::chat_id.eq(cid.0))
+            .order(expense_shares::position.asc())
+            .load::<(SqliteExpenseShare, SqliteSharedExpense)>(conn)?;
+        let mut grouped: HashMap<String, (SqliteSharedExpense, Vec<SqliteExpenseShare>)> = HashMap::new();
+        for (share, expense) in rows.into_iter() {
+            grouped.entry(expense.id.clone()).or_insert((expense, vec![])).1.push(share);
+        }
+        grouped.into_iter()
+            .map(|(id, (expense, shares))| Ok((id, expense.into_expense(shares)?)))
+            .collect()
+    }
+}
+
+pub mod api_token_operations {
+    use diesel::{delete, insert_into};
+    use diesel::prelude::*;
+    use diesel::SqliteConnection;
+    use teloxide::types::ChatId;
+    use crate::types::api_token::{hash_token, ApiToken, NewApiToken, SqliteApiToken};
+    use crate::types::error::TrackerError;
+    use crate::types::schema::api_tokens::dsl::*;
+
+    /// Issues a new token for the chat, the one it had stops working
+    pub fn replace_api_token(conn: &SqliteConnection, new_token: &NewApiToken) -> Result<ApiToken, TrackerError> {
+        let sqlite_token = SqliteApiToken::from(new_token);
+        conn.transaction::<_, TrackerError, _>(|| {
+            let _ = delete(api_tokens.filter(chat_id.eq(sqlite_token.chat_id))).execute(conn)?;
+            let _ = insert_into(api_tokens)
+                .values(&sqlite_token)
+                .execute(conn)?;
+            Ok(())
+        })?;
+        Ok(ApiToken::from(sqlite_token))
+    }
+
+    pub fn get_api_token(conn: &SqliteConnection, cid: &ChatId) -> Result<Option<ApiToken>, TrackerError> {
+        let resp = api_tokens
+            .filter(chat_id.eq(cid.0))
+            .load::<SqliteApiToken>(conn)?;
+        Ok(resp.into_iter().next().map(ApiToken::from))
+    }
+
+    /// Token of the chat whose hash matches the presented token
+    pub fn find_api_token(conn: &SqliteConnection, presented: &str) -> Result<Option<ApiToken>, TrackerError> {
+        let resp = api_tokens
+            .filter(token_hash.eq(hash_token(presented)))
+            .load::<SqliteApiToken>(conn)?;
+        Ok(resp.into_iter().next().map(ApiToken::from))
+    }
+
+    pub fn delete_api_token(conn: &SqliteConnection, cid: &ChatId) -> Result<(), TrackerError> {
+        let _ = delete(api_tokens.filter(chat_id.eq(cid.0))).execute(conn)?;
+        Ok(())
+    }
+}
+
+pub mod migration_operations {
+    use std::io;
+    use diesel::SqliteConnection;
+    use diesel::connection::SimpleConnection;
+    use diesel_migrations::{Migration, MigrationConnection, MigrationError, RunMigrationsError, run_migrations, setup_database};
+    use crate::types::error::TrackerError;
+
+    /// Migration compiled into the binary. Versions follow the diesel CLI
+    /// convention so databases migrated by hand keep working.
+    struct EmbeddedMigration {
+        name: &'static str,
+        version: String,
+        up_sql: &'static str,
+    }
+
+    impl Migration for EmbeddedMigration {
+        fn version(&self) -> &str {
+            &self.version
+        }
+
+        fn run(&self, conn: &dyn SimpleConnection) -> Result<(), RunMigrationsError> {
+            conn.batch_execute(self.up_sql).map_err(Into::into)
+        }
+
+        fn revert(&self, _: &dyn SimpleConnection) -> Result<(), RunMigrationsError> {
+            // down migrations are only ever run by hand through the diesel CLI
+            Err(RunMigrationsError::MigrationError(MigrationError::IoError(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("migration {} can only be reverted with the diesel CLI", self.name),
+            ))))
+        }
+    }
+
+    macro_rules! embed_migration {
+        ($name:literal) => {
+            EmbeddedMigration {
+                name: $name,
+                // "2022-07-01-102900_initial_setup" -> "20220701102900"
+                version: $name.split('_').next().unwrap_or($name).replace('-', ""),
+                up_sql: include_str!(concat!("../../migrations/", $name, "/up.sql")),
+            }
+        };
+    }
+
+    fn embedded_migrations() -> Vec<EmbeddedMigration> {
+        vec![
+            embed_migration!("2022-07-01-102900_initial_setup"),
+            embed_migration!("2026-10-19-090000_typed_schema"),
+            embed_migration!("2026-10-19-100000_categories"),
+            embed_migration!("2026-10-19-110000_recurring"),
+            embed_migration!("2026-10-19-120000_reminders"),
+            embed_migration!("2026-10-19-130000_digests"),
+            embed_migration!("2026-10-19-140000_chat_settings"),
+            embed_migration!("2026-10-19-150000_languages"),
+            embed_migration!("2026-10-19-160000_drafts"),
+            embed_migration!("2026-10-19-170000_events"),
+            embed_migration!("2026-10-19-180000_shared_expenses"),
+            embed_migration!("2026-10-19-190000_roles"),
+            embed_migration!("2026-10-19-200000_recorded_by"),
+            embed_migration!("2026-10-19-210000_api_tokens"),
+            embed_migration!("2026-10-19-220000_assigned_roles"),
+            embed_migration!("2026-10-19-230000_departures"),
+            embed_migration!("2026-10-20-090000_hashed_api_tokens"),
+        ]
+    }
+
+    /// Applies all embedded migrations the database has not seen yet and
+    /// returns the names of the applied ones. Refuses to touch a database
+    /// that was migrated by a newer binary.
+    pub fn run_pending_migrations(conn: &SqliteConnection) -> Result<Vec<String>, TrackerError> {
+        let migrations = embedded_migrations();
+        setup_database(conn)?;
+        // compare the newest migration in the db with the newest one we know
+        let latest_known = migrations.iter().map(|m| m.version.clone()).max().unwrap_or_default();
+        if let Some(latest_run) = conn.latest_run_migration_version()? {
+            if latest_run > latest_known {
+                return Err(TrackerError::Consistency(format!(
+                    "database schema version {} is newer than the latest version supported by this binary ({})",
+                    latest_run,
+                    latest_known
+                )));
+            }
+        }
+        let already_run = conn.previously_run_migration_versions()?;
+        let pending: Vec<String> = migrations.iter()
+            .filter(|m| !already_run.contains(&m.version))
+            .map(|m| m.name.to_string())
+            .collect();
+        run_migrations(conn, migrations, &mut io::sink())?;
+        Ok(pending)
     }
-    
 }
 
 pub mod sqlite_operations {
-    use diesel::{SqliteConnection, Connection};
+    use diesel::{SqliteConnection, Connection, ConnectionError, connection::SimpleConnection};
     use std::{env};
-    pub(crate) fn establish_connection() -> SqliteConnection {
-        SqliteConnection::establish(
-            &env::var("DATABASE_URL").expect("No DATABASE_URL in .env")
-        ).expect("Error connecting to database!")
+    use crate::types::error::TrackerError;
+    use super::migration_operations::run_pending_migrations;
+
+    pub fn establish_connection() -> Result<SqliteConnection, TrackerError> {
+        let url = env::var("DATABASE_URL")
+            .map_err(|_| ConnectionError::BadConnection("$DATABASE_URL is not set (see .env.sample)".to_string()))?;
+        connect(&url)
+    }
+
+    /// Fresh, fully migrated database that lives as long as the connection
+    pub fn establish_memory_connection() -> Result<SqliteConnection, TrackerError> {
+        let conn = connect(":memory:")?;
+        run_pending_migrations(&conn)?;
+        Ok(conn)
+    }
+
+    fn connect(url: &str) -> Result<SqliteConnection, TrackerError> {
+        let conn = SqliteConnection::establish(url)?;
+        // sqlite only enforces foreign keys when asked to (per connection)
+        conn.batch_execute("PRAGMA foreign_keys = ON;")?;
+        Ok(conn)
     }
 }
\ No newline at end of file
@@ -0,0 +1,234 @@
+//! Reads loans written the way people say them ("/loan I paid 23,40€ for
+//! groceries for @ana and @luka"). The amount can be anywhere in the
+//! message, with or without a currency. What is left, without mentions,
+//! hashtags and filler words, describes the loan.
+
+use std::ops::Range;
+use super::{expression, message_validator::parse_amount};
+
+/// Currency symbols and the codes they stand for
+const SYMBOLS: [(char, &str); 3] = [('€', "EUR"), ('$', "USD"), ('£', "GBP")];
+
+/// Currency words that are not ISO codes
+const CURRENCY_NAMES: [(&str, &str); 5] = [
+    ("euro", "EUR"),
+    ("euros", "EUR"),
+    ("evro", "EUR"),
+    ("evra", "EUR"),
+    ("evrov", "EUR"),
+];
+
+/// Codes recognised in any case ("23.40 eur"), other codes only in capitals
+const KNOWN_CODES: [&str; 4] = ["EUR", "USD", "GBP", "CHF"];
+
+/// Words a description does not start with ("I paid for pizza" -> "pizza")
+const LEADING_FILLER: [&str; 21] = [
+    "i", "i've", "we", "me", "us", "just", "paid", "spent", "lent", "loaned", "gave", "bought",
+    "for", "on", "to", "and", "with", "sem", "smo", "za", "plačal",
+];
+
+/// Words a description does not end with ("groceries for @ana" -> "groceries")
+const TRAILING_FILLER: [&str; 9] = ["for", "to", "and", "with", "on", "za", "in", "z", "s"];
+
+/// Punctuation that ends a sentence rather than a number ("... 23,40.")
+const PUNCTUATION: [char; 6] = ['.', ',', '!', '?', ';', ':'];
+
+/// Amount as it is written in the message
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrittenAmount {
+    /// Index of the word with the number among the words of the message
+    /// (the command is word 0)
+    pub word: usize,
+    /// Words the amount is written with, the number and a separate currency
+    pub words: Range<usize>,
+    /// The number or calculation without the currency ("23,40", "120/4")
+    pub number: String,
+    /// Code of the currency written with the amount ("23,40€" -> "EUR")
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Expense {
+    /// The amount, unless the message has none or several that could be it
+    pub amount: Option<WrittenAmount>,
+    /// Every number that could be the amount
+    pub candidates: Vec<WrittenAmount>,
+    /// Words that are not the amount, mentions, hashtags, due dates or filler
+    pub description: String,
+}
+
+impl Expense {
+    /// Several numbers could be the amount and nothing tells them apart, or
+    /// the amount could be read two ways ("12.345")
+    pub fn is_ambiguous(&self) -> bool {
+        match &self.amount {
+            Some(amount) => readings(&amount.number).len() > 1,
+            None => self.candidates.len() > 1,
+        }
+    }
+}
+
+/// Amounts in cents a number could mean when its only separator is
+/// followed by three digits: thousands ("12.345" is 12345) or decimals
+/// rounded to cents (12.35). Empty for numbers that read only one way.
+pub fn readings(number: &str) -> Vec<i64> {
+    let separators: Vec<usize> = number.char_indices().filter(|(_, c)| !c.is_ascii_digit()).map(|(i, _)| i).collect();
+    let (whole, decimals) = match separators.as_slice() {
+        [i] if number.len() - i - 1 == 3 => (&number[..*i], &number[i + 1..]),
+        _ => return vec![],
+    };
+    let thousands = match parse_amount(number) {
+        Some(thousands) => thousands,
+        None => return vec![],
+    };
+    let whole: i64 = match whole.parse() {
+        Ok(whole) => whole,
+        Err(_) => return vec![],
+    };
+    let decimals: i64 = match decimals.parse() {
+        Ok(decimals) => decimals,
+        Err(_) => return vec![],
+    };
+    vec![thousands, whole * 100 + (decimals + 5) / 10]
+}
+
+/// Finds the amount and the description of a loan or payment. A number
+/// right after the command is always the amount ("/loan 2 pizzas @ana" loans
+/// 2), elsewhere a number is the amount if it is the only one or the only
+/// one with a currency.
+pub fn parse_expense(text: &str) -> Expense {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let candidates = match read_amount(&words, 1, true) {
+        Some(first) => vec![first],
+        None => (2..words.len()).filter_map(|i| read_amount(&words, i, false)).collect(),
+    };
+    let with_currency: Vec<&WrittenAmount> = candidates.iter().filter(|c| c.currency.is_some()).collect();
+    let amount = match (candidates.as_slice(), with_currency.as_slice()) {
+        ([only], _) => Some(only.clone()),
+        (_, [only]) => Some((*only).clone()),
+        _ => None,
+    };
+    let description = describe(&words, amount.as_ref());
+    Expense { amount, candidates, description }
+}
+
+/// The message with the amount moved right after the command, which makes
+/// it unambiguous ("/loan 2 pizzas 23,40 @ana" -> "/loan 23,40 2 pizzas @ana")
+pub fn put_amount_first(text: &str, amount: &WrittenAmount) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut ordered: Vec<&str> = words.iter().take(1).copied().collect();
+    ordered.extend(words.get(amount.word).copied());
+    ordered.extend(amount.words.clone().filter(|i| *i != amount.word).filter_map(|i| words.get(i).copied()));
+    ordered.extend(words.iter().enumerate().skip(1).filter(|(i, _)| !amount.words.contains(i)).map(|(_, w)| *w));
+    ordered.join(" ")
+}
+
+/// The amount written at word `i`. The word right after the command is
+/// taken when it has any digits at all (`first`), so a mistake in it gets
+/// reported instead of looking for another number.
+fn read_amount(words: &[&str], i: usize, first: bool) -> Option<WrittenAmount> {
+    let word = words.get(i)?.trim_end_matches(PUNCTUATION);
+    if word.starts_with(['@', '#']) || is_option(word) {
+        return None;
+    }
+    let (number, attached) = split_currency(word);
+    if !number.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let valid = match expression::is_expression(number) {
+        true => expression::evaluate(number).is_ok_and(|cents| cents >= 0),
+        false => parse_amount(number).is_some(),
+    };
+    if !valid && !first {
+        return None;
+    }
+    // "23.40 EUR" or "EUR 23.40"
+    let (words, currency) = match attached {
+        Some(code) => (i..i + 1, Some(code)),
+        None => match (words.get(i + 1).and_then(|w| currency_word(w)), words.get(i - 1).and_then(|w| currency_word(w))) {
+            (Some(code), _) => (i..i + 2, Some(code)),
+            (None, Some(code)) if i > 1 => (i - 1..i + 1, Some(code)),
+            _ => (i..i + 1, None),
+        },
+    };
+    Some(WrittenAmount { word: i, words, number: number.to_string(), currency })
+}
+
+/// Number and the code of a currency written together with it ("€5",
+/// "23,40€", "12EUR")
+fn split_currency(word: &str) -> (&str, Option<String>) {
+    for (symbol, code) in SYMBOLS {
+        if let Some(number) = word.strip_prefix(symbol).or_else(|| word.strip_suffix(symbol)) {
+            return (number, Some(code.to_string()));
+        }
+    }
+    let letters = word.len() - word.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len();
+    if letters == 3 {
+        let (number, code) = word.split_at(word.len() - 3);
+        if let Some(code) = currency_code(code) {
+            return (number, Some(code));
+        }
+    }
+    let letters = word.len() - word.trim_start_matches(|c: char| c.is_ascii_alphabetic()).len();
+    if letters == 3 {
+        let (code, number) = word.split_at(3);
+        if let Some(code) = currency_code(code) {
+            return (number, Some(code));
+        }
+    }
+    (word, None)
+}
+
+/// Currency a separate word stands for ("EUR", "eur", "€", "euros")
+fn currency_word(word: &str) -> Option<String> {
+    let word = word.trim_end_matches(PUNCTUATION);
+    let symbol = SYMBOLS.iter().find(|(symbol, _)| word.chars().eq([*symbol]));
+    let name = CURRENCY_NAMES.iter().find(|(name, _)| word.eq_ignore_ascii_case(name));
+    match symbol.map(|(_, code)| code).or(name.map(|(_, code)| code)) {
+        Some(code) => Some(code.to_string()),
+        None => currency_code(word),
+    }
+}
+
+/// ISO code, known ones in any case, others only in capitals ("CAD")
+fn currency_code(word: &str) -> Option<String> {
+    let code = word.to_ascii_uppercase();
+    let capitals = word.len() == 3 && word.chars().all(|c| c.is_ascii_uppercase());
+    match KNOWN_CODES.contains(&code.as_str()) || capitals {
+        true => Some(code),
+        false => None,
+    }
+}
+
+/// Due date ("due:2026-11-01") or payer ("by:@ana") of the message
+fn is_option(word: &str) -> bool {
+    word.starts_with("due:") || word.starts_with("by:")
+}
+
+/// Words of the message around the amount, mentions, hashtags, due dates
+/// and payers, each stretch without the filler at its edges
+fn describe(words: &[&str], amount: Option<&WrittenAmount>) -> String {
+    let mut stretches: Vec<Vec<&str>> = vec![vec![]];
+    for (i, word) in words.iter().enumerate().skip(1) {
+        let skipped = amount.is_some_and(|a| a.words.contains(&i))
+            || word.starts_with(['@', '#'])
+            || is_option(word);
+        match (skipped, stretches.last_mut()) {
+            (false, Some(stretch)) => stretch.push(word),
+            _ => stretches.push(vec![]),
+        }
+    }
+    let is_filler = |word: &str, filler: &[&str]| filler.contains(&word.trim_end_matches(PUNCTUATION).to_lowercase().as_str());
+    stretches.into_iter()
+        .filter_map(|mut stretch| {
+            let start = stretch.iter().position(|w| !is_filler(w, &LEADING_FILLER))?;
+            while stretch.last().is_some_and(|w| is_filler(w, &TRAILING_FILLER)) {
+                stretch.pop();
+            }
+            let mut described = stretch.get(start..)?.join(" ");
+            described.truncate(described.trim_end_matches([',', ';', ':']).len());
+            Some(described).filter(|d| !d.is_empty())
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,266 @@
+//! Calculator for amounts ("/loan (3*8.5+4) @ana"). Supports + - * /,
+//! parentheses and percentages ("45+10%" adds 10% of 45). Numbers are kept
+//! as exact fractions, so only the final result is rounded to cents.
+
+/// What went wrong and the token it went wrong at
+#[derive(Debug, PartialEq)]
+pub struct ExpressionError {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Fraction),
+    Operator(char),
+    Open,
+    Close,
+    Percent,
+}
+
+/// Exact value as numerator / denominator, the denominator is positive
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fraction {
+    numerator: i128,
+    denominator: i128,
+}
+
+/// Term of a sum, remembering whether it was written as a percentage
+struct Term {
+    value: Fraction,
+    percent: bool,
+}
+
+/// Whether the text is meant as a calculation rather than a plain amount
+pub fn is_expression(text: &str) -> bool {
+    text.chars().any(|c| "+-*/()%×÷".contains(c))
+}
+
+/// Result of the expression in cents, rounded half away from zero
+pub fn evaluate(expression: &str) -> Result<i64, ExpressionError> {
+    let tokens = tokenize(expression)?;
+    let mut parser = Parser { tokens: &tokens, texts: token_texts(expression), position: 0 };
+    let term = parser.sum()?;
+    if parser.position < tokens.len() {
+        return Err(parser.error_here());
+    }
+    term.plain().and_then(|v| v.to_cents()).ok_or_else(|| ExpressionError { token: expression.to_string() })
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, ExpressionError> {
+    token_texts(expression).iter()
+        .map(|text| match text.as_str() {
+            "(" => Ok(Token::Open),
+            ")" => Ok(Token::Close),
+            "%" => Ok(Token::Percent),
+            "+" | "-" | "*" | "/" => Ok(Token::Operator(text.chars().next().unwrap_or('+'))),
+            "×" => Ok(Token::Operator('*')),
+            "÷" => Ok(Token::Operator('/')),
+            number => match parse_number(number) {
+                Some(value) => Ok(Token::Number(value)),
+                None => Err(ExpressionError { token: number.to_string() }),
+            },
+        })
+        .collect()
+}
+
+/// Splits the expression into numbers and single character symbols
+fn token_texts(expression: &str) -> Vec<String> {
+    let mut texts: Vec<String> = vec![];
+    let mut number = String::new();
+    for c in expression.chars() {
+        if c.is_alphanumeric() || c == '.' || c == ',' {
+            number.push(c);
+            continue;
+        }
+        if !number.is_empty() {
+            texts.push(std::mem::take(&mut number));
+        }
+        if !c.is_whitespace() {
+            texts.push(c.to_string());
+        }
+    }
+    if !number.is_empty() {
+        texts.push(number);
+    }
+    texts
+}
+
+/// "8.5" or "8,5", the separator is always the decimal one
+fn parse_number(text: &str) -> Option<Fraction> {
+    let (whole, decimals) = match text.split_once(['.', ',']) {
+        Some((whole, decimals)) => (whole, decimals),
+        None => (text, ""),
+    };
+    let digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if !digits(whole) || !digits(decimals) || (whole.is_empty() && decimals.is_empty()) || decimals.len() > 18 {
+        return None;
+    }
+    let numerator = format!("{}{}", whole, decimals).parse::<i128>().ok()?;
+    Fraction::new(numerator, 10i128.checked_pow(decimals.len() as u32)?)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    texts: Vec<String>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    /// sum = product (("+" | "-") product)*
+    fn sum(&mut self) -> Result<Term, ExpressionError> {
+        let mut left = self.product()?;
+        while let Some(Token::Operator(operator @ ('+' | '-'))) = self.peek().cloned() {
+            let at = self.position;
+            self.position += 1;
+            let right = self.product()?;
+            let value = left.plain().and_then(|left| {
+                // "45+10%" is 45 plus 10% of 45
+                let right = match right.percent {
+                    true => left.multiply(right.plain()?)?,
+                    false => right.value,
+                };
+                match operator {
+                    '+' => left.add(right),
+                    _ => left.add(right.negate()?),
+                }
+            });
+            left = Term { value: value.ok_or_else(|| self.error_at(at))?, percent: false };
+        }
+        Ok(left)
+    }
+
+    /// product = factor (("*" | "/") factor)*
+    fn product(&mut self) -> Result<Term, ExpressionError> {
+        let mut left = self.factor()?;
+        while let Some(Token::Operator(operator @ ('*' | '/'))) = self.peek().cloned() {
+            let at = self.position;
+            self.position += 1;
+            let right = self.factor()?;
+            let value = left.plain().zip(right.plain()).and_then(|(left, right)| match operator {
+                '*' => left.multiply(right),
+                _ => left.divide(right),
+            });
+            left = Term { value: value.ok_or_else(|| self.error_at(at))?, percent: false };
+        }
+        Ok(left)
+    }
+
+    /// factor = "-"? (number | "(" sum ")") "%"?
+    fn factor(&mut self) -> Result<Term, ExpressionError> {
+        let start = self.position;
+        let negative = matches!(self.peek(), Some(Token::Operator('-')));
+        if negative {
+            self.position += 1;
+        }
+        let value = match self.peek().cloned() {
+            Some(Token::Number(value)) => {
+                self.position += 1;
+                value
+            },
+            Some(Token::Open) => {
+                let open = self.position;
+                self.position += 1;
+                let inner = self.sum()?;
+                match self.peek() {
+                    Some(Token::Close) => self.position += 1,
+                    Some(_) => return Err(self.error_here()),
+                    // unclosed parenthesis
+                    None => return Err(self.error_at(open)),
+                }
+                inner.plain().ok_or_else(|| self.error_at(open))?
+            },
+            _ => return Err(self.error_here()),
+        };
+        let percent = matches!(self.peek(), Some(Token::Percent));
+        if percent {
+            self.position += 1;
+        }
+        let value = match negative {
+            true => value.negate().ok_or_else(|| self.error_at(start))?,
+            false => value,
+        };
+        Ok(Term { value, percent })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Error at the current token, or at the last one if the expression ended
+    /// too early
+    fn error_here(&self) -> ExpressionError {
+        self.error_at(self.position.min(self.tokens.len().saturating_sub(1)))
+    }
+
+    fn error_at(&self, position: usize) -> ExpressionError {
+        ExpressionError { token: self.texts.get(position).cloned().unwrap_or_default() }
+    }
+}
+
+impl Term {
+    /// The value itself, percentages turned into fractions (10% -> 0.1)
+    fn plain(&self) -> Option<Fraction> {
+        match self.percent {
+            true => self.value.divide(Fraction::whole(100)),
+            false => Some(self.value),
+        }
+    }
+}
+
+impl Fraction {
+    /// `None` when the denominator is zero or the value doesn't fit
+    fn new(numerator: i128, denominator: i128) -> Option<Fraction> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator, denominator)?.max(1);
+        let sign = denominator.signum();
+        Some(Fraction {
+            numerator: numerator.checked_mul(sign)? / divisor,
+            denominator: denominator.checked_mul(sign)? / divisor,
+        })
+    }
+
+    fn whole(value: i128) -> Fraction {
+        Fraction { numerator: value, denominator: 1 }
+    }
+
+    fn add(self, other: Fraction) -> Option<Fraction> {
+        let numerator = self.numerator.checked_mul(other.denominator)?
+            .checked_add(other.numerator.checked_mul(self.denominator)?)?;
+        Fraction::new(numerator, self.denominator.checked_mul(other.denominator)?)
+    }
+
+    fn negate(self) -> Option<Fraction> {
+        Some(Fraction { numerator: self.numerator.checked_neg()?, denominator: self.denominator })
+    }
+
+    fn multiply(self, other: Fraction) -> Option<Fraction> {
+        Fraction::new(self.numerator.checked_mul(other.numerator)?, self.denominator.checked_mul(other.denominator)?)
+    }
+
+    /// `None` when dividing by zero
+    fn divide(self, other: Fraction) -> Option<Fraction> {
+        Fraction::new(self.numerator.checked_mul(other.denominator)?, self.denominator.checked_mul(other.numerator)?)
+    }
+
+    fn to_cents(self) -> Option<i64> {
+        let hundredths = self.numerator.checked_mul(100)?;
+        let (quotient, remainder) = (hundredths / self.denominator, hundredths % self.denominator);
+        // round half away from zero
+        let rounded = match remainder.abs() >= self.denominator - remainder.abs() {
+            true => quotient + hundredths.signum(),
+            false => quotient,
+        };
+        i64::try_from(rounded).ok()
+    }
+}
+
+/// `None` when a number is too large to be made positive (i128::MIN)
+fn gcd(a: i128, b: i128) -> Option<i128> {
+    let (mut a, mut b) = (a.checked_abs()?, b.checked_abs()?);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    Some(a)
+}
@@ -0,0 +1,158 @@
+//! Message catalogues (locales/*.txt) and locale aware formatting of
+//! numbers, money and dates. Catalogues are compiled into the binary; a
+//! message missing from one is taken from the English catalogue.
+
+use std::{collections::HashMap, sync::OnceLock};
+use chrono::{NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+use crate::types::settings::{ChatSettings, Language};
+
+/// Everything a reply needs to be written for its reader: their language
+/// and the currency and timezone of the chat
+#[derive(Debug, Clone)]
+pub struct Locale {
+    pub language: Language,
+    /// ISO 4217 code (EUR, USD, ...)
+    pub currency: String,
+    pub timezone: Tz,
+}
+
+impl Locale {
+    pub fn new(settings: &ChatSettings, language: Language) -> Self {
+        Self { language, currency: settings.currency.clone(), timezone: settings.timezone }
+    }
+
+    /// Locale of a reader we know nothing about but their Telegram app's
+    /// language, used when even the chat's settings are out of reach
+    pub fn fallback(language_code: Option<&str>) -> Self {
+        Self {
+            language: language_code.and_then(Language::parse).unwrap_or_default(),
+            currency: "EUR".to_string(),
+            timezone: Tz::UTC,
+        }
+    }
+
+    /// Message of the catalogue, the key itself if no catalogue has it
+    pub fn text(&self, key: &str) -> String {
+        lookup(self.language, key).unwrap_or(key).to_string()
+    }
+
+    /// Message with its {placeholders} filled in. Values are inserted as they
+    /// are, braces in them are not treated as placeholders.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let template = lookup(self.language, key).unwrap_or(key);
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let placeholder = rest[start + 1..].find('}').map(|end| &rest[start + 1..start + 1 + end]);
+            match placeholder.and_then(|name| args.iter().find(|(arg, _)| *arg == name).map(|(_, value)| (name, value))) {
+                Some((name, value)) => {
+                    out.push_str(value);
+                    rest = &rest[start + name.len() + 2..];
+                },
+                None => {
+                    out.push('{');
+                    rest = &rest[start + 1..];
+                },
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Amount in cents as a number, "1,234.50" in English and "1.234,50" in
+    /// Slovenian. Whole amounts are printed without decimals.
+    pub fn number(&self, cents: i64) -> String {
+        let (decimal, grouping) = match self.language {
+            Language::English => ('.', ','),
+            Language::Slovenian => (',', '.'),
+        };
+        let (whole, fraction) = (cents.unsigned_abs() / 100, cents.unsigned_abs() % 100);
+        let digits = whole.to_string();
+        let mut out = if cents < 0 { "-".to_string() } else { String::new() };
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                out.push(grouping);
+            }
+            out.push(digit);
+        }
+        if fraction > 0 {
+            out = format!("{}{}{:02}", out, decimal, fraction);
+        }
+        out
+    }
+
+    /// Amount in cents in the currency of the chat ("20€", "$20", "20 CHF"
+    /// in English, "20 €", "20 $" in Slovenian)
+    pub fn money(&self, cents: i64) -> String {
+        let amount = self.number(cents);
+        let symbol = match self.currency.as_str() {
+            "EUR" => "€",
+            "USD" => "$",
+            "GBP" => "£",
+            code => return format!("{} {}", amount, code),
+        };
+        match (self.language, symbol) {
+            (Language::English, "€") => format!("{}€", amount),
+            (Language::English, _) => format!("{}{}", symbol, amount),
+            (Language::Slovenian, _) => format!("{} {}", amount, symbol),
+        }
+    }
+
+    /// Day of a moment (UTC) in the timezone of the chat
+    pub fn date(&self, at: &NaiveDateTime) -> String {
+        self.timezone.from_utc_datetime(at).format("%d.%m.%Y").to_string()
+    }
+
+    /// Day of the month as an ordinal ("1st" in English, "1." in Slovenian)
+    pub fn ordinal(&self, day: u32) -> String {
+        match self.language {
+            Language::English => {
+                let suffix = match (day % 10, day % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+                format!("{}{}", day, suffix)
+            },
+            Language::Slovenian => format!("{}.", day),
+        }
+    }
+}
+
+/// Every message of the language's catalogue by its key
+pub fn catalogue(language: Language) -> &'static HashMap<String, String> {
+    static CATALOGUES: OnceLock<HashMap<Language, HashMap<String, String>>> = OnceLock::new();
+    let catalogues = CATALOGUES.get_or_init(|| {
+        Language::ALL.iter().map(|language| (*language, parse_catalogue(source(*language)))).collect()
+    });
+    &catalogues[&language]
+}
+
+fn source(language: Language) -> &'static str {
+    match language {
+        Language::English => include_str!("../../locales/en.txt"),
+        Language::Slovenian => include_str!("../../locales/sl.txt"),
+    }
+}
+
+/// "key = text" per line, '#' starts a comment line and "\n" in the text
+/// breaks the line
+fn parse_catalogue(source: &str) -> HashMap<String, String> {
+    source.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (key.trim().to_string(), text.trim().replace("\\n", "\n")))
+        .collect()
+}
+
+fn lookup(language: Language, key: &str) -> Option<&'static str> {
+    catalogue(language).get(key)
+        .or_else(|| catalogue(Language::English).get(key))
+        .map(|text| text.as_str())
+}
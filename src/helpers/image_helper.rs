@@ -0,0 +1,287 @@
+use std::{collections::HashMap, f64::consts::PI, fmt::Debug};
+use chrono::{Duration, Utc};
+use image::{ColorType, ImageEncoder, codecs::png::PngEncoder};
+use plotters::{coord::{Shift, types::RangedDateTime}, prelude::*, style::text_anchor::{HPos, Pos, VPos}};
+use teloxide::types::UserId;
+
+use crate::types::{error::TrackerError, graph::Graph, stats::{MonthlyTotal, Stats}};
+use super::{i18n::Locale, text_helper::category_label};
+
+const PANEL_SIZE: u32 = 640;
+const CHART_SIZE: (u32, u32) = (1200, 500);
+const NODE_RADIUS: i32 = 34;
+/// Distance between the two arrows of people owing each other
+const EDGE_OFFSET: f64 = 8.;
+
+/// Draws the recorded and the simplified debt graph next to each other and
+/// returns the picture as PNG.
+pub fn render_debt_graphs(recorded: &Graph, simplified: &Graph, locale: &Locale) -> Result<Vec<u8>, TrackerError> {
+    let (width, height) = (PANEL_SIZE * 2, PANEL_SIZE);
+    let mut pixels = vec![0; (width * height * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (width, height)).into_drawing_area();
+        root.fill(&WHITE).map_err(render_error)?;
+        let (left, right) = root.split_horizontally(PANEL_SIZE);
+        draw_graph(&left, &locale.text("graph.recorded"), recorded, locale)?;
+        draw_graph(&right, &locale.text("graph.simplified"), simplified, locale)?;
+        root.present().map_err(render_error)?;
+    }
+    encode_png(&pixels, width, height)
+}
+
+/// Draws the monthly totals, the balance of every user over time and the
+/// share of each category below each other and returns the picture as PNG.
+pub fn render_stats_charts(stats: &Stats, locale: &Locale) -> Result<Vec<u8>, TrackerError> {
+    let (width, height) = (CHART_SIZE.0, CHART_SIZE.1 * 3);
+    let mut pixels = vec![0; (width * height * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (width, height)).into_drawing_area();
+        root.fill(&WHITE).map_err(render_error)?;
+        let panels = root.split_evenly((3, 1));
+        draw_monthly_totals(&panels[0], stats, locale)?;
+        draw_balance_history(&panels[1], stats, locale)?;
+        draw_category_share(&panels[2], stats, locale)?;
+        root.present().map_err(render_error)?;
+    }
+    encode_png(&pixels, width, height)
+}
+
+pub(crate) fn encode_png(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>, TrackerError> {
+    let mut png = vec![];
+    PngEncoder::new(&mut png)
+        .write_image(pixels, width, height, ColorType::Rgb8)
+        .map_err(render_error)?;
+    Ok(png)
+}
+
+pub(crate) fn render_error<E: Debug>(e: E) -> TrackerError {
+    TrackerError::Rendering(format!("{:?}", e))
+}
+
+fn draw_graph<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    graph: &Graph,
+    locale: &Locale
+) -> Result<(), TrackerError> {
+    let area = area.titled(title, ("sans-serif", 28)).map_err(render_error)?;
+    let (width, height) = area.dim_in_pixel();
+    let centered = TextStyle::from(("sans-serif", 18)).pos(Pos::new(HPos::Center, VPos::Center));
+    if graph.ledgers.iter().all(|l| l.sum <= 0) {
+        return area.draw(&Text::new(locale.text("graph.nobody_owes"), (width as i32 / 2, height as i32 / 2), &centered))
+            .map_err(render_error);
+    }
+    // users sit on a circle, the first one at the top
+    let positions = node_positions(graph, width, height);
+    for ledger in graph.ledgers.iter().filter(|l| l.sum > 0) {
+        let (from, to) = match (positions.get(&ledger.borrower), positions.get(&ledger.owes)) {
+            (Some(from), Some(to)) => (*from, *to),
+            _ => continue,
+        };
+        draw_edge(&area, from, to, &locale.money(ledger.sum as i64))?;
+    }
+    for user in graph.users.iter() {
+        let (x, y) = match positions.get(&user.user_id) {
+            Some(position) => *position,
+            None => continue,
+        };
+        let center = (x as i32, y as i32);
+        area.draw(&Circle::new(center, NODE_RADIUS, RGBColor(214, 234, 248).filled())).map_err(render_error)?;
+        area.draw(&Circle::new(center, NODE_RADIUS, BLUE.stroke_width(2))).map_err(render_error)?;
+        area.draw(&Text::new(user.username.clone(), center, &centered)).map_err(render_error)?;
+    }
+    Ok(())
+}
+
+fn node_positions(graph: &Graph, width: u32, height: u32) -> HashMap<UserId, (f64, f64)> {
+    let center = (width as f64 / 2., height as f64 / 2.);
+    let radius = width.min(height) as f64 / 2. - NODE_RADIUS as f64 - 20.;
+    let count = graph.users.len().max(1) as f64;
+    graph.users.iter()
+        .enumerate()
+        .map(|(i, user)| {
+            let angle = 2. * PI * i as f64 / count - PI / 2.;
+            (user.user_id, (center.0 + radius * angle.cos(), center.1 + radius * angle.sin()))
+        })
+        .collect()
+}
+
+fn draw_edge<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    from: (f64, f64),
+    to: (f64, f64),
+    label: &str
+) -> Result<(), TrackerError> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt().max(1.);
+    let (ux, uy) = (dx / length, dy / length);
+    // shift sideways, so a -> b and b -> a don't overlap
+    let (nx, ny) = (-uy * EDGE_OFFSET, ux * EDGE_OFFSET);
+    let node = NODE_RADIUS as f64;
+    let start = (from.0 + ux * node + nx, from.1 + uy * node + ny);
+    let end = (to.0 - ux * node + nx, to.1 - uy * node + ny);
+    let point = |p: (f64, f64)| (p.0.round() as i32, p.1.round() as i32);
+    area.draw(&PathElement::new(vec![point(start), point(end)], BLACK.stroke_width(2))).map_err(render_error)?;
+    // arrow head
+    let head = 14.;
+    let left = (end.0 - ux * head - uy * head / 2., end.1 - uy * head + ux * head / 2.);
+    let right = (end.0 - ux * head + uy * head / 2., end.1 - uy * head - ux * head / 2.);
+    area.draw(&Polygon::new(vec![point(end), point(left), point(right)], BLACK.filled())).map_err(render_error)?;
+    // amount next to the middle of the arrow
+    let middle = ((start.0 + end.0) / 2. + nx * 2., (start.1 + end.1) / 2. + ny * 2.);
+    let style = TextStyle::from(("sans-serif", 16)).color(&RED).pos(Pos::new(HPos::Center, VPos::Center));
+    area.draw(&Text::new(label.to_string(), point(middle), &style)).map_err(render_error)
+}
+
+fn draw_monthly_totals<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    stats: &Stats,
+    locale: &Locale
+) -> Result<(), TrackerError> {
+    if stats.monthly.is_empty() {
+        return draw_empty(area, &locale.text("chart.monthly"), locale);
+    }
+    let months = stats.monthly.len();
+    let top = stats.monthly.iter().map(|m| m.loaned.max(m.payed)).max().unwrap_or(0) as f64 / 100.;
+    let mut chart = ChartBuilder::on(area)
+        .caption(locale.text("chart.monthly"), ("sans-serif", 28))
+        .margin(15)
+        .x_label_area_size(35)
+        .y_label_area_size(60)
+        .build_cartesian_2d(-0.5..months as f64 - 0.5, 0.0..(top * 1.1).max(1.))
+        .map_err(render_error)?;
+    // months are whole numbers on the x axis, bars sit around them
+    chart.configure_mesh()
+        .disable_x_mesh()
+        .x_labels(months.min(12) + 1)
+        .x_label_formatter(&|x| match stats.monthly.get(x.round() as usize) {
+            Some(m) if x.fract() == 0. => format!("{}/{}", m.month, m.year % 100),
+            _ => String::new(),
+        })
+        .y_label_formatter(&|y| locale.money((y * 100.).round() as i64))
+        .draw()
+        .map_err(render_error)?;
+    let bars = |offset: f64, value: fn(&MonthlyTotal) -> i64, color: RGBColor| {
+        stats.monthly.iter().enumerate().map(move |(i, m)| {
+            let x = i as f64 + offset;
+            Rectangle::new([(x, 0.), (x + 0.35, value(m) as f64 / 100.)], color.filled())
+        })
+    };
+    chart.draw_series(bars(-0.375, |m| m.loaned, RGBColor(52, 152, 219)))
+        .map_err(render_error)?
+        .label(locale.text("chart.loaned"))
+        .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 15, y + 5)], RGBColor(52, 152, 219).filled()));
+    chart.draw_series(bars(0.025, |m| m.payed, RGBColor(46, 204, 113)))
+        .map_err(render_error)?
+        .label(locale.text("chart.paid"))
+        .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 15, y + 5)], RGBColor(46, 204, 113).filled()));
+    chart.configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(render_error)
+}
+
+fn draw_balance_history<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    stats: &Stats,
+    locale: &Locale
+) -> Result<(), TrackerError> {
+    let points = stats.balances.iter().flat_map(|b| b.points.iter());
+    let (start, end) = match (points.clone().map(|p| p.0).min(), points.clone().map(|p| p.0).max()) {
+        (Some(start), Some(end)) => (start, end.max(Utc::now().naive_utc()).max(start + Duration::days(1))),
+        _ => return draw_empty(area, &locale.text("chart.balance"), locale),
+    };
+    let low = points.clone().map(|p| p.1).min().unwrap_or(0).min(0) as f64 / 100.;
+    let high = points.map(|p| p.1).max().unwrap_or(0).max(0) as f64 / 100.;
+    let padding = ((high - low) * 0.1).max(1.);
+    let mut chart = ChartBuilder::on(area)
+        .caption(locale.text("chart.balance"), ("sans-serif", 28))
+        .margin(15)
+        .x_label_area_size(35)
+        .y_label_area_size(60)
+        .build_cartesian_2d(RangedDateTime::from(start..end), (low - padding)..(high + padding))
+        .map_err(render_error)?;
+    chart.configure_mesh()
+        .x_labels(8)
+        .x_label_formatter(&|date| date.format("%d.%m.%y").to_string())
+        .y_label_formatter(&|y| locale.money((y * 100.).round() as i64))
+        .draw()
+        .map_err(render_error)?;
+    for (i, history) in stats.balances.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        // balance only changes with transactions, so the line goes in steps
+        let mut steps = vec![];
+        for (j, (date, balance)) in history.points.iter().enumerate() {
+            if j > 0 {
+                steps.push((*date, history.points[j - 1].1 as f64 / 100.));
+            }
+            steps.push((*date, *balance as f64 / 100.));
+        }
+        if let Some((_, balance)) = history.points.last() {
+            steps.push((end, *balance as f64 / 100.));
+        }
+        chart.draw_series(LineSeries::new(steps, color.stroke_width(2)))
+            .map_err(render_error)?
+            .label(history.username.clone())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color.stroke_width(2)));
+    }
+    chart.configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(render_error)
+}
+
+fn draw_category_share<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    stats: &Stats,
+    locale: &Locale
+) -> Result<(), TrackerError> {
+    let total: i64 = stats.categories.iter().map(|c| c.sum).sum();
+    if total <= 0 {
+        return draw_empty(area, &locale.text("chart.categories"), locale);
+    }
+    let count = stats.categories.len();
+    let mut chart = ChartBuilder::on(area)
+        .caption(locale.text("chart.categories"), ("sans-serif", 28))
+        .margin(15)
+        .x_label_area_size(35)
+        .y_label_area_size(140)
+        .build_cartesian_2d(0.0..100.0, -0.5..count as f64 - 0.5)
+        .map_err(render_error)?;
+    // largest category on top
+    chart.configure_mesh()
+        .disable_y_mesh()
+        .y_labels(count.min(12) + 1)
+        .y_label_formatter(&|y| match stats.categories.get(count.wrapping_sub(1).wrapping_sub(y.round() as usize)) {
+            Some(c) if y.fract() == 0. => category_label(c, locale),
+            _ => String::new(),
+        })
+        .x_label_formatter(&|x| format!("{}%", x))
+        .draw()
+        .map_err(render_error)?;
+    let label_style = TextStyle::from(("sans-serif", 16)).pos(Pos::new(HPos::Left, VPos::Center));
+    for (i, category) in stats.categories.iter().enumerate() {
+        let y = (count - 1 - i) as f64;
+        let share = category.sum as f64 * 100. / total as f64;
+        chart.draw_series(std::iter::once(Rectangle::new(
+            [(0., y - 0.35), (share, y + 0.35)],
+            Palette99::pick(i).filled()
+        ))).map_err(render_error)?;
+        chart.draw_series(std::iter::once(Text::new(
+            format!(" {:.0}% ({})", share, locale.money(category.sum)),
+            (share, y),
+            &label_style
+        ))).map_err(render_error)?;
+    }
+    Ok(())
+}
+
+fn draw_empty<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, title: &str, locale: &Locale) -> Result<(), TrackerError> {
+    let area = area.titled(title, ("sans-serif", 28)).map_err(render_error)?;
+    let (width, height) = area.dim_in_pixel();
+    let centered = TextStyle::from(("sans-serif", 18)).pos(Pos::new(HPos::Center, VPos::Center));
+    area.draw(&Text::new(locale.text("chart.empty"), (width as i32 / 2, height as i32 / 2), &centered))
+        .map_err(render_error)
+}
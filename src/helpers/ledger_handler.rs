@@ -2,8 +2,9 @@ use crate::types::{ledger::NewLedger, transaction::NewTransaction};
 
 pub fn create_ledger_from_transaction(transaction: &NewTransaction) -> NewLedger {
     NewLedger {
-        borrower: serde_json::to_string(&transaction.reciever).unwrap(),
-        owes: serde_json::to_string(&transaction.initiator).unwrap(),
-        sum: 0
+        borrower: transaction.reciever,
+        owes: transaction.initiator,
+        sum: 0,
+        event_id: transaction.event_id.clone(),
     }
 }
\ No newline at end of file
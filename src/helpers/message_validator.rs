@@ -1,94 +1,192 @@
 extern crate chrono;
 
-use std::error::Error;
-use teloxide::types::{Message, MessageKind, MediaKind, MediaText};
-use chrono::Utc;
+use chrono::{Duration, Months, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use diesel::SqliteConnection;
 use crate::{
     types::{
+        category::Category,
+        expense::{ExpenseShare, NewSharedExpense},
         transaction::NewTransaction, 
         user::User, 
-        transaction_type::TransactionType
+        transaction_type::TransactionType,
+        command_input::CommandInput,
+        error::{TrackerError, ValidationError}
     }, 
-    helpers::data_handler::user_operations::get_user_by_user_id
+    helpers::expression,
+    helpers::expense_parser::parse_expense,
+    helpers::data_handler::{
+        category_operations::get_or_insert_categories,
+        event_operations::get_active_event,
+        settings_operations::get_chat_settings,
+        user_operations::get_registered_user
+    }
 };
 
 use super::data_handler::user_operations::get_user_by_username;
 
 
-pub fn validate_loan_message(message: Message) -> Result<Vec<NewTransaction>, Box<dyn Error>> {
-    validate_message(message, TransactionType::Loan)
+pub fn validate_loan_message(conn: &SqliteConnection, input: &CommandInput) -> Result<Vec<NewTransaction>, TrackerError> {
+    validate_message(conn, input, TransactionType::Loan)
 }
 
-pub fn validate_pay_message(message: Message) -> Result<Vec<NewTransaction>, Box<dyn Error>> {
-    validate_message(message, TransactionType::Payment)
+pub fn validate_pay_message(conn: &SqliteConnection, input: &CommandInput) -> Result<Vec<NewTransaction>, TrackerError> {
+    validate_message(conn, input, TransactionType::Payment)
 }
 
 pub fn validate_message(
-    message: Message, 
+    conn: &SqliteConnection,
+    input: &CommandInput, 
     message_type: TransactionType
-) -> Result<Vec<NewTransaction>, Box<dyn Error>> {
+) -> Result<Vec<NewTransaction>, TrackerError> {
     // find sender -> throw any invalid states
-    let user = match extract_user(&message) {
-        Ok(user) => user,
-        Err(e) => return Err(e),
-    };
-    // check message type
-    let mes = match &message.kind {
-        MessageKind::Common(mes) => mes,
-        _ => return Err("Not common message".into()),
-    };
-    // extract media (text)
-    let media = match &mes.media_kind {
-        MediaKind::Text(media) => media,
-        _ => return Err("Not text media".into()),
+    let sender = get_registered_user(conn, &input.sender)?;
+    // "by:@ana" records it on behalf of somebody else, who is the payer then
+    let payer = match extract_payer(&input.text) {
+        Some(username) => match get_user_by_username(conn, username.clone())?.pop() {
+            Some(payer) => payer,
+            None => return Err(ValidationError::UnregisteredUser { username }.into()),
+        },
+        None => sender.clone(),
     };
-    // extract amount to be loaned to recievers
-    let amount = match extract_loan_amount(&media.text) {
-        Some(a) => a,
-        None => return Err("Amount not specified correctly".into()),
+    // find the amount, wherever in the message it is
+    let expense = parse_expense(&input.text);
+    let written = match expense.amount {
+        Some(written) => written,
+        None if expense.is_ambiguous() => return Err(ValidationError::AmbiguousAmount.into()),
+        None => return Err(ValidationError::InvalidAmount.into()),
     };
-    // extract all recievers
-    let recievers = match extract_recievers(media) {
-        Ok(recv) => recv,
-        Err(e) => return Err(e),
-    };
-    // extract description
-    let desctription = match extract_description(&media.text) {
-        Ok(desc) => desc,
-        Err(e) => return Err(e),
+    let currency = get_chat_settings(conn, &input.chat_id)?.currency;
+    if let Some(given) = written.currency.filter(|given| *given != currency) {
+        return Err(ValidationError::OtherCurrency { given, expected: currency }.into());
+    }
+    let (amount, calculation) = extract_loan_amount(&written.number)?;
+    // extract all recievers, the payer is not one of them
+    let mentions: Vec<String> = input.mentions.iter()
+        .filter(|name| !name.eq_ignore_ascii_case(&payer.username))
+        .cloned()
+        .collect();
+    let recievers = extract_recievers(conn, &mentions)?;
+    // the description shows the calculation too
+    let desctription = match (expense.description, calculation) {
+        (description, None) => description,
+        (description, Some(calculation)) if description.is_empty() => calculation,
+        (description, Some(calculation)) => format!("{} ({})", description, calculation),
     };
+    let categories = get_or_insert_categories(conn, &input.chat_id, &extract_hashtags(&input.text))?;
     // convert into transactions
-    let transactions = match into_transactions(
-            user, 
-            amount, 
-            recievers, 
-            desctription,
-            message_type
-        ) {
-        Ok(transactions) => transactions,
-        Err(e) => return Err(e),
-    };
+    let mut transactions = into_transactions(
+        payer, 
+        amount, 
+        recievers, 
+        desctription,
+        categories,
+        input.sent,
+        message_type
+    )?;
+    // they go to the active event of the chat, if there is one
+    let event_id = get_active_event(conn, &input.chat_id)?.map(|e| e.id);
+    transactions.iter_mut().for_each(|t| {
+        t.event_id = event_id.clone();
+        t.recorded_by = Some(sender.user_id);
+    });
     Ok(transactions)
 }
 
 
-fn extract_description(text: &String) -> Result<String, Box<dyn Error>> {
-    Ok(text.to_string())
+/// Shared expense of the message ("/expense @ana=60 @luka=40 @maja @jan
+/// dinner"): everybody mentioned shares it equally, those with an amount
+/// paid that much of it. The cents that don't split go to the first ones.
+pub fn validate_expense_message(conn: &SqliteConnection, input: &CommandInput) -> Result<(NewSharedExpense, Vec<User>), TrackerError> {
+    get_registered_user(conn, &input.sender)?;
+    // usernames and what they paid, in cents
+    let mut people: Vec<(String, i64)> = vec![];
+    for mention in input.text.split_whitespace().filter_map(|word| word.strip_prefix('@')) {
+        let name: String = mention.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+        let paid = match mention[name.len()..].strip_prefix('=') {
+            Some(amount) => extract_loan_amount(amount)?.0,
+            None => 0,
+        };
+        match people.iter_mut().find(|(known, _)| known.eq_ignore_ascii_case(&name)) {
+            Some(person) => person.1 += paid,
+            None if !name.is_empty() => people.push((name, paid)),
+            None => (),
+        }
+    }
+    let total: i64 = people.iter().map(|(_, paid)| paid).sum();
+    if total == 0 || people.len() < 2 {
+        return Err(ValidationError::InvalidExpense.into());
+    }
+    let count = people.len() as i64;
+    let mut users = vec![];
+    let mut shares = vec![];
+    for (i, (name, paid)) in people.into_iter().enumerate() {
+        let user = match get_user_by_username(conn, name.clone())?.pop() {
+            Some(user) => user,
+            None => return Err(ValidationError::UnregisteredUser { username: name }.into()),
+        };
+        let share = total / count + i64::from((i as i64) < total % count);
+        match (i32::try_from(paid), i32::try_from(share)) {
+            (Ok(paid), Ok(share)) => shares.push(ExpenseShare { user_id: user.user_id, paid, share }),
+            _ => return Err(ValidationError::InvalidAmount.into()),
+        }
+        users.push(user);
+    }
+    let expense = NewSharedExpense {
+        chat_id: input.chat_id,
+        description: parse_expense(&input.text).description,
+        created: input.sent,
+        shares,
+        categories: get_or_insert_categories(conn, &input.chat_id, &extract_hashtags(&input.text))?,
+    };
+    Ok((expense, users))
+}
+
+/// Hashtags of the message, lowercase and without the '#', in order
+pub fn extract_hashtags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for word in text.split_whitespace() {
+        let tag = match word.strip_prefix('#') {
+            Some(tag) => tag.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_').to_lowercase(),
+            None => continue,
+        };
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Number of transactions and category to filter by in "/history 20 #food".
+/// Shows the last 10 transactions of any category by default.
+pub fn extract_history_query(text: &str) -> Result<(i64, Option<String>), TrackerError> {
+    let mut count = 10;
+    for word in text.split_whitespace().skip(1).filter(|word| !word.starts_with('#')) {
+        count = match word.parse::<i64>() {
+            Ok(c) if c > 0 => c,
+            _ => return Err(ValidationError::InvalidCount.into()),
+        };
+    }
+    Ok((count, extract_hashtags(text).into_iter().next()))
 }
 
 
 fn into_transactions(
     sender: User, 
-    amount: f64, 
+    amount: i64, 
     recievers: Vec<User>, 
     description: String, 
+    categories: Vec<Category>,
+    created: NaiveDateTime,
     trans_type: TransactionType
-) -> Result<Vec<NewTransaction>, Box<dyn Error>> {
+) -> Result<Vec<NewTransaction>, TrackerError> {
     if recievers.is_empty() {
-        return Err("No recievers recognised.".into());
+        return Err(ValidationError::NoRecievers.into());
     }
-    let int_amount = (amount * 100.) as i32;
-    let one_share = int_amount / recievers.len() as i32;
+    let one_share = match i32::try_from(amount / recievers.len() as i64) {
+        Ok(share) => share,
+        Err(_) => return Err(ValidationError::InvalidAmount.into()),
+    };
     let mut transactions = vec![];
     recievers.into_iter().for_each(|reciever| {
         transactions.push(NewTransaction {
@@ -97,63 +195,169 @@ fn into_transactions(
             reciever: reciever.user_id,
             sum: one_share,
             description: description.clone(),
-            created: Utc::now().naive_utc(),
+            created,
+            event_id: None,
+            expense_id: None,
+            recorded_by: None,
+            categories: categories.clone(),
         });
     });
     Ok(transactions)
 }
 
 
-fn extract_loan_amount(text: &str) -> Option<f64> {
-    let text_fragments = text.split_whitespace().collect::<Vec<&str>>();
-    if text_fragments.len() < 2 {
+/// Amount of the loan in cents. A calculation ("(3*8.5+4)") is worked out
+/// and comes back with its result for the description ("3*8.5+4 = 29.50").
+fn extract_loan_amount(word: &str) -> Result<(i64, Option<String>), TrackerError> {
+    if !expression::is_expression(word) {
+        return match parse_amount(word) {
+            Some(amount) => Ok((amount, None)),
+            None => Err(ValidationError::InvalidAmount.into()),
+        };
+    }
+    let amount = match expression::evaluate(word) {
+        Ok(amount) if amount >= 0 => amount,
+        Ok(_) => return Err(ValidationError::InvalidAmount.into()),
+        Err(e) => return Err(ValidationError::InvalidExpression { expression: word.to_string(), token: e.token }.into()),
+    };
+    // echo the result the way the calculation was written
+    let mut result = format!("{}.{:02}", amount / 100, amount % 100);
+    if word.contains(',') {
+        result = result.replace('.', ",");
+    }
+    let shown = match word.strip_prefix('(').and_then(|inner| inner.strip_suffix(')')) {
+        Some(inner) if expression::evaluate(inner).is_ok() => inner,
+        _ => word,
+    };
+    Ok((amount, Some(format!("{} = {}", shown, result))))
+}
+
+/// Amount in cents, written the English or the Slovenian way: "12.50",
+/// "12,50", "1,234.50" and "1.234,50" all work. A lone separator followed
+/// by three digits groups thousands ("1.234" is 1234), loans and payments
+/// ask first whether that was meant (see `expense_parser::readings`).
+pub fn parse_amount(text: &str) -> Option<i64> {
+    if text.is_empty() || !text.chars().all(|c| c.is_ascii_digit() || c == '.' || c == ',') {
+        return None;
+    }
+    let separators: Vec<(usize, char)> = text.char_indices().filter(|(_, c)| !c.is_ascii_digit()).collect();
+    let decimal = match separators.last() {
+        Some((i, separator)) => {
+            let decimals = text.len() - i - 1;
+            let mixed = separators.iter().any(|(_, c)| c != separator);
+            let lone = separators.len() == 1;
+            match (mixed || lone) && (1..=2).contains(&decimals) {
+                true => Some(*i),
+                false => None,
+            }
+        },
+        None => None,
+    };
+    let (whole, fraction) = match decimal {
+        Some(i) => (&text[..i], &text[i + 1..]),
+        None => (text, ""),
+    };
+    // whatever separates the whole part has to group thousands
+    let groups: Vec<&str> = whole.split(['.', ',']).collect();
+    let grouped = groups.len() == 1
+        || (whole.contains('.') != whole.contains(',')
+            && (1..=3).contains(&groups[0].len())
+            && groups[1..].iter().all(|group| group.len() == 3));
+    if !grouped || (groups.concat().is_empty() && fraction.is_empty()) {
         return None;
     }
-    let amount_fragment = text_fragments[1].replace(',', ".");
-    let amount = amount_fragment.parse::<f64>();
-    match amount {
-        Ok(a) => Some(a),
-        Err(_) => None,
+    let whole: i64 = match groups.concat().as_str() {
+        "" => 0,
+        digits => digits.parse().ok()?,
+    };
+    let fraction: i64 = match fraction.len() {
+        0 => 0,
+        1 => fraction.parse::<i64>().ok()? * 10,
+        _ => fraction.parse().ok()?,
+    };
+    whole.checked_mul(100)?.checked_add(fraction)
+}
+
+/// Start of the time range given in a stats command ("/stats chart 6m").
+/// Supports days, weeks, months and years (30d, 8w, 6m, 1y) and "all",
+/// which returns `None`. Defaults to the last 12 months.
+pub fn extract_time_range(text: &str, now: NaiveDateTime) -> Result<Option<NaiveDateTime>, TrackerError> {
+    let range = text.split_whitespace()
+        .skip(1)
+        .find(|word| !word.eq_ignore_ascii_case("chart") && !word.starts_with('#'));
+    let range = match range {
+        Some(r) => r.to_lowercase(),
+        None => return Ok(now.checked_sub_months(Months::new(12))),
+    };
+    if range == "all" {
+        return Ok(None);
+    }
+    match shift_by_period(now, &range, false) {
+        Some(since) => Ok(Some(since)),
+        None => Err(ValidationError::InvalidTimeRange.into()),
+    }
+}
+
+/// Moment a period (30d, 8w, 6m, 1y) before or after `now`
+pub fn shift_by_period(now: NaiveDateTime, period: &str, forward: bool) -> Option<NaiveDateTime> {
+    let (count, unit) = match period.char_indices().last() {
+        Some((i, _)) => period.split_at(i),
+        None => return None,
+    };
+    let count = count.parse::<u32>().ok()?;
+    let months = match unit {
+        "d" => return shift_by(now, Duration::days(count as i64), forward),
+        "w" => return shift_by(now, Duration::weeks(count as i64), forward),
+        "m" => Months::new(count),
+        "y" => Months::new(count.checked_mul(12)?),
+        _ => return None,
+    };
+    match forward {
+        true => now.checked_add_months(months),
+        false => now.checked_sub_months(months),
+    }
+}
+
+fn shift_by(now: NaiveDateTime, duration: Duration, forward: bool) -> Option<NaiveDateTime> {
+    match forward {
+        true => now.checked_add_signed(duration),
+        false => now.checked_sub_signed(duration),
+    }
+}
+
+/// Due date of a loan ("/loan 20 @ana due:2026-11-01"), the end of that day
+/// in the timezone of the chat (UTC)
+pub fn extract_due_date(text: &str, timezone: Tz) -> Result<Option<NaiveDateTime>, TrackerError> {
+    let date = match text.split_whitespace().find_map(|word| word.strip_prefix("due:")) {
+        Some(date) => date,
+        None => return Ok(None),
+    };
+    let due = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+        .and_then(|d| d.and_hms_opt(23, 59, 59))
+        .and_then(|end| timezone.from_local_datetime(&end).latest());
+    match due {
+        Some(due) => Ok(Some(due.naive_utc())),
+        None => Err(ValidationError::InvalidDueDate.into()),
     }
 }
 
-fn extract_recievers(message: &MediaText)  -> Result<Vec<User>, Box<dyn Error>> {
+/// Username of whoever the transaction is recorded for ("/loan 30 @luka
+/// by:@ana")
+pub fn extract_payer(text: &str) -> Option<String> {
+    text.split_whitespace()
+        .find_map(|word| word.strip_prefix("by:@"))
+        .map(|name| name.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect::<String>())
+        .filter(|name| !name.is_empty())
+}
+
+fn extract_recievers(conn: &SqliteConnection, mentions: &[String])  -> Result<Vec<User>, TrackerError> {
     let mut users = vec![];
-    for entity in message.to_owned().entities.into_iter() {
-        let username = match entity.kind {
-            teloxide::types::MessageEntityKind::Mention => &message.text[(entity.offset + 1)..(entity.offset+entity.length)],
-            _ => continue
-        };
-        let user = match get_user_by_username(username.to_owned()) {
-            Ok(mut found_users) => {
-                match found_users.pop() {
-                    Some(u) => u,
-                    None => continue,
-                }
-            },
-            Err(e) => return Err(e.into())
+    for username in mentions.iter() {
+        let user = match get_user_by_username(conn, username.to_owned())?.pop() {
+            Some(u) => u,
+            None => continue,
         };
         users.push(user);
     }
     Ok(users)
 }
-
-fn extract_user(message: &Message) -> Result<User, Box<dyn Error>> {
-    let user_id = match message.from() {
-        None => return Err("You don't seem to be registered. Just type /register and retry the command.".into()),
-        Some(user) => user.id
-    };
-    match get_user_by_user_id(&user_id) {
-        Err(e) => Err(e.into()),
-        Ok(mut users) => {
-            if users.len() == 1 {
-                match users.pop() {
-                    None => Err("You don't seem to be registered. Just type /register and retry the command.".into()),
-                    Some(u) => Ok(u)
-                }
-            } else {
-                Err("Seems like there is an issue with too many registered users with the same ID. Please contact the developer.".into())
-            }
-        }
-    }
-}
\ No newline at end of file
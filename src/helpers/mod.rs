@@ -1,7 +1,10 @@
-
 pub mod message_validator;
 pub mod cycle_detector;
 pub mod data_handler;
 pub mod transaction_handler;
 pub mod ledger_handler;
-pub mod text_helper;
\ No newline at end of file
+pub mod text_helper;
+pub mod image_helper;
+pub mod i18n;
+pub mod expression;
+pub mod expense_parser;
@@ -1,38 +1,210 @@
+use std::collections::HashMap;
+use chrono::{NaiveDateTime, Weekday};
 use teloxide::types::UserId;
 
-use crate::types::{user::User, transaction_type::TransactionType, ledger::Ledger};
+use crate::types::{
+    approval::Approval,
+    command_input::extract_mentions,
+    user::User,
+    category::Category,
+    digest::Digest,
+    draft::Draft,
+    event::Event,
+    expense::SharedExpense,
+    recurring::{Frequency, Recurring},
+    reminder::ReminderPreference,
+    role::Role,
+    settings::{ChatSettings, Language, ReminderPolicy},
+    transaction::Transaction,
+    transaction_type::TransactionType,
+    ledger::Ledger,
+    graph::Graph,
+    stats::{CategoryTotal, Stats, UNCATEGORISED},
+    transaction::TransactionReceipt,
+    error::{TrackerError, ValidationError}
+};
+use crate::tracker::{
+    Balance,
+    Button,
+    CategoryUpdate,
+    DigestReport,
+    DigestUpdate,
+    EventUpdate,
+    RecurringUpdate,
+    Registration,
+    Statement,
+    ReminderNotice,
+    ReminderReason,
+    ReminderUpdate,
+    RoleUpdate
+};
+use super::expense_parser::{readings, WrittenAmount};
+use super::i18n::Locale;
 
-pub const LOAN_DESCRIPTION: &str = "/loan <amount> <@people>\nThe command loans money to the specified people. The action is constructed from 3 parts (<command> <amount> <poeple>). Amount is a numeric value where the decimal point may be specified with y dot '.' and not comma ','. You may list as many people as you wish as long as they are tagged with a mention (@name). The fee will bi equaly split among the target people.";
-pub const PAY_DESCRIPTION: &str = "/pay <amount> <@people>\nThe command will repay the full amount specified to all mentioned people (as long as they are mentioned with @name). If you pay more than you own, the reviever will own you the difference after the transaction completes.";
-pub const HISTORY_DESCRIPTION: &str = "/history <number of transactions>\nThe histroy command will display the last completed transactions. You may also specify the amount of transactions displayed, but it defaults to 10 if the argument is not specified.";
-pub const BALANCE_DESCRIPTION: &str = "/balance\nThe command will display the current state of debt.";
+/// "/help" lists the commands, "/help <command>" explains one of them (if
+/// there is more to say than the one line of the list)
+pub fn generate_help_response(commands: &[String], topic: Option<&str>, locale: &Locale) -> String {
+    let topic = topic.map(|t| t.trim_start_matches('/').to_lowercase());
+    if let Some(topic) = topic.filter(|t| commands.contains(t)) {
+        let detail = format!("help.detail.{}", topic);
+        let text = locale.text(&detail);
+        if text != detail {
+            return text;
+        }
+    }
+    let mut out = format!("{}\n", locale.text("help.title"));
+    for command in commands.iter() {
+        out = format!("{}\n/{} — {}", out, command, locale.text(&format!("help.command.{}", command)));
+    }
+    format!("{}\n\n{}", out, locale.text("help.more"))
+}
 
-pub fn generate_transaction_response(
-    sum: i32, 
-    sender: User, 
-    reciever: User, 
-    success: bool, 
-    trans_type: TransactionType
+pub fn generate_transactions_response(
+    receipts: &[Result<TransactionReceipt, TrackerError>],
+    locale: &Locale
 ) -> String {
-    let action = match trans_type {
-        TransactionType::Loan => "loaned".to_string(),
-        TransactionType::Payment => "payed".to_string(),
-    };
-    match success {
-        true => format!(
-            "{} {} {}€ to {}!", 
-            sender.username,
-            action,
-            (sum as f32 / 100.), 
-            reciever.username
-        ),
-       false => "Oops! Something went wrong when processing the transaction! :(".to_string()
+    receipts.iter()
+        .map(|receipt| match receipt {
+            Ok(receipt) => generate_transaction_response(receipt, locale),
+            Err(e) => generate_error_response(e, locale),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn generate_loan_response(
+    receipts: &[Result<TransactionReceipt, TrackerError>],
+    due: Option<NaiveDateTime>,
+    locale: &Locale
+) -> String {
+    let out = generate_transactions_response(receipts, locale);
+    match due {
+        Some(due) if receipts.iter().any(|r| r.is_ok()) => {
+            format!("{}\n{}", out, locale.format("loan.due", &[("date", &locale.date(&due))]))
+        },
+        _ => out,
+    }
+}
+
+/// What everybody paid and the loans that even it out
+pub fn generate_shared_expense_response(
+    expense: &SharedExpense,
+    users: &[User],
+    receipts: &[Result<TransactionReceipt, TrackerError>],
+    locale: &Locale
+) -> String {
+    let header = locale.format("expense.recorded", &[
+        ("payers", &describe_payers(expense, users, locale)),
+        ("sum", &locale.money(expense.total())),
+        ("count", &expense.shares.len().to_string()),
+    ]);
+    match receipts.is_empty() {
+        true => format!("{}\n{}", header, locale.text("expense.even")),
+        false => format!("{}\n{}", header, generate_transactions_response(receipts, locale)),
+    }
+}
+
+/// "ana (60€), luka (40€)"
+fn describe_payers(expense: &SharedExpense, users: &[User], locale: &Locale) -> String {
+    expense.shares.iter()
+        .filter(|share| share.paid > 0)
+        .map(|share| locale.format("expense.payer", &[
+            ("name", &map_user_id_to_username(&share.user_id, users).unwrap_or_else(|| locale.text("someone"))),
+            ("sum", &locale.money(share.paid as i64)),
+        ]))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn generate_transaction_response(receipt: &TransactionReceipt, locale: &Locale) -> String {
+    let TransactionReceipt { transaction_type, sender, reciever, sum } = receipt;
+    let key = match transaction_type {
+        TransactionType::Loan => "transaction.loaned",
+        TransactionType::Payment => "transaction.paid",
+    };
+    locale.format(key, &[
+        ("sender", &sender.username),
+        ("sum", &locale.money(*sum as i64)),
+        ("receiver", &reciever.username),
+    ])
+}
+
+pub fn generate_registration_response(registration: &Registration, locale: &Locale) -> String {
+    match registration {
+        Registration::Registered(user) => locale.format("registration.registered", &[("name", &user.username)]),
+        Registration::AddedToChat(_) => locale.text("registration.added"),
+        Registration::UsernameUpdated(user) => locale.format("registration.updated", &[("name", &user.username)]),
+        Registration::AlreadyRegistered(_) => locale.text("registration.already"),
+    }
+}
+
+pub fn generate_error_response(error: &TrackerError, locale: &Locale) -> String {
+    if let TrackerError::Validation(ValidationError::InvalidExpression { expression, token }) = error {
+        return locale.format("error.invalid_expression", &[("expression", expression), ("token", token)]);
     }
+    if let TrackerError::Validation(ValidationError::OtherCurrency { given, expected }) = error {
+        return locale.format("error.other_currency", &[("given", given), ("expected", expected)]);
+    }
+    if let TrackerError::Validation(ValidationError::UnregisteredUser { username }) = error {
+        return locale.format("error.unregistered_user", &[("name", username)]);
+    }
+    if let TrackerError::Validation(ValidationError::NotMember { username }) = error {
+        return locale.format("error.not_member", &[("name", username)]);
+    }
+    let key = match error {
+        TrackerError::Validation(e) => match e {
+            ValidationError::UnknownSender => "error.unknown_sender",
+            ValidationError::MissingUsername => "error.missing_username",
+            ValidationError::NotText => "error.not_text",
+            ValidationError::InvalidAmount => "error.invalid_amount",
+            ValidationError::InvalidExpression { .. } => "error.invalid_expression",
+            ValidationError::AmbiguousAmount => "error.ambiguous_amount",
+            ValidationError::OtherCurrency { .. } => "error.other_currency",
+            ValidationError::NoRecievers => "error.no_receivers",
+            ValidationError::InvalidTimeRange => "error.invalid_time_range",
+            ValidationError::InvalidCount => "error.invalid_count",
+            ValidationError::UnknownCategory => "error.unknown_category",
+            ValidationError::InvalidCategory => "error.invalid_category",
+            ValidationError::InvalidSchedule => "error.invalid_schedule",
+            ValidationError::UnknownRecurring => "error.unknown_recurring",
+            ValidationError::InvalidDueDate => "error.invalid_due_date",
+            ValidationError::InvalidReminder => "error.invalid_reminder",
+            ValidationError::InvalidDigest => "error.invalid_digest",
+            ValidationError::InvalidTimezone => "error.invalid_timezone",
+            ValidationError::InvalidSetting => "error.invalid_setting",
+            ValidationError::InvalidLanguage => "error.invalid_language",
+            ValidationError::UnknownApproval => "error.unknown_approval",
+            ValidationError::UnknownDraft => "error.unknown_draft",
+            ValidationError::InvalidEvent => "error.invalid_event",
+            ValidationError::UnknownEvent => "error.unknown_event",
+            ValidationError::EventExists => "error.event_exists",
+            ValidationError::InvalidExpense => "error.invalid_expense",
+            ValidationError::UnregisteredUser { .. } => "error.unregistered_user",
+            ValidationError::InvalidRole => "error.invalid_role",
+            ValidationError::NotMember { .. } => "error.not_member",
+            ValidationError::OwnerRole => "error.owner_role",
+            ValidationError::OtherUser => "error.other_user",
+            ValidationError::InvalidApiToken => "error.invalid_api_token",
+            ValidationError::GroupOnly => "error.group_only",
+            ValidationError::RecurringForOther => "error.recurring_for_other",
+        },
+        TrackerError::NotRegistered => "error.not_registered",
+        TrackerError::Forbidden => "error.forbidden",
+        TrackerError::OwnerOnly => "error.owner_only",
+        TrackerError::ReadOnly => "error.read_only",
+        TrackerError::NotParty => "error.not_party",
+        TrackerError::OpenBalances => "error.open_balances",
+        TrackerError::Storage(_) => "error.storage",
+        TrackerError::Consistency(_) => "error.consistency",
+        TrackerError::Rendering(_) => "error.rendering",
+    };
+    locale.text(key)
 }
 
 pub fn generate_balance_response(
     ledgers: Vec<Ledger>,
     users: Vec<User>,
+    locale: &Locale,
 ) -> String {
     let mut out = "".to_string();
     for ledger in ledgers.into_iter() {
@@ -47,17 +219,587 @@ pub fn generate_balance_response(
             Some(name) => name,
             None => continue
         };
-        let sum = ledger.sum as f32 / 100.;
-        out = format!("{}\n{} owes {}€ to {}", out, borrower, sum, owes);
+        let line = locale.format("balance.owes", &[
+            ("borrower", &borrower),
+            ("sum", &locale.money(ledger.sum as i64)),
+            ("owes", &owes),
+        ]);
+        out = format!("{}\n{}", out, line);
+    }
+    out
+}
+
+pub fn generate_recurring_response(update: &RecurringUpdate, locale: &Locale) -> String {
+    match update {
+        RecurringUpdate::Listed(listed) => {
+            if listed.is_empty() {
+                return locale.text("recurring.empty");
+            }
+            let mut out = locale.text("recurring.list");
+            for (i, recurring) in listed.iter().enumerate() {
+                out = format!("{}\n{}. {}", out, i + 1, describe_recurring(recurring, locale));
+            }
+            out
+        },
+        RecurringUpdate::Added(recurring) => {
+            locale.format("recurring.added", &[("recurring", &describe_recurring(recurring, locale))])
+        },
+        RecurringUpdate::Paused(recurring) => {
+            locale.format("recurring.paused", &[("recurring", &describe_recurring(recurring, locale))])
+        },
+        RecurringUpdate::Resumed(recurring) => {
+            locale.format("recurring.resumed", &[("recurring", &describe_recurring(recurring, locale))])
+        },
+        RecurringUpdate::Deleted(recurring) => locale.format("recurring.deleted", &[
+            ("frequency", &describe_frequency(&recurring.frequency, locale)),
+            ("loan", &recurring.loan),
+        ]),
+    }
+}
+
+pub fn generate_recurring_run_response(
+    recurring: &Recurring,
+    run_at: &NaiveDateTime,
+    receipts: &Result<Vec<Result<TransactionReceipt, TrackerError>>, TrackerError>,
+    locale: &Locale
+) -> String {
+    let outcome = match receipts {
+        Ok(receipts) => generate_transactions_response(receipts, locale),
+        Err(e) => generate_error_response(e, locale),
+    };
+    let title = locale.format("recurring.run", &[
+        ("date", &locale.date(run_at)),
+        ("frequency", &describe_frequency(&recurring.frequency, locale)),
+    ]);
+    format!("{}\n{}", title, outcome)
+}
+
+fn describe_recurring(recurring: &Recurring, locale: &Locale) -> String {
+    let state = match recurring.paused {
+        true => locale.text("recurring.state.paused"),
+        false => locale.format("recurring.state.next", &[("date", &locale.date(&recurring.next_run))]),
+    };
+    locale.format("recurring.describe", &[
+        ("frequency", &describe_frequency(&recurring.frequency, locale)),
+        ("loan", &recurring.loan),
+        ("state", &state),
+    ])
+}
+
+/// "every monday", "monthly on the 1st"
+fn describe_frequency(frequency: &Frequency, locale: &Locale) -> String {
+    match frequency {
+        Frequency::Daily => locale.text("frequency.daily"),
+        Frequency::Weekly(day) => locale.text(match day {
+            Weekday::Mon => "frequency.mon",
+            Weekday::Tue => "frequency.tue",
+            Weekday::Wed => "frequency.wed",
+            Weekday::Thu => "frequency.thu",
+            Weekday::Fri => "frequency.fri",
+            Weekday::Sat => "frequency.sat",
+            Weekday::Sun => "frequency.sun",
+        }),
+        Frequency::Monthly(day) => locale.format("frequency.monthly", &[("day", &locale.ordinal(*day))]),
+    }
+}
+
+pub fn generate_reminder_response(notice: &ReminderNotice, locale: &Locale) -> String {
+    let reason = match &notice.reason {
+        ReminderReason::Overdue(due) => locale.format("reminder.overdue", &[("date", &locale.date(due))]),
+        ReminderReason::OverThreshold(threshold) => {
+            locale.format("reminder.over_threshold", &[("sum", &locale.money(*threshold as i64))])
+        },
+    };
+    // mention the borrower in groups so they get notified
+    let key = match notice.private {
+        true => "reminder.private",
+        false => "reminder.group",
+    };
+    locale.format(key, &[
+        ("borrower", &notice.borrower.username),
+        ("sum", &locale.money(notice.debt)),
+        ("owes", &notice.owes.username),
+        ("reason", &reason),
+    ])
+}
+
+pub fn generate_reminder_update_response(update: &ReminderUpdate, locale: &Locale) -> String {
+    let threshold_text = |threshold: &Option<i32>| match threshold {
+        Some(threshold) => locale.format("reminder.threshold", &[("sum", &locale.money(*threshold as i64))]),
+        None => locale.text("reminder.no_threshold"),
+    };
+    let preference_text = |preference: &ReminderPreference| match (preference.opted_out, preference.snoozed_until) {
+        (true, _) => locale.text("reminder.opted_out"),
+        (false, Some(until)) => locale.format("reminder.snoozed", &[("date", &locale.date(&until))]),
+        (false, None) => locale.text("reminder.on"),
+    };
+    match update {
+        ReminderUpdate::Status(preference, threshold) => format!("{}\n{}", preference_text(preference), threshold_text(threshold)),
+        ReminderUpdate::Preference(preference) => preference_text(preference),
+        ReminderUpdate::Threshold(threshold) => threshold_text(threshold),
+    }
+}
+
+pub fn generate_digest_update_response(update: &DigestUpdate, locale: &Locale) -> String {
+    match update {
+        DigestUpdate::Status(Some(digest)) => locale.format("digest.status", &[
+            ("digest", &describe_digest(digest, locale)),
+            ("date", &Locale { timezone: digest.timezone, ..locale.clone() }.date(&digest.next_run)),
+        ]),
+        DigestUpdate::Status(None) => locale.text("digest.none"),
+        DigestUpdate::Scheduled(digest) => locale.format("digest.scheduled", &[("digest", &describe_digest(digest, locale))]),
+        DigestUpdate::Stopped => locale.text("digest.stopped"),
+    }
+}
+
+pub fn generate_digest_response(report: &DigestReport, locale: &Locale) -> String {
+    let users = &report.balance.users;
+    let mut out = locale.format("digest.title", &[("digest", &describe_digest(&report.digest, locale))]);
+    if report.balance.ledgers.iter().any(|l| l.sum != 0) {
+        out = format!(
+            "{}\n\n{}{}",
+            out,
+            locale.text("digest.balance"),
+            generate_balance_response(report.balance.ledgers.clone(), users.clone(), locale)
+        );
+    } else {
+        out = format!("{}\n\n{}", out, locale.text("digest.even"));
+    }
+    out = format!("{}\n\n{}", out, locale.format("digest.since", &[("date", &locale.date(&report.since))]));
+    if report.transactions.is_empty() {
+        out = format!("{}\n{}", out, locale.text("digest.no_transactions"));
+    } else {
+        out = format!("{}{}", out, generate_history_response(&report.transactions, users, &report.tagged, &report.expenses, locale));
+    }
+    if !report.transfers.is_empty() {
+        out = format!("{}\n\n{}{}", out, locale.text("digest.settle"), generate_transfer_lines(&report.transfers, users, locale));
     }
     out
 }
 
-fn map_user_id_to_username(uid: &UserId, users: &Vec<User>) -> Option<String> {
-    for user in users.into_iter() {
+/// Net debt with every counterparty, the total and the latest transactions
+pub fn generate_statement_response(statement: &Statement, locale: &Locale) -> String {
+    let mut out = locale.text("statement.title");
+    for (counterparty, sum) in statement.balances.iter() {
+        let name = map_user_id_to_username(counterparty, &statement.users).unwrap_or_else(|| locale.text("someone"));
+        let key = match *sum > 0 {
+            true => "statement.owed_by",
+            false => "statement.owed_to",
+        };
+        out = format!("{}\n{}", out, locale.format(key, &[("name", &name), ("sum", &locale.money(sum.abs()))]));
+    }
+    let total: i64 = statement.balances.iter().map(|(_, sum)| sum).sum();
+    let summary = match total {
+        0 if statement.balances.is_empty() => locale.text("statement.even"),
+        0 => locale.text("statement.net_even"),
+        total if total > 0 => locale.format("statement.net_owed", &[("sum", &locale.money(total))]),
+        total => locale.format("statement.net_owes", &[("sum", &locale.money(-total))]),
+    };
+    out = format!("{}\n{}\n\n{}", out, summary, locale.text("statement.recent"));
+    let history = generate_history_response(&statement.transactions, &statement.users, &HashMap::new(), &HashMap::new(), locale);
+    match statement.transactions.is_empty() {
+        true => format!("{}\n{}", out, history),
+        false => format!("{}{}", out, history),
+    }
+}
+
+/// "luka pays 20€ to ana", one transfer per line (each starts with a line break)
+fn generate_transfer_lines(transfers: &[(UserId, UserId, i64)], users: &[User], locale: &Locale) -> String {
+    let unknown = || locale.text("someone");
+    transfers.iter()
+        .map(|(payer, payee, sum)| format!("\n{}", locale.format("digest.transfer", &[
+            ("payer", &map_user_id_to_username(payer, users).unwrap_or_else(unknown)),
+            ("sum", &locale.money(*sum)),
+            ("payee", &map_user_id_to_username(payee, users).unwrap_or_else(unknown)),
+        ])))
+        .collect()
+}
+
+/// "/balance", headed by the name of the event when one is active
+pub fn generate_event_balance_response(balance: Balance, locale: &Locale) -> String {
+    let event = match balance.event {
+        Some(event) => event,
+        None => return generate_balance_response(balance.ledgers, balance.users, locale),
+    };
+    let title = locale.format("event.balance", &[("event", &event.name)]);
+    match balance.ledgers.iter().any(|l| l.sum != 0) {
+        true => format!("{}{}", title, generate_balance_response(balance.ledgers, balance.users, locale)),
+        false => format!("{}\n{}", title, locale.text("digest.even")),
+    }
+}
+
+/// "/settle" of the everyday debt or of the active event
+pub fn generate_settlement_response(transfers: &[(UserId, UserId, i64)], users: &[User], event: Option<&Event>, locale: &Locale) -> String {
+    if transfers.is_empty() {
+        return locale.text("digest.even");
+    }
+    let title = match event {
+        Some(event) => locale.format("event.settle", &[("event", &event.name)]),
+        None => locale.text("digest.settle"),
+    };
+    format!("{}{}", title, generate_transfer_lines(transfers, users, locale))
+}
+
+pub fn generate_event_response(update: &EventUpdate, locale: &Locale) -> String {
+    match update {
+        EventUpdate::Listed(events) if events.is_empty() => locale.text("event.empty"),
+        EventUpdate::Listed(events) => {
+            let mut out = locale.text("event.list");
+            for event in events.iter() {
+                let line = match (event.closed, event.active) {
+                    (Some(closed), _) => locale.format("event.closed", &[("event", &event.name), ("date", &locale.date(&closed))]),
+                    (None, true) => locale.format("event.active", &[("event", &event.name), ("date", &locale.date(&event.created))]),
+                    (None, false) => locale.format("event.open", &[("event", &event.name), ("date", &locale.date(&event.created))]),
+                };
+                out = format!("{}\n{}", out, line);
+            }
+            if !events.iter().any(|e| e.active) {
+                out = format!("{}\n{}", out, locale.text("event.everyday"));
+            }
+            out
+        },
+        EventUpdate::Started(event) => locale.format("event.started", &[("event", &event.name)]),
+        EventUpdate::Switched(Some(event)) => locale.format("event.switched", &[("event", &event.name)]),
+        EventUpdate::Switched(None) => locale.text("event.switched_everyday"),
+        EventUpdate::Closed(event, transfers, _) if transfers.is_empty() => {
+            format!("{}\n{}", locale.format("event.archived", &[("event", &event.name)]), locale.text("digest.even"))
+        },
+        EventUpdate::Closed(event, transfers, users) => format!(
+            "{}\n{}{}\n{}",
+            locale.format("event.archived", &[("event", &event.name)]),
+            locale.text("event.final"),
+            generate_transfer_lines(transfers, users, locale),
+            locale.text("event.carried_over")
+        ),
+    }
+}
+
+fn describe_digest(digest: &Digest, locale: &Locale) -> String {
+    locale.format("digest.schedule", &[
+        ("frequency", &describe_frequency(&digest.frequency, locale)),
+        ("time", &digest.time.format("%H:%M").to_string()),
+        ("timezone", digest.timezone.name()),
+    ])
+}
+
+pub fn generate_stats_response(stats: &Stats, locale: &Locale) -> String {
+    let period = match stats.since {
+        Some(since) => locale.format("stats.since", &[("date", &locale.date(&since))]),
+        None => locale.text("stats.all_time"),
+    };
+    let mut out = match &stats.category {
+        Some(category) => locale.format("stats.period_in_category", &[("period", &period), ("category", category)]),
+        None => locale.format("stats.period", &[("period", &period)]),
+    };
+    if stats.loan_count + stats.payment_count == 0 {
+        return format!("{}\n{}", out, locale.text("stats.empty"));
+    }
+    let loans = locale.format("stats.loans", &[
+        ("count", &stats.loan_count.to_string()),
+        ("sum", &locale.money(stats.loaned())),
+    ]);
+    let payments = locale.format("stats.payments", &[
+        ("count", &stats.payment_count.to_string()),
+        ("sum", &locale.money(stats.payed())),
+    ]);
+    out = format!("{}\n{}\n{}", out, loans, payments);
+    let total: i64 = stats.categories.iter().map(|c| c.sum).sum();
+    if total > 0 {
+        out = format!("{}\n\n{}", out, locale.text("stats.by_category"));
+        for category in stats.categories.iter() {
+            out = format!(
+                "{}\n{} {} ({:.0}%)",
+                out,
+                category_label(category, locale),
+                locale.money(category.sum),
+                category.sum as f64 * 100. / total as f64
+            );
+        }
+    }
+    out
+}
+
+/// "#food", untagged spending is "other" in the language of the reader
+pub fn category_label(category: &CategoryTotal, locale: &Locale) -> String {
+    match category.category.as_str() {
+        UNCATEGORISED => locale.text("stats.other"),
+        _ => category.label(),
+    }
+}
+
+/// One line per transaction, oldest first
+pub fn generate_history_response(
+    transactions: &[Transaction],
+    users: &[User],
+    tagged: &HashMap<String, Vec<Category>>,
+    expenses: &HashMap<String, SharedExpense>,
+    locale: &Locale
+) -> String {
+    if transactions.is_empty() {
+        return locale.text("history.empty");
+    }
+    let mut out = "".to_string();
+    for transaction in transactions.iter() {
+        let unknown = || locale.text("someone");
+        let key = match transaction.transaction_type {
+            TransactionType::Loan => "history.loaned",
+            TransactionType::Payment => "history.paid",
+        };
+        let expense = transaction.expense_id.as_ref().and_then(|id| expenses.get(id));
+        let mut line = match expense {
+            // one line for all the loans of a shared expense
+            Some(expense) => locale.format("history.shared", &[
+                ("date", &locale.date(&transaction.created)),
+                ("payers", &describe_payers(expense, users, locale)),
+                ("sum", &locale.money(expense.total())),
+                ("count", &expense.shares.len().to_string()),
+            ]),
+            None => locale.format(key, &[
+                ("date", &locale.date(&transaction.created)),
+                ("sender", &map_user_id_to_username(&transaction.initiator, users).unwrap_or_else(unknown)),
+                ("sum", &locale.money(transaction.sum as i64)),
+                ("receiver", &map_user_id_to_username(&transaction.reciever, users).unwrap_or_else(unknown)),
+            ]),
+        };
+        if !transaction.description.is_empty() {
+            line = format!("{}: {}", line, transaction.description);
+        }
+        // recorded on behalf of the payer
+        let payers: Vec<UserId> = match expense {
+            Some(expense) => expense.shares.iter().filter(|share| share.paid > 0).map(|share| share.user_id).collect(),
+            None => vec![transaction.initiator],
+        };
+        if let Some(recorder) = transaction.recorded_by.filter(|recorder| !payers.contains(recorder)) {
+            line = format!("{} ({})", line, locale.format("history.recorded_by", &[
+                ("name", &map_user_id_to_username(&recorder, users).unwrap_or_else(unknown)),
+            ]));
+        }
+        for category in tagged.get(&transaction.id).into_iter().flatten() {
+            line = format!("{} #{}", line, category.name);
+        }
+        out = format!("{}\n{}", out, line);
+    }
+    out
+}
+
+pub fn generate_category_response(update: &CategoryUpdate, locale: &Locale) -> String {
+    match update {
+        CategoryUpdate::Listed(categories, aliases) => {
+            if categories.is_empty() {
+                return locale.text("category.empty");
+            }
+            let mut out = locale.text("category.list");
+            for category in categories.iter() {
+                let also: Vec<String> = aliases.iter()
+                    .filter(|a| a.category_id == category.id)
+                    .map(|a| format!("#{}", a.alias))
+                    .collect();
+                out = match also.is_empty() {
+                    true => format!("{}\n#{}", out, category.name),
+                    false => format!(
+                        "{}\n{}",
+                        out,
+                        locale.format("category.also", &[("name", &category.name), ("aliases", &also.join(", "))])
+                    ),
+                };
+            }
+            out
+        },
+        CategoryUpdate::Added(category) => locale.format("category.added", &[("name", &category.name)]),
+        CategoryUpdate::AlreadyExists(name, category) => {
+            locale.format("category.taken", &[("name", name), ("category", &category.name)])
+        },
+        CategoryUpdate::Aliased(alias, category) => {
+            locale.format("category.aliased", &[("alias", &alias.alias), ("category", &category.name)])
+        },
+    }
+}
+
+pub fn generate_language_response(chosen: Option<Language>, current: Language, locale: &Locale) -> String {
+    let name = locale.text(&format!("language.{}", current.code()));
+    match chosen {
+        Some(_) => locale.format("language.chosen", &[("language", &name)]),
+        None => locale.text("language.automatic"),
+    }
+}
+
+pub fn generate_language_status_response(current: Language, locale: &Locale) -> String {
+    locale.format("language.current", &[("language", &locale.text(&format!("language.{}", current.code())))])
+}
+
+pub fn generate_settings_response(settings: &ChatSettings, locale: &Locale) -> String {
+    let switch = |on: bool| match on {
+        true => locale.text("settings.on"),
+        false => locale.text("settings.off"),
+    };
+    let language = match settings.language {
+        Some(language) => locale.text(&format!("language.{}", language.code())),
+        None => locale.text("settings.automatic"),
+    };
+    [
+        locale.text("settings.title"),
+        locale.format("settings.currency", &[("value", &settings.currency)]),
+        locale.format("settings.timezone", &[("value", settings.timezone.name())]),
+        locale.format("settings.language", &[("value", &language)]),
+        locale.format("settings.simplify", &[("value", &switch(settings.auto_simplify))]),
+        locale.format("settings.approval", &[("value", &switch(settings.approval_required))]),
+        locale.format("settings.reminders", &[("value", &describe_policy(&settings.reminders, locale))]),
+        locale.format("settings.admin_only", &[("value", &switch(settings.admin_only))]),
+        locale.format("settings.for_others", &[("value", &switch(settings.record_for_others))]),
+    ].join("\n")
+}
+
+fn describe_policy(policy: &ReminderPolicy, locale: &Locale) -> String {
+    locale.text(&format!("settings.policy.{}", policy))
+}
+
+/// One row of choices per option, the current choice is ticked. Every
+/// button sends the /settings command that picks its choice.
+pub fn generate_settings_keyboard(settings: &ChatSettings, locale: &Locale) -> Vec<Vec<Button>> {
+    let button = |label: String, command: &str, current: bool| Button {
+        label: match current {
+            true => format!("✓ {}", label),
+            false => label,
+        },
+        data: format!("/settings {}", command),
+    };
+    let currencies = ["EUR", "USD", "GBP", "CHF"].iter()
+        .map(|code| button(code.to_string(), &format!("currency {}", code), settings.currency == *code))
+        .collect();
+    let timezones = ["UTC", "Europe/Ljubljana", "Europe/London"].iter()
+        .map(|tz| button(tz.to_string(), &format!("timezone {}", tz), settings.timezone.name() == *tz))
+        .collect();
+    // every language is offered under its own name
+    let mut languages = vec![button(locale.text("settings.automatic"), "language auto", settings.language.is_none())];
+    for language in Language::ALL {
+        let own = Locale { language, ..locale.clone() };
+        let label = own.text(&format!("language.{}", language.code()));
+        languages.push(button(label, &format!("language {}", language.code()), settings.language == Some(language)));
+    }
+    let switches = |label: &str, option: &str, on: bool| vec![
+        button(format!("{} {}", locale.text(label), locale.text("settings.on")), &format!("{} on", option), on),
+        button(format!("{} {}", locale.text(label), locale.text("settings.off")), &format!("{} off", option), !on),
+    ];
+    let reminders = [ReminderPolicy::Auto, ReminderPolicy::Private, ReminderPolicy::Off].iter()
+        .map(|policy| button(
+            format!("{} {}", locale.text("settings.button.reminders"), describe_policy(policy, locale)),
+            &format!("reminders {}", policy),
+            settings.reminders == *policy
+        ))
+        .collect();
+    vec![
+        currencies,
+        timezones,
+        languages,
+        switches("settings.button.simplify", "simplify", settings.auto_simplify),
+        switches("settings.button.approval", "approval", settings.approval_required),
+        reminders,
+        switches("settings.button.admin_only", "adminonly", settings.admin_only),
+        switches("settings.button.for_others", "forothers", settings.record_for_others),
+    ]
+}
+
+pub fn generate_role_response(update: &RoleUpdate, locale: &Locale) -> String {
+    let role_name = |role: &Role| locale.text(&format!("role.{}", role));
+    match update {
+        RoleUpdate::Listed(roles) => {
+            let mut out = locale.text("role.title");
+            for (user, role) in roles.iter() {
+                out = format!("{}\n{}", out, locale.format("role.line", &[("name", &user.username), ("role", &role_name(role))]));
+            }
+            out
+        },
+        RoleUpdate::Changed(user, role) => {
+            locale.format("role.changed", &[("name", &user.username), ("role", &role_name(role))])
+        },
+    }
+}
+
+pub fn generate_approval_request_response(approval: &Approval, locale: &Locale) -> String {
+    let approvers: Vec<String> = extract_mentions(&approval.text).iter().map(|name| format!("@{}", name)).collect();
+    match approvers.is_empty() {
+        true => locale.format("approval.waiting_admin", &[("command", &approval.text)]),
+        false => locale.format("approval.waiting", &[("approvers", &approvers.join(", ")), ("command", &approval.text)]),
+    }
+}
+
+pub fn generate_approval_buttons(approval: &Approval, locale: &Locale) -> Vec<Vec<Button>> {
+    vec![vec![
+        Button { label: locale.text("approval.approve"), data: format!("approve {}", approval.id) },
+        Button { label: locale.text("approval.reject"), data: format!("reject {}", approval.id) },
+    ]]
+}
+
+/// Confirms or cancels forgetting the user, only they can tap them
+pub fn generate_forget_buttons(user_id: &UserId, locale: &Locale) -> Vec<Vec<Button>> {
+    vec![vec![
+        Button { label: locale.text("forget.button.confirm"), data: format!("forget {} yes", user_id) },
+        Button { label: locale.text("forget.button.cancel"), data: format!("forget {} no", user_id) },
+    ]]
+}
+
+/// Confirms or cancels a loan typed in inline mode
+pub fn generate_inline_buttons(draft_id: &str, locale: &Locale) -> Vec<Vec<Button>> {
+    vec![vec![
+        Button { label: locale.text("inline.confirm"), data: format!("inline {} yes", draft_id) },
+        Button { label: locale.text("inline.cancel"), data: format!("inline {} no", draft_id) },
+    ]]
+}
+
+/// One button per number that could be the amount, as it was written, and
+/// one to drop the message
+pub fn generate_amount_buttons(draft: &Draft, candidates: &[WrittenAmount], locale: &Locale) -> Vec<Vec<Button>> {
+    let words: Vec<&str> = draft.text.split_whitespace().collect();
+    let amounts = candidates.iter()
+        .flat_map(|candidate| match readings(&candidate.number).as_slice() {
+            // a number that reads two ways gets a button for each reading
+            [] => vec![Button {
+                label: words.get(candidate.words.clone()).unwrap_or_default().join(" "),
+                data: format!("amount {} {}", draft.id, candidate.word),
+            }],
+            readings => readings.iter().map(|cents| Button {
+                label: locale.money(*cents),
+                data: format!("amount {} {}:{}", draft.id, candidate.word, cents),
+            }).collect(),
+        })
+        .collect();
+    vec![amounts, vec![Button { label: locale.text("amount.cancel"), data: format!("amount {} cancel", draft.id) }]]
+}
+
+/// Graphviz version of the debt graph, recorded and simplified debt side by side
+pub fn generate_dot_graph(recorded: &Graph, simplified: &Graph, locale: &Locale) -> String {
+    let mut out = "digraph debt {\n    rankdir=LR;\n".to_string();
+    let clusters = [
+        ("r", locale.text("graph.recorded"), recorded),
+        ("s", locale.text("graph.simplified"), simplified),
+    ];
+    for (prefix, label, graph) in clusters {
+        out.push_str(&format!("    subgraph cluster_{} {{\n        label={:?};\n", prefix, label));
+        for user in graph.users.iter() {
+            out.push_str(&format!("        {}{} [label={:?}];\n", prefix, user.user_id, user.username));
+        }
+        for ledger in graph.ledgers.iter().filter(|l| l.sum > 0) {
+            out.push_str(&format!(
+                "        {}{} -> {}{} [label={:?}];\n",
+                prefix,
+                ledger.borrower,
+                prefix,
+                ledger.owes,
+                locale.money(ledger.sum as i64)
+            ));
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Name of the user, `None` for users who are not around or were forgotten
+fn map_user_id_to_username(uid: &UserId, users: &[User]) -> Option<String> {
+    for user in users.iter().filter(|user| !user.username.is_empty()) {
         if user.user_id == *uid {
             return Some(user.username.clone())
         }
     }
     None
-}
\ No newline at end of file
+}
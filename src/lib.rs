@@ -0,0 +1,8 @@
+pub mod helpers;
+pub mod types;
+pub mod tracker;
+pub mod server;
+
+extern crate strum;
+
+#[macro_use] extern crate diesel;
@@ -1,51 +1,170 @@
-use log::info;
-use teloxide::{prelude::*, utils::command::BotCommands};
+use log::{info, error};
+use teloxide::{prelude::*, types::{
+    ChosenInlineResult,
+    InlineKeyboardButton,
+    InlineKeyboardMarkup,
+    InlineQueryResult,
+    InlineQueryResultArticle,
+    InputFile,
+    InputMessageContent,
+    InputMessageContentText,
+    User
+}};
 use std::error::Error;
-use std::env;
+use std::path::Path;
+use std::time::Duration;
+use std::{env, process};
+use chrono::Utc;
 use dotenv::dotenv;
 
-mod helpers;
-mod types;
-use helpers::message_validator;
+use debt_tracket_telegram_bot_v2::helpers::data_handler::{
+    migration_operations::run_pending_migrations,
+    sqlite_operations::establish_connection
+};
+use debt_tracket_telegram_bot_v2::server::api::{self, ApiConfig};
+use debt_tracket_telegram_bot_v2::helpers::{i18n::Locale, text_helper::generate_error_response};
+use debt_tracket_telegram_bot_v2::server::webhook::{self, WebhookConfig};
+use debt_tracket_telegram_bot_v2::tracker::{
+    callback_chat,
+    inline_loan,
+    member_left,
+    pick_inline_loan,
+    register_member,
+    respond,
+    respond_callback,
+    run_scheduled_jobs,
+    saved_role,
+    simulation,
+    tracked_chats,
+    Button,
+    Command,
+    Reply
+};
+use debt_tracket_telegram_bot_v2::types::{command_input::CommandInput, role::Role};
 
-use crate::helpers::{data_handler::{user_operations::{insert_user, get_user_by_user_id, update_user}, chat_operations::{insert_user_into_room, is_user_in_chat, get_chat_users}, ledger_operations::get_group_ledgers}, text_helper::generate_balance_response, cycle_detector::detect_debt};
-use crate::helpers::transaction_handler::execute_transactions;
-
-extern crate strum;
 extern crate pretty_env_logger;
 
-#[macro_use] extern crate diesel;
-
 #[tokio::main]
 async fn main() {
     // setup env variables
     dotenv().ok();
-    env::set_var("TELOXIDE_TOKEN", env::var("TELOXIDE_TOKEN").expect("$TELOXIDE_TOKEN is not set"));
     // init stuff
     pretty_env_logger::init();
+    // replay a conversation against an in-memory database and exit
+    let args: Vec<String> = env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--simulate") {
+        let script = match args.get(i + 1) {
+            Some(script) => script,
+            None => {
+                error!("Usage: --simulate <conversation file>");
+                process::exit(2);
+            }
+        };
+        run_simulation(Path::new(script));
+    }
+    // bring the database schema up to date before anything touches it
+    let migrated = establish_connection().and_then(|conn| run_pending_migrations(&conn));
+    match migrated {
+        Ok(applied) => applied.iter().for_each(|name| info!("Applied migration {}", name)),
+        Err(e) => {
+            error!("Unable to migrate the database: {}", e);
+            process::exit(1);
+        }
+    }
+    if args.iter().any(|arg| arg == "--migrate-only") {
+        info!("Database is up to date, exiting (--migrate-only).");
+        return;
+    }
+    env::set_var("TELOXIDE_TOKEN", env::var("TELOXIDE_TOKEN").expect("$TELOXIDE_TOKEN is not set"));
+    let (webhook, api) = match (WebhookConfig::from_env(), ApiConfig::from_env()) {
+        (Ok(webhook), Ok(api)) => (webhook, api),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("{}", e);
+            process::exit(2);
+        }
+    };
+    // the HTTP API only reads, it runs next to the bot
+    if let Some(config) = api {
+        if let Err(e) = api::listen(&config, establish_connection).await {
+            error!("Unable to listen on {}: {}", config.address, e);
+            process::exit(1);
+        }
+    }
     // run bot
     let bot = Bot::from_env().auto_send();
+    tokio::spawn(run_scheduler(bot.clone()));
     info!("Running telegram bot!");
-    teloxide::commands_repl(bot, answer, Command::ty()).await;
+    let handler = dptree::entry()
+        .branch(Update::filter_message().filter_command::<Command>().endpoint(answer))
+        .branch(Update::filter_callback_query().endpoint(answer_callback))
+        .branch(Update::filter_inline_query().endpoint(answer_inline_query))
+        .branch(Update::filter_chosen_inline_result().endpoint(answer_chosen_inline_result))
+        .branch(Update::filter_message()
+            .chain(dptree::filter_map(|message: Message| message.left_chat_member().cloned()))
+            .endpoint(answer_member_left))
+        .branch(Update::filter_message()
+            .chain(dptree::filter_map(|message: Message| message.new_chat_members().map(|users| users.to_vec())))
+            .endpoint(answer_members_joined))
+        .branch(Update::filter_message().endpoint(answer_message));
+    let mut dispatcher = Dispatcher::builder(bot.clone(), handler)
+        .default_handler(|_| async {})
+        .build();
+    dispatcher.setup_ctrlc_handler();
+    // the same handler gets the updates Telegram posts to the webhook
+    match webhook {
+        Some(config) => {
+            if let Err(e) = webhook::register(bot.inner(), &config).await {
+                error!("Unable to register the webhook: {}", e);
+                process::exit(1);
+            }
+            let listener = match webhook::listen(&config).await {
+                Ok((_, listener)) => listener,
+                Err(e) => {
+                    error!("Unable to listen on {}: {}", config.address, e);
+                    process::exit(1);
+                }
+            };
+            dispatcher
+                .dispatch_with_listener(listener, LoggingErrorHandler::with_custom_text("Webhook failed"))
+                .await;
+        },
+        None => dispatcher.dispatch().await,
+    }
+}
+
+fn run_simulation(script: &Path) -> ! {
+    match simulation::run_file(script) {
+        Ok(()) => {
+            println!("{}: all expectations met", script.display());
+            process::exit(0);
+        }
+        Err(failures) => {
+            failures.iter().for_each(|failure| println!("{}: {}", script.display(), failure));
+            process::exit(1);
+        }
+    }
 }
 
-#[derive(BotCommands, Clone)]
-#[command(rename = "lowercase", description = "These commands are supported:")]
-enum Command {
-    #[command(description = "Display help text")]
-    Help,
-    #[command(description = "Loan money to (multiple) people")]
-    Loan,
-    #[command(description = "Pay money back to a person")]
-    Pay,
-    #[command(description = "Show ledger balance")]
-    Balance,
-    #[command(description = "Show past transactions")]
-    History,
-    #[command(description = "Show balance statistics")]
-    Stats,
-    #[command(description = "Register self to use the tracker")]
-    Register,
+/// Runs the scheduled jobs once a minute and posts their messages. Jobs that
+/// were missed while the bot was down run on the first tick.
+async fn run_scheduler(bot: AutoSend<Bot>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let posts = establish_connection().and_then(|conn| run_scheduled_jobs(&conn, Utc::now().naive_utc()));
+        let posts = match posts {
+            Ok(posts) => posts,
+            Err(e) => {
+                error!("Scheduler failed: {}", e);
+                continue;
+            }
+        };
+        for (chat_id, text) in posts.into_iter() {
+            if let Err(e) = bot.send_message(chat_id, text).await {
+                error!("Unable to post to chat {}: {}", chat_id, e);
+            }
+        }
+    }
 }
 
 async fn answer(
@@ -53,120 +172,232 @@ async fn answer(
     message: Message,
     command: Command,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    match command {
-        Command::Help => { bot.send_message(message.chat.id, Command::descriptions().to_string()).await? },
-        Command::Loan => { bot.send_message(message.chat.id, loan(&bot, message)).await? },
-        Command::Pay => { bot.send_message(message.chat.id, pay(&bot, message)).await? },
-        Command::Balance => { bot.send_message(message.chat.id, balance(&bot, message)).await? },
-        Command::History => { bot.send_message(message.chat.id, Command::descriptions().to_string()).await? },
-        Command::Stats => { bot.send_message(message.chat.id, Command::descriptions().to_string()).await? },
-        Command::Register => { bot.send_message(message.chat.id, register(&bot,message)).await? },
+    let prepared = CommandInput::try_from(&message)
+        .and_then(|input| establish_connection().map(|conn| (conn, input)));
+    let reply = match prepared {
+        Ok((conn, mut input)) => {
+            if input.role != Role::Owner {
+                // when Telegram can't tell, the saved role stands
+                input.role = match telegram_role(&bot, input.chat_id, input.sender).await {
+                    Some(role) => role,
+                    None => saved_role(&conn, &input.sender, &input.chat_id).unwrap_or_default(),
+                };
+            }
+            respond(&conn, &command, &input)
+        },
+        Err(e) => {
+            e.log();
+            let language_code = message.from().and_then(|user| user.language_code.as_deref());
+            Reply::Text(generate_error_response(&e, &Locale::fallback(language_code)))
+        }
     };
-    Ok(())
+    send_reply(&bot, message.chat.id, reply).await
 }
 
-fn register(
-    _: &AutoSend<Bot>,
-    message: Message,
-) -> String {
-    info!("User is signing up for the tracker!");
-    // check valid teloxide message
-    let user = match message.from() {
-        None => return "Oops something went wrong! Can't detect user.".to_string(),
-        Some(user) => user
+/// Sends the reply to the chat, or to the one it belongs to
+async fn send_reply(bot: &AutoSend<Bot>, chat_id: ChatId, reply: Reply) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (chat_id, reply) = match reply {
+        Reply::Elsewhere { chat_id, reply } => (chat_id, *reply),
+        reply => (chat_id, reply),
     };
-    // check if user has username setup
-    match user.username {
-        None => return "Please setup a telegram username (under settings -> edit profile) so I can identify you.".to_string(),
-        Some(_) =>  {}
-    }
-    // find all redistered users with same id (should be vec of 0 or 1 users)
-    let mut users = match get_user_by_user_id(&user.id) {
-        Err(e) => return e.to_string(),
-        Ok(users) => users
-    };
-    // user does not exist ->  register
-    if users.is_empty() {
-        let created_user = match insert_user(types::user::NewUser::from(user)) {
-            Err(e) => return e.to_string(),
-            Ok(created_user) => created_user
-        };
-        match insert_user_into_room(&created_user.user_id, &message.chat.id) {
-            Err(e) => e.to_string(),
-            Ok(_) => format!("Registered user as: {:?}", created_user.username)
-        }
-    // too many users exist -> notify invalid state
-    } else if users.len() > 1 {
-        "Invalid number of users with same id! Please contact the developer.".to_string()
-    // user already registered -> check for username change
-    // also check if registering from new chat
-    } else {
-        let mut resp = "User already registered".to_string();
-        // check if new chat should be inserted
-        match is_user_in_chat(user.id, message.chat.id) {
-            Err(e) => return e.to_string(),
-            Ok(b) => {
-                if !b {
-                    match insert_user_into_room(&user.id, &message.chat.id) {
-                        Err(e) => return e.to_string(),
-                        Ok(_) => resp = "You have been added to chat!".to_string()
-                    } 
+    match reply {
+        Reply::Text(text) => {
+            bot.send_message(chat_id, text).await?;
+        },
+        Reply::Photo { image, caption } => {
+            bot.send_photo(chat_id, InputFile::memory(image)).caption(caption).await?;
+        },
+        Reply::Document { file_name, content, caption } => {
+            bot.send_document(chat_id, InputFile::memory(content).file_name(file_name)).caption(caption).await?;
+        },
+        Reply::Menu { text, buttons } => {
+            bot.send_message(chat_id, text).reply_markup(keyboard(buttons)).await?;
+        },
+        Reply::Elsewhere { reply, .. } => {
+            bot.send_message(chat_id, reply.text()).await?;
+        },
+        Reply::Direct { user, text, notice, unreachable, start } => {
+            // Telegram refuses messages to people who never started a chat with the bot
+            let notice = match bot.send_message(ChatId(user.0 as i64), text).await {
+                Ok(_) => notice,
+                Err(e) => {
+                    info!("Unable to message user {} privately: {}", user, e);
+                    let me = bot.get_me().await?;
+                    format!("{}\nhttps://t.me/{}?start={}", unreachable, me.username(), start)
                 }
-            },
+            };
+            bot.send_message(chat_id, notice).await?;
+        },
+    }
+    Ok(())
+}
+
+/// A tapped button replaces the message it belongs to with the reply
+async fn answer_callback(
+    bot: AutoSend<Bot>,
+    query: CallbackQuery,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    bot.answer_callback_query(query.id.clone()).await?;
+    let prepared = CommandInput::try_from(&query)
+        .and_then(|input| establish_connection().map(|conn| (conn, input)));
+    let reply = match prepared {
+        Ok((conn, mut input)) => {
+            // buttons of loans typed in inline mode act in the chat of the loan
+            let chat_id = callback_chat(&conn, &input).unwrap_or(input.chat_id);
+            if input.role != Role::Owner || chat_id != input.chat_id {
+                // when Telegram can't tell, the saved role stands
+                input.role = match telegram_role(&bot, chat_id, input.sender).await {
+                    Some(role) => role,
+                    None => saved_role(&conn, &input.sender, &chat_id).unwrap_or_default(),
+                };
+            }
+            respond_callback(&conn, &input)
+        },
+        Err(e) => {
+            e.log();
+            Reply::Text(generate_error_response(&e, &Locale::fallback(query.from.language_code.as_deref())))
         }
-        // check for username change
-        let mut existinig_user = users.pop().unwrap();
-        if user.username.as_ref().unwrap().ne(&existinig_user.username) {
-            existinig_user.username = user.username.as_ref().unwrap().clone();
-            match update_user(existinig_user) {
-                Err(e) => e.to_string(),
-                Ok(updated_user) => format!("Updated user as: {:?}", updated_user.username)
+    };
+    // messages sent in inline mode belong to no chat, a reply for a chat
+    // goes there and the message gets its text
+    let message = match (query.message.as_ref(), query.inline_message_id.as_ref()) {
+        (Some(message), _) => message,
+        (None, Some(inline_message_id)) => {
+            bot.edit_message_text_inline(inline_message_id, reply.text()).await?;
+            if let Reply::Elsewhere { chat_id, reply } = reply {
+                send_reply(&bot, chat_id, *reply).await?;
             }
-        } else {
-            resp
+            return Ok(());
+        },
+        (None, None) => return Ok(()),
+    };
+    match reply {
+        Reply::Menu { text, buttons } => {
+            bot.edit_message_text(message.chat.id, message.id, text).reply_markup(keyboard(buttons)).await?;
+        },
+        reply => {
+            bot.edit_message_text(message.chat.id, message.id, reply.text()).await?;
+        },
+    }
+    Ok(())
+}
+
+/// "@DebtBot 12.50 @ana pizza" typed in any chat offers to record the loan
+/// in each group the user is tracked in
+async fn answer_inline_query(
+    bot: AutoSend<Bot>,
+    query: InlineQuery,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let input = CommandInput::from(&query);
+    let chat_ids = match establish_connection().and_then(|conn| tracked_chats(&conn, &input.sender)) {
+        Ok(chat_ids) => chat_ids,
+        Err(e) => {
+            e.log();
+            vec![]
         }
+    };
+    let mut chats = vec![];
+    for chat_id in chat_ids.into_iter() {
+        let chat = match bot.get_chat(chat_id).await {
+            Ok(chat) => chat,
+            Err(e) => {
+                error!("Unable to look up chat {}: {}", chat_id, e);
+                continue;
+            }
+        };
+        let role = match telegram_role(&bot, chat_id, input.sender).await {
+            Some(role) => role,
+            None => continue,
+        };
+        chats.push((chat_id, chat.title().unwrap_or_default().to_string(), role));
     }
+    let loans = match establish_connection().and_then(|conn| inline_loan(&conn, &input, &chats)) {
+        Ok(loans) => loans,
+        Err(e) => {
+            e.log();
+            vec![]
+        }
+    };
+    let results = loans.into_iter().map(|loan| {
+        let content = InputMessageContent::Text(InputMessageContentText::new(loan.text));
+        InlineQueryResult::Article(InlineQueryResultArticle::new(loan.id, loan.title, content)
+            .description(loan.description)
+            .reply_markup(keyboard(loan.buttons)))
+    });
+    // the results are loans of this user, nobody else gets them
+    bot.answer_inline_query(query.id, results).is_personal(true).cache_time(0).await?;
+    Ok(())
 }
 
-fn loan(
-    _: &AutoSend<Bot>,
-    message: Message,
-) -> String {
-    info!("User is executing a loan!");
-    match message_validator::validate_loan_message(message) {
-        Ok(transactions) => execute_transactions(transactions).join("\n"),
-        Err(e) => e.to_string()
+/// The user picked one of the results of `answer_inline_query` (Telegram
+/// only reports it with inline feedback on), its loan waits for the buttons
+async fn answer_chosen_inline_result(result: ChosenInlineResult) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let input = CommandInput::from(&result);
+    let picked = establish_connection().and_then(|conn| pick_inline_loan(&conn, &input, &result.result_id));
+    if let Err(e) = picked {
+        e.log();
     }
+    Ok(())
 }
 
-fn pay(
-    _: &AutoSend<Bot>,
+/// Somebody left the group or was removed from it
+async fn answer_member_left(
+    bot: AutoSend<Bot>,
     message: Message,
-) -> String {
-    info!("User is executing a payment!");
-    match message_validator::validate_pay_message(message) {
-        Ok(transactions) => execute_transactions(transactions).join("\n"),
-        Err(e) => e.to_string()
+    user: User,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let post = establish_connection().and_then(|conn| member_left(&conn, &message.chat.id, &user.id));
+    match post {
+        Ok(Some(text)) => {
+            bot.send_message(message.chat.id, text).await?;
+        },
+        Ok(None) => (),
+        Err(e) => e.log(),
     }
+    Ok(())
 }
 
+/// Somebody joined the group or was added to it
+async fn answer_members_joined(message: Message, users: Vec<User>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    users.iter().filter(|user| !user.is_bot).for_each(|user| register_user(&message, user, true));
+    Ok(())
+}
 
-fn balance(
-    _: &AutoSend<Bot>,
-    message: Message,
-) -> String {
-    info!("Some user is checking balance!");
-    let users = match get_chat_users(&message.chat.id) {
-        Ok(users) => users,
-        Err(e) => return e.to_string()
-    };
-    let ledgers = match get_group_ledgers(&users) {
-        Ok(ledgers) => ledgers,
-        Err(e) => return e.to_string()
-    };
-    match detect_debt(users, ledgers) {
-        Ok((leds, usrs)) => generate_balance_response(leds, usrs),
-        Err(e) => e.to_string(),
+/// Anything else somebody writes in the chat registers them too, unless
+/// they left it
+async fn answer_message(message: Message) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(user) = message.from().filter(|user| !user.is_bot) {
+        register_user(&message, user, false);
     }
+    Ok(())
 }
 
+fn register_user(message: &Message, user: &User, joined: bool) {
+    let registered = establish_connection()
+        .and_then(|conn| register_member(&conn, &message.chat.id, &user.id, user.username.as_deref(), joined));
+    if let Err(e) = registered {
+        e.log();
+    }
+}
+
+fn keyboard(buttons: Vec<Vec<Button>>) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(buttons.into_iter().map(|row| {
+        row.into_iter()
+            .map(|button| InlineKeyboardButton::callback(button.label, button.data))
+            .collect::<Vec<InlineKeyboardButton>>()
+    }))
+}
+
+/// Role the user has in the group according to Telegram (owner, admin or
+/// just a member)
+async fn telegram_role(bot: &AutoSend<Bot>, chat_id: ChatId, user_id: UserId) -> Option<Role> {
+    match bot.get_chat_member(chat_id, user_id).await {
+        Ok(member) if member.is_owner() => Some(Role::Owner),
+        Ok(member) if member.is_administrator() => Some(Role::Admin),
+        Ok(_) => Some(Role::Member),
+        Err(e) => {
+            error!("Unable to look up member {} of chat {}: {}", user_id, chat_id, e);
+            None
+        }
+    }
+}
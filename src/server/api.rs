@@ -0,0 +1,304 @@
+//! Read-only JSON API over the debt of a chat, for dashboards and the like.
+//! Every request carries the token an admin got with /apitoken in the
+//! header `Authorization: Bearer <token>`, and gets the data of that chat:
+//!
+//! ```text
+//! GET /api/chat                     the chat, its currency and active event
+//! GET /api/users                    members and their roles
+//! GET /api/balances                 recorded debt and net balances
+//! GET /api/balances/simplified      the same after simplification
+//! GET /api/transactions?offset=0&limit=20
+//!                                   history, newest first
+//! GET /api/settlement               who pays whom to settle up
+//! ```
+//!
+//! Users are referred to by their Telegram id and amounts are in cents.
+//! Balances and the settlement are of the active event, as in the chat.
+
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use chrono::NaiveDateTime;
+use diesel::SqliteConnection;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use log::{error, info};
+use serde::Serialize;
+use serde_json::{json, Value};
+use teloxide::types::{ChatId, UserId};
+
+use crate::helpers::data_handler::{
+    api_token_operations::find_api_token,
+    category_operations::get_transaction_categories,
+    chat_operations::{get_chat_members, get_chat_users},
+    event_operations::get_active_event,
+    settings_operations::get_chat_settings,
+    transaction_operations::get_group_transactions
+};
+use crate::tracker::debt_graphs;
+use crate::types::{error::TrackerError, graph::Graph, transaction_type::TransactionType};
+
+/// Transactions a page has unless the request asks for another number
+const PAGE_SIZE: usize = 20;
+
+/// Most transactions a page can have
+const MAX_PAGE_SIZE: usize = 100;
+
+/// The API runs when `API_LISTEN` holds the address it listens on
+/// (`0.0.0.0:8080`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiConfig {
+    pub address: SocketAddr,
+}
+
+impl ApiConfig {
+    /// None when the API is off
+    pub fn from_env() -> Result<Option<ApiConfig>, String> {
+        let address = match env::var("API_LISTEN") {
+            Ok(address) => address,
+            Err(_) => return Ok(None),
+        };
+        let address = address.parse()
+            .map_err(|e| format!("$API_LISTEN is not an address like 0.0.0.0:8080: {}", e))?;
+        Ok(Some(ApiConfig { address }))
+    }
+}
+
+#[derive(Serialize)]
+struct ChatView {
+    id: i64,
+    currency: String,
+    timezone: String,
+    /// Name of the active event, `None` for the everyday debt
+    event: Option<String>,
+}
+
+#[derive(Serialize)]
+struct UserView {
+    id: u64,
+    username: String,
+    role: String,
+}
+
+#[derive(Serialize)]
+struct DebtView {
+    borrower: u64,
+    owes: u64,
+    cents: i64,
+}
+
+#[derive(Serialize)]
+struct NetView {
+    user: u64,
+    /// What the user is owed minus what they owe
+    cents: i64,
+}
+
+#[derive(Serialize)]
+struct BalancesView {
+    event: Option<String>,
+    debts: Vec<DebtView>,
+    net: Vec<NetView>,
+}
+
+#[derive(Serialize)]
+struct TransactionView {
+    id: String,
+    #[serde(rename = "type")]
+    transaction_type: TransactionType,
+    from: u64,
+    to: u64,
+    cents: i32,
+    description: String,
+    created: NaiveDateTime,
+    event: Option<String>,
+    /// Shared expense the loan is a part of
+    expense: Option<String>,
+    recorded_by: Option<u64>,
+    categories: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct TransactionPage {
+    total: usize,
+    offset: usize,
+    limit: usize,
+    transactions: Vec<TransactionView>,
+}
+
+#[derive(Serialize)]
+struct TransferView {
+    from: u64,
+    to: u64,
+    cents: i64,
+}
+
+/// Starts the server and returns the address it listens on. Every request
+/// gets a connection of its own from `connect`.
+pub async fn listen(
+    config: &ApiConfig,
+    connect: fn() -> Result<SqliteConnection, TrackerError>,
+) -> Result<SocketAddr, hyper::Error> {
+    let make_service = make_service_fn(move |_| async move {
+        Ok::<_, Infallible>(service_fn(move |request| receive(request, connect)))
+    });
+    let server = Server::try_bind(&config.address)?.serve(make_service);
+    let address = server.local_addr();
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            error!("API server failed: {}", e);
+        }
+    });
+    info!("API listening on {}", address);
+    Ok(address)
+}
+
+async fn receive(
+    request: Request<Body>,
+    connect: fn() -> Result<SqliteConnection, TrackerError>,
+) -> Result<Response<Body>, Infallible> {
+    let (status, body) = match request.method() {
+        &Method::GET => {
+            let token = request.headers().get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            match connect() {
+                Ok(conn) => answer(&conn, token, request.uri().path(), request.uri().query()),
+                Err(e) => failed(e),
+            }
+        },
+        _ => (StatusCode::METHOD_NOT_ALLOWED, error("only GET requests are supported")),
+    };
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().expect("valid header"));
+    Ok(response)
+}
+
+/// Answers a request for `path` made with `token`, independent of HTTP so
+/// simulations can ask too
+pub fn answer(conn: &SqliteConnection, token: Option<&str>, path: &str, query: Option<&str>) -> (StatusCode, Value) {
+    let chat_id = match token.map(|token| find_api_token(conn, token)).transpose() {
+        Ok(Some(Some(token))) => token.chat_id,
+        Ok(_) => return (StatusCode::UNAUTHORIZED, error("missing or unknown API token, get one with /apitoken")),
+        Err(e) => return failed(e),
+    };
+    let answered = match path.trim_end_matches('/') {
+        "/api/chat" => chat(conn, &chat_id),
+        "/api/users" => users(conn, &chat_id),
+        "/api/balances" => balances(conn, &chat_id, false),
+        "/api/balances/simplified" => balances(conn, &chat_id, true),
+        "/api/transactions" => match page(query) {
+            Some((offset, limit)) => transactions(conn, &chat_id, offset, limit),
+            None => return (StatusCode::BAD_REQUEST, error("offset and limit have to be numbers, limit at most 100")),
+        },
+        "/api/settlement" => settlement(conn, &chat_id),
+        _ => return (StatusCode::NOT_FOUND, error("no such endpoint")),
+    };
+    match answered {
+        Ok(body) => (StatusCode::OK, body),
+        Err(e) => failed(e),
+    }
+}
+
+fn failed(e: TrackerError) -> (StatusCode, Value) {
+    e.log();
+    (StatusCode::INTERNAL_SERVER_ERROR, error("the records could not be read"))
+}
+
+fn error(message: &str) -> Value {
+    json!({ "error": message })
+}
+
+/// "offset=40&limit=20", both optional
+fn page(query: Option<&str>) -> Option<(usize, usize)> {
+    let (mut offset, mut limit) = (0, PAGE_SIZE);
+    for pair in query.unwrap_or_default().split('&').filter(|pair| !pair.is_empty()) {
+        match pair.split_once('=') {
+            Some(("offset", value)) => offset = value.parse().ok()?,
+            Some(("limit", value)) => limit = value.parse().ok()?,
+            _ => (),
+        }
+    }
+    (limit <= MAX_PAGE_SIZE).then_some((offset, limit))
+}
+
+fn to_json<T: Serialize>(view: T) -> Result<Value, TrackerError> {
+    serde_json::to_value(view).map_err(|e| TrackerError::Consistency(e.to_string()))
+}
+
+fn chat(conn: &SqliteConnection, chat_id: &ChatId) -> Result<Value, TrackerError> {
+    let settings = get_chat_settings(conn, chat_id)?;
+    let event = get_active_event(conn, chat_id)?;
+    to_json(ChatView {
+        id: chat_id.0,
+        currency: settings.currency,
+        timezone: settings.timezone.to_string(),
+        event: event.map(|e| e.name),
+    })
+}
+
+fn users(conn: &SqliteConnection, chat_id: &ChatId) -> Result<Value, TrackerError> {
+    let members = get_chat_members(conn, chat_id)?;
+    let users: Vec<UserView> = get_chat_users(conn, chat_id)?.into_iter()
+        .filter_map(|user| {
+            let member = members.iter().find(|m| m.user_id == user.user_id)?;
+            Some(UserView { id: user.user_id.0, username: user.username, role: member.role.to_string() })
+        })
+        .collect();
+    to_json(users)
+}
+
+fn balances(conn: &SqliteConnection, chat_id: &ChatId, simplified: bool) -> Result<Value, TrackerError> {
+    let event = get_active_event(conn, chat_id)?;
+    let (recorded, simple) = debt_graphs(conn, chat_id, event.as_ref().map(|e| e.id.as_str()))?;
+    let graph = if simplified { simple } else { recorded };
+    let mut debts: Vec<DebtView> = graph.ledgers.iter()
+        .filter(|ledger| ledger.sum > 0)
+        .map(|ledger| DebtView { borrower: ledger.borrower.0, owes: ledger.owes.0, cents: ledger.sum as i64 })
+        .collect();
+    debts.sort_by_key(|debt| (debt.borrower, debt.owes));
+    to_json(BalancesView { event: event.map(|e| e.name), debts, net: net_balances(&graph) })
+}
+
+/// Net balance of every member of the graph, square ones included
+fn net_balances(graph: &Graph) -> Vec<NetView> {
+    let balances = graph.net_balances();
+    let mut net: Vec<NetView> = graph.users.iter()
+        .map(|user| NetView { user: user.user_id.0, cents: balances.get(&user.user_id).copied().unwrap_or(0) })
+        .collect();
+    net.sort_by_key(|n| n.user);
+    net
+}
+
+fn transactions(conn: &SqliteConnection, chat_id: &ChatId, offset: usize, limit: usize) -> Result<Value, TrackerError> {
+    let users = get_chat_users(conn, chat_id)?;
+    let mut transactions = get_group_transactions(conn, &users)?;
+    transactions.reverse();
+    let tagged = get_transaction_categories(conn, chat_id)?;
+    let total = transactions.len();
+    let transactions = transactions.into_iter().skip(offset).take(limit).map(|t| TransactionView {
+        categories: tagged.get(&t.id).map(|c| c.iter().map(|c| c.name.clone()).collect()).unwrap_or_default(),
+        id: t.id,
+        transaction_type: t.transaction_type,
+        from: t.initiator.0,
+        to: t.reciever.0,
+        cents: t.sum,
+        description: t.description,
+        created: t.created,
+        event: t.event_id,
+        expense: t.expense_id,
+        recorded_by: t.recorded_by.map(|UserId(id)| id),
+    }).collect();
+    to_json(TransactionPage { total, offset, limit, transactions })
+}
+
+fn settlement(conn: &SqliteConnection, chat_id: &ChatId) -> Result<Value, TrackerError> {
+    let event = get_active_event(conn, chat_id)?;
+    let (recorded, _) = debt_graphs(conn, chat_id, event.as_ref().map(|e| e.id.as_str()))?;
+    let transfers: Vec<TransferView> = recorded.settlement_transfers().into_iter()
+        .map(|(from, to, cents)| TransferView { from: from.0, to: to.0, cents })
+        .collect();
+    to_json(transfers)
+}
@@ -0,0 +1,160 @@
+use std::convert::Infallible;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use log::{error, info, warn};
+use serde::Serialize;
+use teloxide::dispatching::{stop_token::AsyncStopToken, update_listeners::{StatefulListener, UpdateListener}};
+use teloxide::prelude::*;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+/// Header Telegram puts the secret token given to setWebhook in
+pub const SECRET_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+/// Webhook mode is configured from the environment:
+/// - `WEBHOOK_LISTEN`: address the server listens on (`0.0.0.0:8443`), webhook
+///   mode is on when it is set
+/// - `WEBHOOK_URL`: public URL Telegram posts the updates to, the webhook is
+///   only registered with Telegram when it is set
+/// - `WEBHOOK_SECRET`: token Telegram sends with every update, requests
+///   without it are refused. Webhook mode doesn't start without one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    pub address: SocketAddr,
+    pub url: Option<String>,
+    pub secret: String,
+}
+
+type Updates = UnboundedReceiverStream<Result<Update, Infallible>>;
+
+impl WebhookConfig {
+    /// None when the bot should poll for updates
+    pub fn from_env() -> Result<Option<WebhookConfig>, String> {
+        let address = match env::var("WEBHOOK_LISTEN") {
+            Ok(address) => address,
+            Err(_) => return Ok(None),
+        };
+        let address = address.parse()
+            .map_err(|e| format!("$WEBHOOK_LISTEN is not an address like 0.0.0.0:8443: {}", e))?;
+        let url = env::var("WEBHOOK_URL").ok().filter(|url| !url.is_empty());
+        // anybody who finds the webhook could post updates without it
+        let secret = match env::var("WEBHOOK_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret,
+            _ => return Err("$WEBHOOK_SECRET has to be set when $WEBHOOK_LISTEN is".to_string()),
+        };
+        // what Telegram accepts as a secret token
+        let valid = secret.len() <= 256
+            && secret.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err("$WEBHOOK_SECRET may only have up to 256 letters, digits, _ and -".to_string());
+        }
+        Ok(Some(WebhookConfig { address, url, secret }))
+    }
+}
+
+#[derive(Serialize)]
+struct SetWebhook<'a> {
+    url: &'a str,
+    secret_token: &'a str,
+}
+
+/// Tells Telegram to post the updates to the URL of the config. The request
+/// is made by hand since teloxide does not know about secret tokens yet.
+pub async fn register(bot: &Bot, config: &WebhookConfig) -> Result<(), reqwest::Error> {
+    let url = match &config.url {
+        Some(url) => url,
+        None => {
+            info!("$WEBHOOK_URL is not set, the webhook is not registered with Telegram");
+            return Ok(());
+        }
+    };
+    let endpoint = format!("{}/bot{}/setWebhook", bot.api_url().as_str().trim_end_matches('/'), bot.token());
+    let payload = SetWebhook { url, secret_token: &config.secret };
+    bot.client().post(endpoint).json(&payload).send().await?.error_for_status()?;
+    info!("Registered the webhook {}", url);
+    Ok(())
+}
+
+/// Starts the server and returns the address it listens on with the updates
+/// it receives, ready for `Dispatcher::dispatch_with_listener`. Stopping the
+/// listener shuts the server down.
+pub async fn listen(config: &WebhookConfig) -> Result<(SocketAddr, impl UpdateListener<Infallible>), hyper::Error> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let (stop_token, stop_flag) = AsyncStopToken::new_pair();
+    let secret = Arc::new(config.secret.clone());
+    let make_service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        let secret = secret.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| receive(request, tx.clone(), secret.clone())))
+        }
+    });
+    let server = Server::try_bind(&config.address)?.serve(make_service);
+    let address = server.local_addr();
+    tokio::spawn(async move {
+        if let Err(e) = server.with_graceful_shutdown(stop_flag).await {
+            error!("Webhook server failed: {}", e);
+        }
+    });
+    info!("Listening for updates on {}", address);
+    let listener = StatefulListener::new(
+        (UnboundedReceiverStream::new(rx), stop_token),
+        updates,
+        |state: &mut (Updates, AsyncStopToken)| state.1.clone(),
+    );
+    Ok((address, listener))
+}
+
+fn updates(state: &mut (Updates, AsyncStopToken)) -> &mut Updates {
+    &mut state.0
+}
+
+/// Hands one update posted by Telegram over to the dispatcher
+async fn receive(
+    request: Request<Body>,
+    tx: UnboundedSender<Result<Update, Infallible>>,
+    secret: Arc<String>,
+) -> Result<Response<Body>, Infallible> {
+    if request.method() != Method::POST {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let token = request.headers().get(SECRET_HEADER).map(|token| token.as_bytes());
+    if !token.is_some_and(|token| same_secret(token, secret.as_bytes())) {
+        warn!("Refused an update without the secret token");
+        return Ok(status(StatusCode::UNAUTHORIZED));
+    }
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Unable to read an update: {}", e);
+            return Ok(status(StatusCode::BAD_REQUEST));
+        }
+    };
+    let update = match serde_json::from_slice::<Update>(&body) {
+        Ok(update) => update,
+        Err(e) => {
+            error!("Unable to parse an update: {}", e);
+            return Ok(status(StatusCode::BAD_REQUEST));
+        }
+    };
+    // Telegram posts the update again later when it isn't taken
+    if tx.send(Ok(update)).is_err() {
+        return Ok(status(StatusCode::SERVICE_UNAVAILABLE));
+    }
+    Ok(status(StatusCode::OK))
+}
+
+/// Compares every byte whatever the ones before, so the time it takes
+/// doesn't tell how much of the secret a guess got right
+fn same_secret(token: &[u8], secret: &[u8]) -> bool {
+    token.len() == secret.len() && token.iter().zip(secret).fold(0, |differ, (a, b)| differ | (a ^ b)) == 0
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
@@ -0,0 +1,1879 @@
+pub mod simulation;
+
+use std::collections::{HashMap, HashSet};
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use diesel::{Connection, SqliteConnection};
+use log::{error, info};
+use teloxide::{types::{ChatId, UserId}, utils::command::BotCommands};
+use uuid::Uuid;
+
+use crate::helpers::{
+    data_handler::{
+        user_operations::{forget_user, insert_user, get_user_by_user_id, get_user_by_username, is_forgotten, update_user},
+        chat_operations::{
+            get_chat_member,
+            get_chat_members,
+            get_chat_users,
+            get_left_chats,
+            get_user_chats,
+            insert_user_into_room,
+            is_user_in_chat,
+            remove_user_from_room,
+            update_chat_member
+        },
+        user_operations::get_registered_user,
+        ledger_operations::{get_group_ledgers, get_ledger, get_user_ledgers, update_ledger},
+        transaction_operations::{get_group_transactions, get_user_transactions},
+        category_operations::{
+            find_category,
+            get_chat_aliases,
+            get_chat_categories,
+            get_transaction_categories,
+            insert_category,
+            insert_category_alias
+        },
+        reminder_operations::{
+            delete_reminders,
+            get_overdue_reminders,
+            get_reminder,
+            get_reminder_preference,
+            get_reminder_threshold,
+            get_reminder_thresholds,
+            insert_reminder,
+            save_reminder_preference,
+            set_reminder_threshold,
+            update_reminder
+        },
+        digest_operations::{delete_digest, get_digest, get_due_digests, save_digest},
+        settings_operations::{get_chat_settings, get_user_language, save_chat_settings, set_user_language},
+        approval_operations::{delete_approval, get_approval, insert_approval},
+        api_token_operations::{delete_api_token, replace_api_token},
+        draft_operations::{delete_draft, delete_drafts_before, get_draft, insert_draft},
+        expense_operations::{get_chat_shared_expenses, insert_shared_expense},
+        event_operations::{activate_event, get_active_event, get_chat_events, insert_event, update_event},
+        recurring_operations::{
+            delete_recurring,
+            get_chat_recurring,
+            get_due_recurring,
+            insert_recurring,
+            update_recurring
+        }
+    },
+    text_helper::{
+        generate_amount_buttons,
+        generate_approval_buttons,
+        generate_category_response,
+        generate_digest_response,
+        generate_digest_update_response,
+        generate_dot_graph,
+        generate_error_response,
+        generate_event_balance_response,
+        generate_forget_buttons,
+        generate_event_response,
+        generate_help_response,
+        generate_history_response,
+        generate_inline_buttons,
+        generate_language_response,
+        generate_language_status_response,
+        generate_loan_response,
+        generate_reminder_response,
+        generate_reminder_update_response,
+        generate_recurring_response,
+        generate_recurring_run_response,
+        generate_approval_request_response,
+        generate_registration_response,
+        generate_role_response,
+        generate_settlement_response,
+        generate_shared_expense_response,
+        generate_settings_keyboard,
+        generate_settings_response,
+        generate_statement_response,
+        generate_stats_response,
+        generate_transactions_response
+    },
+    i18n::Locale,
+    cycle_detector::{detect_debt, detect_mutual_debt},
+    image_helper::{render_debt_graphs, render_stats_charts},
+    message_validator,
+    expense_parser::{parse_expense, put_amount_first, readings, Expense},
+    transaction_handler::execute_transactions,
+};
+use crate::types::{
+    api_token::NewApiToken,
+    approval::{Approval, NewApproval},
+    category::{Category, CategoryAlias, NewCategory},
+    command_input::{extract_mentions, CommandInput},
+    digest::Digest,
+    draft::{Draft, NewDraft},
+    event::{Event, NewEvent},
+    expense::SharedExpense,
+    error::{TrackerError, ValidationError},
+    graph::Graph,
+    ledger::Ledger,
+    recurring::{Frequency, NewRecurring, Recurring},
+    reminder::{NewReminder, Reminder, ReminderPreference},
+    role::Role,
+    settings::{ChatSettings, Language, ReminderPolicy},
+    stats::Stats,
+    transaction::{NewTransaction, Transaction, TransactionReceipt},
+    transaction_type::TransactionType,
+    user::{NewUser, User}
+};
+
+/// Outcome of every transaction of a command, one by one
+pub type Receipts = Vec<Result<TransactionReceipt, TrackerError>>;
+
+/// The same debt is reminded about at most this often
+const REMIND_EVERY_DAYS: i64 = 7;
+
+/// Transactions a statement lists
+const STATEMENT_TRANSACTIONS: usize = 10;
+
+/// Unanswered amount questions and inline loans are dropped after this long
+const DRAFT_DAYS: i64 = 1;
+
+/// Parameter of the link that opens a private chat and asks for the
+/// statement ("https://t.me/<bot>?start=statement")
+pub const STATEMENT_START: &str = "statement";
+
+/// Start of the parameter of the link that opens a private chat and asks
+/// for the API token of a group, its id follows ("apitoken-100123")
+pub const API_TOKEN_START: &str = "apitoken";
+
+#[derive(BotCommands, Clone, Debug)]
+#[command(rename = "lowercase", description = "These commands are supported:")]
+pub enum Command {
+    #[command(description = "Display help text")]
+    Help,
+    #[command(description = "Loan money to (multiple) people")]
+    Loan,
+    #[command(description = "Pay money back to a person")]
+    Pay,
+    #[command(description = "Share a bill several people paid (\"/expense @ana=60 @luka=40 @maja dinner\")")]
+    Expense,
+    #[command(description = "Show ledger balance")]
+    Balance,
+    #[command(description = "Show past transactions (\"/history 20 #food\" for the last 20 food expenses)")]
+    History,
+    #[command(description = "Show spending statistics (\"/stats chart 6m\" for charts of the last 6 months)")]
+    Stats,
+    #[command(description = "Register self to use the tracker (writing in the chat does it too)")]
+    Register,
+    #[command(description = "Draw the debt graph (\"/graph dot\" for a Graphviz file)")]
+    Graph,
+    #[command(description = "List, add and alias spending categories")]
+    Category,
+    #[command(description = "Repeat a loan on a schedule (\"/recurring add monthly 1st 45 @ana #internet\")")]
+    Recurring,
+    #[command(description = "Reminders about your debt (on, off, snooze 7d, threshold 50)")]
+    Remind,
+    #[command(description = "Periodic balance summary (\"/digest weekly mon 09:00 Europe/Ljubljana\")")]
+    Digest,
+    #[command(description = "Show and change the options of this chat")]
+    Settings,
+    #[command(description = "Choose the language I talk to you in (\"/language sl\")")]
+    Language,
+    #[command(description = "Keep the debt of a trip or party apart (\"/event start Ski2026\")")]
+    Event,
+    #[command(description = "Show who pays whom to settle up")]
+    Settle,
+    #[command(description = "Show or change what members may do (\"/role @luka readonly\")")]
+    Role,
+    #[command(description = "Your own balance with everybody, sent to you privately")]
+    Me,
+    #[command(description = "Leave the tracker of this chat, once your debt here is settled")]
+    Leave,
+    #[command(rename = "forget_me", description = "Remove your name from the tracker, once all your debt is settled")]
+    ForgetMe,
+    #[command(description = "Get a token for the HTTP API of this chat (\"/apitoken revoke\" stops it)")]
+    ApiToken,
+    #[command(description = "off")]
+    Statement,
+    #[command(description = "off")]
+    Start,
+}
+
+/// What the bot sends back to the chat
+#[derive(Debug)]
+pub enum Reply {
+    Text(String),
+    Photo { image: Vec<u8>, caption: String },
+    Document { file_name: String, content: Vec<u8>, caption: String },
+    /// Text with buttons below it, tapping one sends its data back
+    Menu { text: String, buttons: Vec<Vec<Button>> },
+    /// Text for the eyes of `user` only, sent to their private chat. The
+    /// chat gets the notice, or `unreachable` and a link to start a private
+    /// chat if the user never did. The link starts it with `start`, so the
+    /// user gets the text there.
+    Direct { user: UserId, text: String, notice: String, unreachable: String, start: String },
+    /// Reply that belongs to another chat than the one it was asked from (a
+    /// loan confirmed in inline mode goes to the chat it is recorded in).
+    /// Where it was asked from gets its text.
+    Elsewhere { chat_id: ChatId, reply: Box<Reply> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Button {
+    pub label: String,
+    pub data: String,
+}
+
+impl Reply {
+    /// The text part of the reply (message text or caption)
+    pub fn text(&self) -> &str {
+        match self {
+            Reply::Text(text) | Reply::Menu { text, .. } => text,
+            Reply::Photo { caption, .. } | Reply::Document { caption, .. } => caption,
+            Reply::Direct { notice, .. } => notice,
+            Reply::Elsewhere { reply, .. } => reply.text(),
+        }
+    }
+}
+
+/// Loan typed in inline mode, one result per chat it can be recorded in
+#[derive(Debug)]
+pub struct InlineLoan {
+    /// Id of the result, the chat and the id of the draft the loan becomes
+    /// when picked ("-100123 <draft id>")
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    /// Message sent to the chat the user is typing in, with the buttons
+    pub text: String,
+    pub buttons: Vec<Vec<Button>>,
+}
+
+#[derive(Debug)]
+pub enum Registration {
+    Registered(User),
+    AddedToChat(User),
+    UsernameUpdated(User),
+    AlreadyRegistered(User),
+}
+
+#[derive(Debug)]
+pub enum CategoryUpdate {
+    Listed(Vec<Category>, Vec<CategoryAlias>),
+    Added(Category),
+    /// The name is already used by a category (directly or as an alias)
+    AlreadyExists(String, Category),
+    Aliased(CategoryAlias, Category),
+}
+
+#[derive(Debug)]
+pub enum RecurringUpdate {
+    Listed(Vec<Recurring>),
+    Added(Recurring),
+    Paused(Recurring),
+    Resumed(Recurring),
+    Deleted(Recurring),
+}
+
+#[derive(Debug)]
+pub enum EventUpdate {
+    /// Events of the chat, oldest first
+    Listed(Vec<Event>),
+    Started(Event),
+    /// `None` is back to the everyday debt
+    Switched(Option<Event>),
+    /// The archived event, the transfers that settle it and the users they
+    /// are between. The transfers were moved to the everyday debt.
+    Closed(Event, Vec<(UserId, UserId, i64)>, Vec<User>),
+}
+
+#[derive(Debug)]
+pub enum RoleUpdate {
+    /// Members of the chat with their roles, the owner first
+    Listed(Vec<(User, Role)>),
+    Changed(User, Role),
+}
+
+#[derive(Debug)]
+pub enum ReminderUpdate {
+    /// Preference of the sender and threshold of the chat (in cents)
+    Status(ReminderPreference, Option<i32>),
+    Preference(ReminderPreference),
+    Threshold(Option<i32>),
+}
+
+#[derive(Debug)]
+pub enum DigestUpdate {
+    Status(Option<Digest>),
+    Scheduled(Digest),
+    Stopped,
+}
+
+/// What happened in a chat since its last digest
+#[derive(Debug)]
+pub struct DigestReport {
+    pub digest: Digest,
+    pub since: NaiveDateTime,
+    /// Simplified debt
+    pub balance: Graph,
+    pub transactions: Vec<Transaction>,
+    pub tagged: HashMap<String, Vec<Category>>,
+    pub expenses: HashMap<String, SharedExpense>,
+    /// Who pays how much (in cents) to whom to settle all debt
+    pub transfers: Vec<(UserId, UserId, i64)>,
+}
+
+#[derive(Debug)]
+pub enum ReminderReason {
+    Overdue(NaiveDateTime),
+    /// Debt is over the chat's threshold (in cents)
+    OverThreshold(i32),
+}
+
+/// Reminder that is about to be sent to the borrower
+#[derive(Debug)]
+pub struct ReminderNotice {
+    pub borrower: User,
+    pub owes: User,
+    /// In cents
+    pub debt: i64,
+    pub reason: ReminderReason,
+    /// Sent by DM instead of to the chat
+    pub private: bool,
+}
+
+/// Debt of one user with everybody, over all chats and events
+#[derive(Debug)]
+pub struct Statement {
+    pub user: User,
+    /// What each counterparty owes the user, negative if the user owes
+    /// them (in cents), the largest first
+    pub balances: Vec<(UserId, i64)>,
+    /// Latest transactions of the user, oldest first
+    pub transactions: Vec<Transaction>,
+    /// The user and everybody the statement mentions
+    pub users: Vec<User>,
+}
+
+/// Debt state of a chat, after mutual and cyclic debt has been resolved
+#[derive(Debug)]
+pub struct Balance {
+    pub ledgers: Vec<Ledger>,
+    pub users: Vec<User>,
+    /// Event the debt is of, `None` for the everyday debt
+    pub event: Option<Event>,
+}
+
+/// Executes the command and renders the reply in the language of the
+/// sender. Errors are logged in detail, the chat only gets a friendly version.
+pub fn respond(conn: &SqliteConnection, command: &Command, input: &CommandInput) -> Reply {
+    let mut locale = Locale::fallback(input.language_code.as_deref());
+    let response = get_chat_settings(conn, &input.chat_id).and_then(|settings| {
+        locale = reader_locale(conn, input, &settings)?;
+        execute(conn, command, input, &settings, &locale)
+    });
+    match response {
+        Ok(reply) => reply,
+        Err(e) => {
+            e.log();
+            Reply::Text(generate_error_response(&e, &locale))
+        }
+    }
+}
+
+/// Replies to a user are in the language they picked, the one of the chat,
+/// the one of their Telegram app or English, whichever comes first
+pub fn reader_locale(conn: &SqliteConnection, input: &CommandInput, settings: &ChatSettings) -> Result<Locale, TrackerError> {
+    let language = get_user_language(conn, &input.sender)?
+        .or(settings.language)
+        .or_else(|| input.language_code.as_deref().and_then(Language::parse))
+        .unwrap_or_default();
+    Ok(Locale::new(settings, language))
+}
+
+/// Posts nobody in particular asked for are in the language of the chat
+fn chat_locale(settings: &ChatSettings) -> Locale {
+    Locale::new(settings, settings.language.unwrap_or_default())
+}
+
+fn execute(
+    conn: &SqliteConnection,
+    command: &Command,
+    input: &CommandInput,
+    settings: &ChatSettings,
+    locale: &Locale
+) -> Result<Reply, TrackerError> {
+    // using the bot registers, unless that is what the command is about
+    if !matches!(command, Command::Register | Command::Leave | Command::ForgetMe) {
+        register_sender(conn, input)?;
+    }
+    // listing is fine for everybody, changing the records is not
+    let changes_records = match command {
+        Command::Loan | Command::Pay | Command::Expense => true,
+        Command::Category | Command::Recurring | Command::Event => {
+            input.text.split_whitespace().nth(1).is_some_and(|action| !action.eq_ignore_ascii_case("list"))
+        },
+        _ => false,
+    };
+    let role = member_role(conn, input)?;
+    if changes_records && !role.records() {
+        return Err(TrackerError::ReadOnly);
+    }
+    if changes_records && settings.admin_only && !role.administers() {
+        return Err(TrackerError::Forbidden);
+    }
+    // members record what they take part in, unless the chat lets them do more
+    let for_others = match command {
+        Command::Expense => !takes_part(conn, input)?,
+        Command::Loan | Command::Pay => records_for_other(conn, input)?,
+        _ => false,
+    };
+    if for_others && !settings.record_for_others && !role.administers() {
+        return Err(TrackerError::NotParty);
+    }
+    match command {
+        Command::Help => Ok(Reply::Text(help(input, locale))),
+        Command::Loan | Command::Pay if parse_expense(&input.text).is_ambiguous() => ask_for_amount(conn, input, locale),
+        Command::Loan | Command::Pay | Command::Expense if settings.approval_required => request_approval(conn, input, locale),
+        Command::Loan => loan(conn, input).map(|(receipts, due)| Reply::Text(generate_loan_response(&receipts, due, locale))),
+        Command::Pay => pay(conn, input).map(|receipts| Reply::Text(generate_transactions_response(&receipts, locale))),
+        Command::Expense => shared_expense(conn, input).map(|(expense, users, receipts)| {
+            Reply::Text(generate_shared_expense_response(&expense, &users, &receipts, locale))
+        }),
+        Command::Balance => balance(conn, settings).map(|b| Reply::Text(generate_event_balance_response(b, locale))),
+        Command::History => history(conn, input, locale),
+        Command::Stats => stats(conn, input, locale),
+        Command::Register => register(conn, input).map(|r| Reply::Text(generate_registration_response(&r, locale))),
+        Command::Graph => graph(conn, input, locale),
+        Command::Category => category(conn, input, locale),
+        Command::Recurring => recurring(conn, input, locale),
+        Command::Remind => remind(conn, input, locale),
+        Command::Digest => digest(conn, input, settings, locale),
+        Command::Settings => settings_menu(conn, input, settings.clone()),
+        Command::Language => language(conn, input, settings, locale),
+        Command::Event => event(conn, input, locale),
+        Command::Settle => settle(conn, input, locale),
+        Command::Role => update_role(conn, input).map(|update| Reply::Text(generate_role_response(&update, locale))),
+        Command::Leave => leave(conn, input).map(|user| Reply::Text(locale.format("leave.left", &[("name", &user.username)]))),
+        Command::ForgetMe => ask_to_forget(conn, input, locale),
+        Command::ApiToken => api_token(conn, input, locale),
+        Command::Me | Command::Statement => send_statement(conn, input, locale),
+        // Telegram sends "/start" when a private chat is opened, with the
+        // parameter of the link it was opened from
+        Command::Start if input.text.split_whitespace().nth(1) == Some(STATEMENT_START) => send_statement(conn, input, locale),
+        Command::Start if input.text.split_whitespace().nth(1).is_some_and(|start| start.starts_with(API_TOKEN_START)) => {
+            started_api_token(conn, input, locale)
+        },
+        Command::Start => Ok(Reply::Text(help(input, locale))),
+    }
+}
+
+/// "/help" or "/help <command>"
+fn help(input: &CommandInput, locale: &Locale) -> String {
+    let commands: Vec<String> = Command::bot_commands().into_iter()
+        .map(|c| c.command.trim_start_matches('/').to_string())
+        .collect();
+    generate_help_response(&commands, input.text.split_whitespace().nth(1), locale)
+}
+
+/// Handles a tapped button, `input.text` holds the data of the button.
+/// Settings buttons carry a /settings command, approvals "approve <id>" or
+/// "reject <id>" and amount questions "amount <id> <word>|cancel".
+pub fn respond_callback(conn: &SqliteConnection, input: &CommandInput) -> Reply {
+    let mut locale = Locale::fallback(input.language_code.as_deref());
+    let response = get_chat_settings(conn, &input.chat_id).and_then(|settings| {
+        locale = reader_locale(conn, input, &settings)?;
+        match input.text.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            ["/settings", ..] => settings_menu(conn, input, settings),
+            ["approve", id] => approve(conn, input, id, true, &locale),
+            ["reject", id] => approve(conn, input, id, false, &locale),
+            ["amount", id, choice] => pick_amount(conn, input, id, choice, &settings, &locale),
+            ["forget", id, answer] => answer_forget(conn, input, id, answer == &"yes", &locale),
+            ["inline", id, answer] => answer_inline(conn, input, id, answer == &"yes", &locale),
+            _ => Err(ValidationError::InvalidSetting.into()),
+        }
+    });
+    match response {
+        Ok(reply) => reply,
+        Err(e) => {
+            e.log();
+            Reply::Text(generate_error_response(&e, &locale))
+        }
+    }
+}
+
+pub fn register(conn: &SqliteConnection, input: &CommandInput) -> Result<Registration, TrackerError> {
+    info!("User is signing up for the tracker!");
+    // check if user has username setup
+    let new_user = NewUser::try_from(input)?;
+    sync_user(conn, new_user, &input.chat_id, input.role)
+}
+
+/// Registers the sender of any message without being asked to, and keeps
+/// their username and chats up to date. Senders without a username are
+/// left alone, they get told when they use a command, and so are the ones
+/// who left the chat or were forgotten.
+pub fn register_sender(conn: &SqliteConnection, input: &CommandInput) -> Result<Option<Registration>, TrackerError> {
+    if stays_away(conn, &input.sender, &input.chat_id)? {
+        return Ok(None);
+    }
+    match NewUser::try_from(input) {
+        Ok(new_user) => sync_user(conn, new_user, &input.chat_id, input.role).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Registers somebody Telegram reports in the chat: they `joined` it (or
+/// were added to it), or wrote something that is not a command. Only
+/// joining brings back somebody who left.
+pub fn register_member(
+    conn: &SqliteConnection,
+    chat_id: &ChatId,
+    user_id: &UserId,
+    username: Option<&str>,
+    joined: bool,
+) -> Result<Option<Registration>, TrackerError> {
+    if !joined && stays_away(conn, user_id, chat_id)? {
+        return Ok(None);
+    }
+    match username {
+        Some(username) => sync_user(conn, NewUser { user_id: *user_id, username: username.to_string() }, chat_id, Role::Member).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Whether the user left the chat, or asked to be forgotten, and hasn't
+/// come back with /register or by joining since
+fn stays_away(conn: &SqliteConnection, user_id: &UserId, chat_id: &ChatId) -> Result<bool, TrackerError> {
+    if get_left_chats(conn, user_id)?.contains(chat_id) {
+        return Ok(true);
+    }
+    Ok(is_forgotten(conn, user_id)? && get_user_by_user_id(conn, user_id)?.is_empty())
+}
+
+/// Makes sure the user is registered under their current username and is a
+/// member of the chat
+fn sync_user(conn: &SqliteConnection, new_user: NewUser, chat_id: &ChatId, role: Role) -> Result<Registration, TrackerError> {
+    // find all redistered users with same id (should be vec of 0 or 1 users)
+    let mut users = get_user_by_user_id(conn, &new_user.user_id)?;
+    // user does not exist ->  register
+    if users.is_empty() {
+        let created_user = insert_user(conn, new_user)?;
+        insert_user_into_room(conn, &created_user.user_id, chat_id, role)?;
+        return Ok(Registration::Registered(created_user));
+    }
+    // too many users exist -> notify invalid state
+    if users.len() > 1 {
+        return Err(TrackerError::Consistency(format!("{} registered users with id {}", users.len(), new_user.user_id)));
+    }
+    // user already registered -> check for username change
+    // also check if registering from new chat
+    let mut existing_user = match users.pop() {
+        Some(u) => u,
+        None => return Err(TrackerError::NotRegistered),
+    };
+    let mut added_to_chat = false;
+    // check if new chat should be inserted
+    if !is_user_in_chat(conn, existing_user.user_id, *chat_id)? {
+        insert_user_into_room(conn, &existing_user.user_id, chat_id, role)?;
+        added_to_chat = true;
+    }
+    // check for username change
+    if new_user.username.ne(&existing_user.username) {
+        existing_user.username = new_user.username;
+        let updated_user = update_user(conn, existing_user)?;
+        Ok(Registration::UsernameUpdated(updated_user))
+    } else if added_to_chat {
+        Ok(Registration::AddedToChat(existing_user))
+    } else {
+        Ok(Registration::AlreadyRegistered(existing_user))
+    }
+}
+
+/// Whether the user neither owes nor is owed anything in the ledgers, with
+/// each person and in each event on its own
+fn settled(ledgers: &[Ledger], user_id: &UserId) -> bool {
+    let mut owed: HashMap<(UserId, Option<&str>), i64> = HashMap::new();
+    for ledger in ledgers.iter() {
+        let event = ledger.event_id.as_deref();
+        if ledger.borrower == *user_id {
+            *owed.entry((ledger.owes, event)).or_default() -= ledger.sum as i64;
+        } else if ledger.owes == *user_id {
+            *owed.entry((ledger.borrower, event)).or_default() += ledger.sum as i64;
+        }
+    }
+    owed.values().all(|sum| *sum == 0)
+}
+
+/// Debt between the members of the chat, everyday and of the events that
+/// are still open
+fn open_chat_ledgers(conn: &SqliteConnection, chat_id: &ChatId) -> Result<Vec<Ledger>, TrackerError> {
+    let users = get_chat_users(conn, chat_id)?;
+    let mut ledgers = get_group_ledgers(conn, &users, None)?;
+    for event in get_chat_events(conn, chat_id)?.into_iter().filter(|e| e.closed.is_none()) {
+        ledgers.extend(get_group_ledgers(conn, &users, Some(&event.id))?);
+    }
+    Ok(ledgers)
+}
+
+/// Takes the sender out of the chat, as long as they are even with
+/// everybody in it. Transactions stay, the sender just isn't a member.
+pub fn leave(conn: &SqliteConnection, input: &CommandInput) -> Result<User, TrackerError> {
+    let user = get_registered_user(conn, &input.sender)?;
+    if get_chat_member(conn, &input.sender, &input.chat_id)?.is_none() {
+        return Err(ValidationError::NotMember { username: user.username }.into());
+    }
+    if !settled(&open_chat_ledgers(conn, &input.chat_id)?, &input.sender) {
+        return Err(TrackerError::OpenBalances);
+    }
+    remove_user_from_room(conn, &input.sender, &input.chat_id)?;
+    Ok(user)
+}
+
+/// Telegram reports that somebody left the group (or was removed). Members
+/// who are even leave the tracker of the chat too, the others stay until
+/// their debt is settled and the chat is told so.
+pub fn member_left(conn: &SqliteConnection, chat_id: &ChatId, user_id: &UserId) -> Result<Option<String>, TrackerError> {
+    let user = match get_user_by_user_id(conn, user_id)?.pop() {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    if get_chat_member(conn, user_id, chat_id)?.is_none() {
+        return Ok(None);
+    }
+    if settled(&open_chat_ledgers(conn, chat_id)?, user_id) {
+        remove_user_from_room(conn, user_id, chat_id)?;
+        return Ok(None);
+    }
+    let locale = chat_locale(&get_chat_settings(conn, chat_id)?);
+    Ok(Some(locale.format("leave.left_open", &[("name", &user.username)])))
+}
+
+/// "/forget_me" asks first, forgetting can't be undone
+fn ask_to_forget(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    get_registered_user(conn, &input.sender)?;
+    if !settled(&get_user_ledgers(conn, &input.sender)?, &input.sender) {
+        return Err(TrackerError::OpenBalances);
+    }
+    Ok(Reply::Menu {
+        text: locale.text("forget.confirm"),
+        buttons: generate_forget_buttons(&input.sender, locale),
+    })
+}
+
+fn answer_forget(conn: &SqliteConnection, input: &CommandInput, user_id: &str, confirmed: bool, locale: &Locale) -> Result<Reply, TrackerError> {
+    if user_id != input.sender.to_string() {
+        return Err(ValidationError::OtherUser.into());
+    }
+    if !confirmed {
+        return Ok(Reply::Text(locale.text("forget.cancelled")));
+    }
+    forget_me(conn, input)?;
+    Ok(Reply::Text(locale.text("forget.done")))
+}
+
+/// Anonymises the sender everywhere, once they are even with everybody in
+/// every chat. Transactions they took part in stay, without their name.
+pub fn forget_me(conn: &SqliteConnection, input: &CommandInput) -> Result<User, TrackerError> {
+    get_registered_user(conn, &input.sender)?;
+    if !settled(&get_user_ledgers(conn, &input.sender)?, &input.sender) {
+        return Err(TrackerError::OpenBalances);
+    }
+    info!("User asked to be forgotten!");
+    forget_user(conn, &input.sender)
+}
+
+/// "/apitoken" issues a new token for the HTTP API of the chat, "/apitoken
+/// revoke" drops it (`None`). Only admins hand out the debt of the chat.
+/// The issued token is returned in plain text, it isn't stored that way.
+pub fn update_api_token(conn: &SqliteConnection, input: &CommandInput) -> Result<Option<NewApiToken>, TrackerError> {
+    // the API reads the debt of a group
+    if input.chat_id.0 >= 0 {
+        return Err(ValidationError::GroupOnly.into());
+    }
+    if !member_role(conn, input)?.administers() {
+        return Err(TrackerError::Forbidden);
+    }
+    match input.text.split_whitespace().nth(1) {
+        None => {
+            let issued = NewApiToken::new(input.chat_id, input.sent);
+            replace_api_token(conn, &issued)?;
+            Ok(Some(issued))
+        },
+        Some(arg) if arg.eq_ignore_ascii_case("revoke") => {
+            delete_api_token(conn, &input.chat_id)?;
+            Ok(None)
+        },
+        Some(_) => Err(ValidationError::InvalidApiToken.into()),
+    }
+}
+
+/// The token is a password, it goes to the sender privately
+fn api_token(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let token = match update_api_token(conn, input)? {
+        Some(token) => token,
+        None => return Ok(Reply::Text(locale.text("api.revoked"))),
+    };
+    let sender = get_registered_user(conn, &input.sender)?;
+    let name = [("name", sender.username.as_str())];
+    Ok(Reply::Direct {
+        user: input.sender,
+        text: locale.format("api.issued", &[("token", &token.token)]),
+        notice: locale.format("api.sent", &name),
+        unreachable: locale.format("api.unreachable", &name),
+        start: format!("{}{}", API_TOKEN_START, input.chat_id),
+    })
+}
+
+/// "/start apitoken-100123", from the link admins get when the token can't
+/// be sent to them: a new token of that group, in private. Telegram isn't
+/// asked about the group from here, the role saved there counts.
+fn started_api_token(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let start = input.text.split_whitespace().nth(1).unwrap_or_default();
+    let chat_id = match start.strip_prefix(API_TOKEN_START).map(str::parse) {
+        Some(Ok(chat_id)) => ChatId(chat_id),
+        _ => return Err(ValidationError::InvalidApiToken.into()),
+    };
+    let in_group = CommandInput {
+        chat_id,
+        text: "/apitoken".to_string(),
+        mentions: vec![],
+        role: saved_role(conn, &input.sender, &chat_id)?,
+        ..input.clone()
+    };
+    match update_api_token(conn, &in_group)? {
+        Some(token) => Ok(Reply::Text(locale.format("api.issued", &[("token", &token.token)]))),
+        None => Err(ValidationError::InvalidApiToken.into()),
+    }
+}
+
+/// Role of the sender in the chat, the one given with /role unless they
+/// own or administer the Telegram group. Those roles are saved, so /role
+/// lists the owner and admins of the group too, and are taken away again
+/// once Telegram does.
+pub fn member_role(conn: &SqliteConnection, input: &CommandInput) -> Result<Role, TrackerError> {
+    match get_chat_member(conn, &input.sender, &input.chat_id)? {
+        Some(mut member) if member.role < input.role && input.role.administers() => {
+            member.role = input.role;
+            member.role_assigned = false;
+            Ok(update_chat_member(conn, member)?.role)
+        },
+        Some(mut member) if member.role > input.role && member.role.administers() && !member.role_assigned => {
+            member.role = input.role;
+            Ok(update_chat_member(conn, member)?.role)
+        },
+        Some(member) => Ok(member.role),
+        None => Ok(input.role),
+    }
+}
+
+/// Role saved for the user in the chat, for when Telegram can't be asked
+pub fn saved_role(conn: &SqliteConnection, user_id: &UserId, chat_id: &ChatId) -> Result<Role, TrackerError> {
+    Ok(get_chat_member(conn, user_id, chat_id)?.map_or(Role::Member, |member| member.role))
+}
+
+/// Whether the sender is among the people the command mentions
+fn takes_part(conn: &SqliteConnection, input: &CommandInput) -> Result<bool, TrackerError> {
+    let sender = get_registered_user(conn, &input.sender)?;
+    Ok(extract_mentions(&input.text).iter().any(|name| name.eq_ignore_ascii_case(&sender.username)))
+}
+
+/// Whether the loan or payment is recorded on behalf of somebody else than
+/// the sender ("by:@ana")
+fn records_for_other(conn: &SqliteConnection, input: &CommandInput) -> Result<bool, TrackerError> {
+    let sender = get_registered_user(conn, &input.sender)?;
+    Ok(message_validator::extract_payer(&input.text).is_some_and(|name| !name.eq_ignore_ascii_case(&sender.username)))
+}
+
+/// "/role" lists the roles of the chat, "/role @name <role>" changes one.
+/// Admins change roles, only the owner makes and unmakes admins and the
+/// owner stays the owner.
+pub fn update_role(conn: &SqliteConnection, input: &CommandInput) -> Result<RoleUpdate, TrackerError> {
+    let args: Vec<&str> = input.text.split_whitespace().skip(1).collect();
+    let (username, role) = match args.as_slice() {
+        [] => {
+            let users = get_chat_users(conn, &input.chat_id)?;
+            let mut roles: Vec<(User, Role)> = get_chat_members(conn, &input.chat_id)?.into_iter()
+                .filter_map(|member| users.iter().find(|u| u.user_id == member.user_id).map(|u| (u.clone(), member.role)))
+                .collect();
+            roles.sort_by(|(a, a_role), (b, b_role)| b_role.cmp(a_role).then_with(|| a.username.cmp(&b.username)));
+            return Ok(RoleUpdate::Listed(roles));
+        },
+        [name, role] => match (name.strip_prefix('@'), Role::parse(role)) {
+            (Some(name), Some(role)) => (name, role),
+            _ => return Err(ValidationError::InvalidRole.into()),
+        },
+        _ => return Err(ValidationError::InvalidRole.into()),
+    };
+    let sender_role = member_role(conn, input)?;
+    if !sender_role.administers() {
+        return Err(TrackerError::Forbidden);
+    }
+    let not_member = || ValidationError::NotMember { username: username.to_string() };
+    let user = get_user_by_username(conn, username.to_string())?.pop().ok_or_else(not_member)?;
+    let mut member = get_chat_member(conn, &user.user_id, &input.chat_id)?.ok_or_else(not_member)?;
+    if member.role == Role::Owner || role == Role::Owner {
+        return Err(ValidationError::OwnerRole.into());
+    }
+    if (member.role == Role::Admin || role == Role::Admin) && sender_role != Role::Owner {
+        return Err(TrackerError::OwnerOnly);
+    }
+    member.role = role;
+    member.role_assigned = true;
+    update_chat_member(conn, member)?;
+    Ok(RoleUpdate::Changed(user, role))
+}
+
+/// Executes the loan and returns its due date, if it has one
+pub fn loan(conn: &SqliteConnection, input: &CommandInput) -> Result<(Receipts, Option<NaiveDateTime>), TrackerError> {
+    info!("User is executing a loan!");
+    let due = message_validator::extract_due_date(&input.text, get_chat_settings(conn, &input.chat_id)?.timezone)?;
+    let transactions = message_validator::validate_loan_message(conn, input)?;
+    let receipts = execute_transactions(conn, transactions);
+    if let Some(due) = due {
+        for receipt in receipts.iter().flatten() {
+            remind_when_due(conn, &input.chat_id, receipt, due)?;
+        }
+    }
+    Ok((receipts, due))
+}
+
+/// Saves a loan or payment until the people it concerns approve it. The
+/// message is checked right away, so mistakes don't wait for approval.
+fn request_approval(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    message_validator::extract_due_date(&input.text, locale.timezone)?;
+    if input.text.starts_with("/expense") {
+        message_validator::validate_expense_message(conn, input)?;
+    } else if input.text.starts_with("/pay") {
+        message_validator::validate_message(conn, input, TransactionType::Payment)?;
+    } else {
+        message_validator::validate_message(conn, input, TransactionType::Loan)?;
+    }
+    let approval = insert_approval(conn, NewApproval {
+        chat_id: input.chat_id,
+        sender: input.sender,
+        text: input.text.clone(),
+        created: input.sent,
+    })?;
+    Ok(Reply::Menu {
+        text: generate_approval_request_response(&approval, locale),
+        buttons: generate_approval_buttons(&approval, locale),
+    })
+}
+
+/// Approves (executes) or rejects a waiting loan or payment. Anybody it
+/// mentions, except the sender, or an admin of the chat can decide.
+fn approve(
+    conn: &SqliteConnection,
+    input: &CommandInput,
+    approval_id: &str,
+    approved: bool,
+    locale: &Locale
+) -> Result<Reply, TrackerError> {
+    let approval = match get_approval(conn, approval_id)? {
+        Some(approval) if approval.chat_id == input.chat_id => approval,
+        _ => return Err(ValidationError::UnknownApproval.into()),
+    };
+    let approver = get_registered_user(conn, &input.sender)?;
+    let mentioned = extract_mentions(&approval.text).iter().any(|name| name.eq_ignore_ascii_case(&approver.username));
+    if !member_role(conn, input)?.administers() && (!mentioned || approver.user_id == approval.sender) {
+        return Err(TrackerError::Forbidden);
+    }
+    conn.transaction::<_, TrackerError, _>(|| {
+        delete_approval(conn, &approval.id)?;
+        if !approved {
+            return Ok(Reply::Text(locale.format("approval.rejected", &[
+                ("name", &approver.username),
+                ("command", &approval.text),
+            ])));
+        }
+        let recorded = approved_input(&approval);
+        let text = if approval.text.starts_with("/expense") {
+            let (expense, users, receipts) = shared_expense(conn, &recorded)?;
+            generate_shared_expense_response(&expense, &users, &receipts, locale)
+        } else if approval.text.starts_with("/pay") {
+            generate_transactions_response(&pay(conn, &recorded)?, locale)
+        } else {
+            let (receipts, due) = loan(conn, &recorded)?;
+            generate_loan_response(&receipts, due, locale)
+        };
+        Ok(Reply::Text(format!("{}\n{}", text, locale.format("approval.approved", &[("name", &approver.username)]))))
+    })
+}
+
+/// Asks the sender which of the numbers in the message is the amount
+fn ask_for_amount(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let draft = insert_draft(conn, NewDraft {
+        id: None,
+        chat_id: input.chat_id,
+        sender: input.sender,
+        text: input.text.clone(),
+        created: input.sent,
+    })?;
+    let (key, candidates) = match parse_expense(&draft.text) {
+        // the amount is known, not how to read it
+        Expense { amount: Some(amount), .. } => ("amount.reading", vec![amount]),
+        expense => ("amount.ask", expense.candidates),
+    };
+    Ok(Reply::Menu {
+        text: locale.format(key, &[("command", &draft.text)]),
+        buttons: generate_amount_buttons(&draft, &candidates, locale),
+    })
+}
+
+/// Records the loan or payment of a draft with the amount its sender picked
+/// (the index of the word), or drops it
+fn pick_amount(
+    conn: &SqliteConnection,
+    input: &CommandInput,
+    draft_id: &str,
+    choice: &str,
+    settings: &ChatSettings,
+    locale: &Locale
+) -> Result<Reply, TrackerError> {
+    let draft = match get_draft(conn, draft_id)? {
+        Some(draft) if draft.chat_id == input.chat_id => draft,
+        _ => return Err(ValidationError::UnknownDraft.into()),
+    };
+    if draft.sender != input.sender {
+        return Err(TrackerError::Forbidden);
+    }
+    // "<word>" or "<word>:<cents>" for one of the readings of the word
+    let (word, cents) = match choice.split_once(':') {
+        Some((word, cents)) => (word, cents.parse::<i64>().ok()),
+        None => (choice, None),
+    };
+    let amount = parse_expense(&draft.text).candidates.into_iter()
+        .find(|candidate| candidate.word.to_string() == word);
+    let text = match (choice, amount, cents) {
+        ("cancel", _, _) => {
+            delete_draft(conn, &draft.id)?;
+            return Ok(Reply::Text(locale.text("amount.cancelled")));
+        },
+        (_, Some(amount), Some(cents)) if readings(&amount.number).contains(&cents) => {
+            // written so it reads only one way ("12.345" -> "12345.00")
+            let unambiguous = format!("{}.{:02}", cents / 100, cents % 100);
+            let words: Vec<String> = draft.text.split_whitespace().enumerate()
+                .map(|(i, word)| match i == amount.word {
+                    true => word.replacen(&amount.number, &unambiguous, 1),
+                    false => word.to_string(),
+                })
+                .collect();
+            put_amount_first(&words.join(" "), &amount)
+        },
+        (_, Some(amount), None) => put_amount_first(&draft.text, &amount),
+        _ => return Err(ValidationError::UnknownDraft.into()),
+    };
+    delete_draft(conn, &draft.id)?;
+    let command = match text.starts_with("/pay") {
+        true => Command::Pay,
+        false => Command::Loan,
+    };
+    let picked = CommandInput {
+        sender: draft.sender,
+        sender_username: input.sender_username.clone(),
+        chat_id: draft.chat_id,
+        mentions: extract_mentions(&text),
+        text,
+        sent: draft.created,
+        role: input.role,
+        language_code: input.language_code.clone(),
+    };
+    execute(conn, &command, &picked, settings, locale)
+}
+
+/// Group chats the user can record loans in from inline mode
+pub fn tracked_chats(conn: &SqliteConnection, user_id: &UserId) -> Result<Vec<ChatId>, TrackerError> {
+    get_registered_user(conn, user_id)?;
+    Ok(get_user_chats(conn, user_id)?.into_iter()
+        .map(|member| member.chat_id)
+        .filter(|chat_id| chat_id.0 < 0)
+        .collect())
+}
+
+/// "@DebtBot 12.50 @ana pizza" typed in any chat: one result per chat in
+/// `chats` (the ones of `tracked_chats` with their titles and the role
+/// Telegram gives the user there) the user may record the loan in. Nothing
+/// is saved until the user picks a result (`pick_inline_loan`), and nothing
+/// is offered until there is an amount.
+pub fn inline_loan(conn: &SqliteConnection, input: &CommandInput, chats: &[(ChatId, String, Role)]) -> Result<Vec<InlineLoan>, TrackerError> {
+    let user = get_registered_user(conn, &input.sender)?;
+    let expense = parse_expense(&input.text);
+    if expense.amount.is_none() && !expense.is_ambiguous() {
+        return Ok(vec![]);
+    }
+    let mut loans = vec![];
+    for (chat_id, title, role) in chats.iter() {
+        let settings = get_chat_settings(conn, chat_id)?;
+        let in_chat = CommandInput { chat_id: *chat_id, role: *role, ..input.clone() };
+        let role = member_role(conn, &in_chat)?;
+        if !role.records() || (settings.admin_only && !role.administers()) {
+            continue;
+        }
+        let locale = reader_locale(conn, input, &settings)?;
+        // the draft gets this id once the result is picked
+        let draft_id = Uuid::new_v4().to_string();
+        loans.push(InlineLoan {
+            id: format!("{} {}", chat_id, draft_id),
+            title: locale.format("inline.title", &[("chat", title)]),
+            description: input.text.clone(),
+            text: locale.format("inline.request", &[("name", &user.username), ("chat", title), ("command", &input.text)]),
+            buttons: generate_inline_buttons(&draft_id, &locale),
+        });
+    }
+    Ok(loans)
+}
+
+/// Telegram reports the result of `inline_loan` the user picked: its loan
+/// waits for the confirmation from now on
+pub fn pick_inline_loan(conn: &SqliteConnection, input: &CommandInput, result_id: &str) -> Result<Draft, TrackerError> {
+    let (chat_id, draft_id) = match result_id.split_once(' ').map(|(chat, draft)| (chat.parse().map(ChatId), draft)) {
+        Some((Ok(chat_id), draft_id)) => (chat_id, draft_id),
+        _ => return Err(ValidationError::UnknownDraft.into()),
+    };
+    if !tracked_chats(conn, &input.sender)?.contains(&chat_id) {
+        return Err(ValidationError::UnknownDraft.into());
+    }
+    if let Some(draft) = get_draft(conn, draft_id)? {
+        return Ok(draft);
+    }
+    insert_draft(conn, NewDraft {
+        id: Some(draft_id.to_string()),
+        chat_id,
+        sender: input.sender,
+        text: input.text.clone(),
+        created: input.sent,
+    })
+}
+
+/// Chat a tapped button acts in: the one of the loan for the buttons of a
+/// loan typed in inline mode, otherwise the one it was tapped in. The bot
+/// fills in the role of the sender there.
+pub fn callback_chat(conn: &SqliteConnection, input: &CommandInput) -> Result<ChatId, TrackerError> {
+    match input.text.split_whitespace().collect::<Vec<&str>>().as_slice() {
+        ["inline", id, _] => Ok(get_draft(conn, id)?.map_or(input.chat_id, |draft| draft.chat_id)),
+        _ => Ok(input.chat_id),
+    }
+}
+
+/// Confirms or cancels a loan typed in inline mode. A confirmed one is
+/// recorded in its chat as if it was sent there, `input.role` is the role
+/// of the sender in that chat (see `callback_chat`).
+fn answer_inline(conn: &SqliteConnection, input: &CommandInput, draft_id: &str, confirmed: bool, locale: &Locale) -> Result<Reply, TrackerError> {
+    let draft = match get_draft(conn, draft_id)? {
+        Some(draft) => draft,
+        None => return Err(ValidationError::UnknownDraft.into()),
+    };
+    if draft.sender != input.sender {
+        return Err(ValidationError::OtherUser.into());
+    }
+    delete_draft(conn, &draft.id)?;
+    if !confirmed {
+        return Ok(Reply::Text(locale.text("inline.cancelled")));
+    }
+    let recorded = CommandInput {
+        sender: draft.sender,
+        sender_username: input.sender_username.clone(),
+        chat_id: draft.chat_id,
+        mentions: extract_mentions(&draft.text),
+        text: draft.text,
+        sent: input.sent,
+        role: input.role,
+        language_code: input.language_code.clone(),
+    };
+    let settings = get_chat_settings(conn, &recorded.chat_id)?;
+    let locale = reader_locale(conn, &recorded, &settings)?;
+    let reply = execute(conn, &Command::Loan, &recorded, &settings, &locale)?;
+    Ok(Reply::Elsewhere { chat_id: recorded.chat_id, reply: Box::new(reply) })
+}
+
+/// The waiting command as if it was sent just now by its sender
+fn approved_input(approval: &Approval) -> CommandInput {
+    CommandInput {
+        sender: approval.sender,
+        sender_username: None,
+        chat_id: approval.chat_id,
+        text: approval.text.clone(),
+        mentions: extract_mentions(&approval.text),
+        sent: approval.created,
+        role: Role::Member,
+        language_code: None,
+    }
+}
+
+pub fn pay(conn: &SqliteConnection, input: &CommandInput) -> Result<Receipts, TrackerError> {
+    info!("User is executing a payment!");
+    let transactions = message_validator::validate_pay_message(conn, input)?;
+    let receipts = execute_transactions(conn, transactions);
+    // settled debt needs no more reminding
+    for receipt in receipts.iter().flatten() {
+        if net_debt(conn, &receipt.sender.user_id, &receipt.reciever.user_id)? <= 0 {
+            delete_reminders(conn, &receipt.sender.user_id, &receipt.reciever.user_id)?;
+        }
+    }
+    Ok(receipts)
+}
+
+/// Records a shared expense as the loans that even out what everybody paid,
+/// together with the people it is shared by
+pub fn shared_expense(conn: &SqliteConnection, input: &CommandInput) -> Result<(SharedExpense, Vec<User>, Receipts), TrackerError> {
+    info!("User is sharing an expense!");
+    let (new_expense, users) = message_validator::validate_expense_message(conn, input)?;
+    let event_id = get_active_event(conn, &input.chat_id)?.map(|e| e.id);
+    conn.transaction(|| {
+        let expense = insert_shared_expense(conn, &new_expense)?;
+        let mut transactions: Vec<NewTransaction> = expense.loans().into_iter()
+            .map(|(lender, borrower, sum)| NewTransaction {
+                transaction_type: TransactionType::Loan,
+                initiator: lender,
+                reciever: borrower,
+                sum,
+                description: expense.description.clone(),
+                created: expense.created,
+                event_id: event_id.clone(),
+                expense_id: Some(expense.id.clone()),
+                recorded_by: Some(input.sender),
+                categories: new_expense.categories.clone(),
+            })
+            .collect();
+        // executed from the last one, the receipts are in mention order
+        transactions.reverse();
+        let receipts = execute_transactions(conn, transactions);
+        Ok((expense, users, receipts))
+    })
+}
+
+/// Reciever of the loan gets reminded in the chat once it is due. An earlier
+/// due date of the same debt is kept.
+fn remind_when_due(conn: &SqliteConnection, chat_id: &ChatId, receipt: &TransactionReceipt, due: NaiveDateTime) -> Result<(), TrackerError> {
+    let (borrower, owes) = (receipt.reciever.user_id, receipt.sender.user_id);
+    match get_reminder(conn, chat_id, &borrower, &owes)? {
+        Some(mut reminder) => {
+            reminder.due = Some(reminder.due.map_or(due, |earlier| earlier.min(due)));
+            update_reminder(conn, reminder)?;
+        },
+        None => {
+            insert_reminder(conn, NewReminder { chat_id: *chat_id, borrower, owes, due: Some(due) })?;
+        },
+    }
+    Ok(())
+}
+
+/// What `borrower` owes `owes` after debt in the other direction is
+/// subtracted, in cents. Only everyday debt gets reminded about.
+fn net_debt(conn: &SqliteConnection, borrower: &UserId, owes: &UserId) -> Result<i64, TrackerError> {
+    let owed: i64 = get_ledger(conn, borrower, owes, None)?.iter().map(|l| l.sum as i64).sum();
+    let lent: i64 = get_ledger(conn, owes, borrower, None)?.iter().map(|l| l.sum as i64).sum();
+    Ok(owed - lent)
+}
+
+/// Debt of the chat (of its active event, if there is one). With
+/// auto-simplify on the simplified debt is saved in place of the recorded
+/// one, otherwise the recorded debt is shown as is.
+pub fn balance(conn: &SqliteConnection, settings: &ChatSettings) -> Result<Balance, TrackerError> {
+    info!("Some user is checking balance!");
+    let users = get_chat_users(conn, &settings.chat_id)?;
+    let event = get_active_event(conn, &settings.chat_id)?;
+    let original = get_group_ledgers(conn, &users, event.as_ref().map(|e| e.id.as_str()))?;
+    if !settings.auto_simplify {
+        return Ok(Balance { ledgers: original, users, event });
+    }
+    let (ledgers, users) = detect_debt(users, original.clone());
+    // simplified debt replaces the recorded one
+    conn.transaction::<_, TrackerError, _>(|| {
+        for (before, after) in original.iter().zip(ledgers.iter()) {
+            if before.sum != after.sum {
+                update_ledger(conn, after.clone())?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(Balance { ledgers, users, event })
+}
+
+/// Recorded and simplified debt of a chat, the everyday one or of an event.
+/// Unlike `balance` this only looks, the ledgers are left as they are.
+pub fn debt_graphs(conn: &SqliteConnection, chat_id: &ChatId, event: Option<&str>) -> Result<(Graph, Graph), TrackerError> {
+    let users = get_chat_users(conn, chat_id)?;
+    let ledgers = get_group_ledgers(conn, &users, event)?;
+    let recorded = Graph::from(users, ledgers);
+    let (ledgers, users) = detect_debt(recorded.users.clone(), recorded.ledgers.clone());
+    Ok((recorded, Graph::from(users, ledgers)))
+}
+
+/// Who pays how much to whom to settle the debt of the active event (or the
+/// everyday debt)
+fn settle(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let event = get_active_event(conn, &input.chat_id)?;
+    let (recorded, _) = debt_graphs(conn, &input.chat_id, event.as_ref().map(|e| e.id.as_str()))?;
+    let transfers = recorded.settlement_transfers();
+    Ok(Reply::Text(generate_settlement_response(&transfers, &recorded.users, event.as_ref(), locale)))
+}
+
+/// Net debt of the user with every counterparty and their latest
+/// transactions, over every chat they share with the bot
+pub fn statement(conn: &SqliteConnection, user_id: &UserId) -> Result<Statement, TrackerError> {
+    let user = get_registered_user(conn, user_id)?;
+    let mut owed: HashMap<UserId, i64> = HashMap::new();
+    for ledger in get_user_ledgers(conn, user_id)? {
+        match ledger.borrower == *user_id {
+            true => *owed.entry(ledger.owes).or_default() -= ledger.sum as i64,
+            false => *owed.entry(ledger.borrower).or_default() += ledger.sum as i64,
+        }
+    }
+    let mut balances: Vec<(UserId, i64)> = owed.into_iter().filter(|(_, sum)| *sum != 0).collect();
+    balances.sort_by_key(|(id, sum)| (-sum, id.0));
+    let mut transactions = get_user_transactions(conn, user_id)?;
+    let skip = transactions.len().saturating_sub(STATEMENT_TRANSACTIONS);
+    transactions.drain(..skip);
+    let mut counterparties: Vec<UserId> = balances.iter().map(|(id, _)| *id)
+        .chain(transactions.iter().flat_map(|t| [t.initiator, t.reciever]))
+        .filter(|id| id != user_id)
+        .collect();
+    counterparties.sort_by_key(|id| id.0);
+    counterparties.dedup();
+    let mut users = vec![user.clone()];
+    for id in counterparties.iter() {
+        users.extend(get_user_by_user_id(conn, id)?.pop());
+    }
+    Ok(Statement { user, balances, transactions, users })
+}
+
+/// The statement of the sender, asked for in a group it goes by DM
+fn send_statement(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let statement = statement(conn, &input.sender)?;
+    let text = generate_statement_response(&statement, locale);
+    // a private chat with the bot has the id of the user
+    if input.chat_id.0 == input.sender.0 as i64 {
+        return Ok(Reply::Text(text));
+    }
+    let name = [("name", statement.user.username.as_str())];
+    Ok(Reply::Direct {
+        user: input.sender,
+        text,
+        notice: locale.format("statement.sent", &name),
+        unreachable: locale.format("statement.unreachable", &name),
+        start: STATEMENT_START.to_string(),
+    })
+}
+
+fn graph(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    info!("Some user is drawing the debt graph!");
+    let event = get_active_event(conn, &input.chat_id)?;
+    let (recorded, simplified) = debt_graphs(conn, &input.chat_id, event.as_ref().map(|e| e.id.as_str()))?;
+    let dot = input.text.split_whitespace().nth(1).is_some_and(|arg| arg.eq_ignore_ascii_case("dot"));
+    if dot {
+        return Ok(Reply::Document {
+            file_name: "debt.dot".to_string(),
+            content: generate_dot_graph(&recorded, &simplified, locale).into_bytes(),
+            caption: locale.text("graph.dot_caption"),
+        });
+    }
+    Ok(Reply::Photo {
+        image: render_debt_graphs(&recorded, &simplified, locale)?,
+        caption: locale.text("graph.caption"),
+    })
+}
+
+/// Spending statistics of the chat in the time range given in the message,
+/// only for the category given with a hashtag ("/stats 6m #food")
+pub fn chat_stats(conn: &SqliteConnection, input: &CommandInput) -> Result<Stats, TrackerError> {
+    let since = message_validator::extract_time_range(&input.text, input.sent)?;
+    let filter = message_validator::extract_hashtags(&input.text).into_iter().next();
+    let filter = filter.map(|tag| resolve_category(conn, input, &tag)).transpose()?;
+    let users = get_chat_users(conn, &input.chat_id)?;
+    let mut transactions = get_group_transactions(conn, &users)?;
+    let tagged = get_transaction_categories(conn, &input.chat_id)?;
+    if let Some(category) = filter.as_ref() {
+        transactions.retain(|t| is_tagged(&tagged, t.id.as_str(), category));
+    }
+    let mut stats = Stats::from(&users, &transactions, &tagged, since);
+    stats.category = filter.map(|c| c.name);
+    Ok(stats)
+}
+
+fn history(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    info!("Some user is checking history!");
+    let (count, filter) = message_validator::extract_history_query(&input.text)?;
+    let filter = filter.map(|tag| resolve_category(conn, input, &tag)).transpose()?;
+    let users = get_chat_users(conn, &input.chat_id)?;
+    let mut transactions = get_group_transactions(conn, &users)?;
+    let tagged = get_transaction_categories(conn, &input.chat_id)?;
+    if let Some(category) = filter.as_ref() {
+        transactions.retain(|t| is_tagged(&tagged, t.id.as_str(), category));
+    }
+    show_expenses_once(&mut transactions);
+    let expenses = get_chat_shared_expenses(conn, &input.chat_id)?;
+    let skip = transactions.len().saturating_sub(count as usize);
+    Ok(Reply::Text(generate_history_response(&transactions[skip..], &users, &tagged, &expenses, locale)))
+}
+
+/// The loans of a shared expense are one entry of the history, the first
+/// of them stands for the expense
+fn show_expenses_once(transactions: &mut Vec<Transaction>) {
+    let mut shown = HashSet::new();
+    transactions.retain(|t| t.expense_id.as_ref().is_none_or(|id| shown.insert(id.clone())));
+}
+
+/// "/category", "/category add <name>" or "/category alias <alias> <name>"
+pub fn update_categories(conn: &SqliteConnection, input: &CommandInput) -> Result<Option<CategoryUpdate>, TrackerError> {
+    let args: Vec<String> = input.text.split_whitespace()
+        .skip(1)
+        .map(|arg| arg.trim_start_matches('#').to_lowercase())
+        .collect();
+    let valid = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match args.as_slice() {
+        [] => Ok(Some(CategoryUpdate::Listed(
+            get_chat_categories(conn, &input.chat_id)?,
+            get_chat_aliases(conn, &input.chat_id)?
+        ))),
+        ["add", name] => {
+            if !valid(name) {
+                return Err(ValidationError::InvalidCategory.into());
+            }
+            if let Some(existing) = find_category(conn, &input.chat_id, name)? {
+                return Ok(Some(CategoryUpdate::AlreadyExists(name.to_string(), existing)));
+            }
+            let category = insert_category(conn, NewCategory { chat_id: input.chat_id, name: name.to_string() })?;
+            Ok(Some(CategoryUpdate::Added(category)))
+        },
+        ["alias", alias, name] => {
+            if !valid(alias) {
+                return Err(ValidationError::InvalidCategory.into());
+            }
+            if let Some(existing) = find_category(conn, &input.chat_id, alias)? {
+                return Ok(Some(CategoryUpdate::AlreadyExists(alias.to_string(), existing)));
+            }
+            let category = resolve_category(conn, input, name)?;
+            let alias = insert_category_alias(conn, &category, alias)?;
+            Ok(Some(CategoryUpdate::Aliased(alias, category)))
+        },
+        _ => Ok(None),
+    }
+}
+
+fn category(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let text = match update_categories(conn, input)? {
+        Some(update) => generate_category_response(&update, locale),
+        None => locale.text("help.detail.category"),
+    };
+    Ok(Reply::Text(text))
+}
+
+fn resolve_category(conn: &SqliteConnection, input: &CommandInput, name: &str) -> Result<Category, TrackerError> {
+    match find_category(conn, &input.chat_id, name)? {
+        Some(category) => Ok(category),
+        None => Err(ValidationError::UnknownCategory.into()),
+    }
+}
+
+fn is_tagged(tagged: &HashMap<String, Vec<Category>>, transaction_id: &str, category: &Category) -> bool {
+    tagged.get(transaction_id).is_some_and(|categories| categories.contains(category))
+}
+
+fn stats(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    info!("Some user is checking stats!");
+    let stats = chat_stats(conn, input)?;
+    let chart = input.text.split_whitespace().nth(1).is_some_and(|arg| arg.eq_ignore_ascii_case("chart"));
+    if !chart {
+        return Ok(Reply::Text(generate_stats_response(&stats, locale)));
+    }
+    Ok(Reply::Photo {
+        image: render_stats_charts(&stats, locale)?,
+        caption: generate_stats_response(&stats, locale),
+    })
+}
+
+/// "/recurring [list]", "/recurring add <schedule> <loan>" and
+/// "/recurring pause|resume|delete <number from the list>"
+pub fn update_recurring_loans(conn: &SqliteConnection, input: &CommandInput) -> Result<Option<RecurringUpdate>, TrackerError> {
+    let words: Vec<&str> = input.text.split_whitespace().skip(1).collect();
+    let (action, args) = match words.split_first() {
+        Some((action, args)) => (action.to_lowercase(), args),
+        None => return Ok(Some(RecurringUpdate::Listed(get_chat_recurring(conn, &input.chat_id)?))),
+    };
+    match (action.as_str(), args) {
+        ("list", []) => Ok(Some(RecurringUpdate::Listed(get_chat_recurring(conn, &input.chat_id)?))),
+        ("add", args) => {
+            let (frequency, used) = match Frequency::parse(args) {
+                Some(schedule) => schedule,
+                None => return Err(ValidationError::InvalidSchedule.into()),
+            };
+            let loan = args[used..].join(" ");
+            // runs skip the checks and approvals of loans recorded for others
+            if records_for_other(conn, input)? {
+                return Err(ValidationError::RecurringForOther.into());
+            }
+            // the loan has to make sense now, not only when it first runs
+            message_validator::validate_loan_message(conn, &recurring_input(input.sender, input.chat_id, &loan, input.sent))?;
+            let added = insert_recurring(conn, NewRecurring {
+                chat_id: input.chat_id,
+                creator: input.sender,
+                frequency,
+                loan,
+                timezone: get_chat_settings(conn, &input.chat_id)?.timezone,
+                created: input.sent,
+            })?;
+            Ok(Some(RecurringUpdate::Added(added)))
+        },
+        ("pause", [number]) => {
+            let mut selected = select_recurring(conn, input, number)?;
+            selected.paused = true;
+            Ok(Some(RecurringUpdate::Paused(update_recurring(conn, selected)?)))
+        },
+        ("resume", [number]) => {
+            // runs missed while paused are skipped
+            let mut selected = select_recurring(conn, input, number)?;
+            selected.paused = false;
+            selected.next_run = selected.frequency.next_run_after(input.sent, get_chat_settings(conn, &input.chat_id)?.timezone);
+            Ok(Some(RecurringUpdate::Resumed(update_recurring(conn, selected)?)))
+        },
+        ("delete", [number]) => {
+            let selected = select_recurring(conn, input, number)?;
+            delete_recurring(conn, &selected.id)?;
+            Ok(Some(RecurringUpdate::Deleted(selected)))
+        },
+        _ => Ok(None),
+    }
+}
+
+fn recurring(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let text = match update_recurring_loans(conn, input)? {
+        Some(update) => generate_recurring_response(&update, locale),
+        None => locale.text("help.detail.recurring"),
+    };
+    Ok(Reply::Text(text))
+}
+
+/// Recurring loan by its (1 based) number in "/recurring list"
+fn select_recurring(conn: &SqliteConnection, input: &CommandInput, number: &str) -> Result<Recurring, TrackerError> {
+    let mut listed = get_chat_recurring(conn, &input.chat_id)?;
+    match number.parse::<usize>() {
+        Ok(n) if (1..=listed.len()).contains(&n) => Ok(listed.remove(n - 1)),
+        _ => Err(ValidationError::UnknownRecurring.into()),
+    }
+}
+
+fn recurring_input(sender: UserId, chat_id: ChatId, loan: &str, sent: NaiveDateTime) -> CommandInput {
+    CommandInput {
+        sender,
+        sender_username: None,
+        chat_id,
+        text: format!("/loan {}", loan),
+        mentions: extract_mentions(loan),
+        sent,
+        role: Role::Member,
+        language_code: None,
+    }
+}
+
+/// "/event" lists the events of the chat, "/event start <name>" starts one,
+/// "/event switch [name]" makes another one (or the everyday debt) active and
+/// "/event close [name]" settles and archives one (the active one by default)
+pub fn update_events(conn: &SqliteConnection, input: &CommandInput) -> Result<EventUpdate, TrackerError> {
+    let args: Vec<&str> = input.text.split_whitespace().skip(1).collect();
+    let events = get_chat_events(conn, &input.chat_id)?;
+    let open = |name: &str| events.iter().find(|e| e.closed.is_none() && e.name.eq_ignore_ascii_case(name)).cloned();
+    let valid = |name: &str| name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    match args.as_slice() {
+        [] => Ok(EventUpdate::Listed(events)),
+        [action, name] if action.eq_ignore_ascii_case("start") => {
+            if !valid(name) {
+                return Err(ValidationError::InvalidEvent.into());
+            }
+            if open(name).is_some() {
+                return Err(ValidationError::EventExists.into());
+            }
+            let event = insert_event(conn, NewEvent { chat_id: input.chat_id, name: name.to_string(), created: input.sent })?;
+            Ok(EventUpdate::Started(event))
+        },
+        [action, rest @ ..] if action.eq_ignore_ascii_case("switch") && rest.len() <= 1 => {
+            let event = match rest.first() {
+                Some(name) => Some(open(name).ok_or(ValidationError::UnknownEvent)?),
+                None => None,
+            };
+            activate_event(conn, &input.chat_id, event.as_ref().map(|e| e.id.as_str()))?;
+            Ok(EventUpdate::Switched(event))
+        },
+        [action, rest @ ..] if action.eq_ignore_ascii_case("close") && rest.len() <= 1 => {
+            let event = match rest.first() {
+                Some(name) => open(name),
+                None => events.iter().find(|e| e.active).cloned(),
+            };
+            match event {
+                Some(event) => close_event(conn, input, event),
+                None => Err(ValidationError::UnknownEvent.into()),
+            }
+        },
+        _ => Err(ValidationError::InvalidEvent.into()),
+    }
+}
+
+/// Archives the event. What is left of its debt becomes everyday debt, as
+/// the few transfers that settle it, so it can be paid back with /pay.
+fn close_event(conn: &SqliteConnection, input: &CommandInput, mut event: Event) -> Result<EventUpdate, TrackerError> {
+    let (recorded, _) = debt_graphs(conn, &input.chat_id, Some(&event.id))?;
+    let transfers = recorded.settlement_transfers();
+    conn.transaction::<_, TrackerError, _>(|| {
+        let carried_over = transfers.iter().map(|(payer, payee, sum)| Ok(NewTransaction {
+            transaction_type: TransactionType::Loan,
+            initiator: *payee,
+            reciever: *payer,
+            sum: i32::try_from(*sum).map_err(|_| TrackerError::Consistency(format!("debt of event {} too large", event.id)))?,
+            description: event.name.clone(),
+            created: input.sent,
+            event_id: None,
+            expense_id: None,
+            recorded_by: Some(input.sender),
+            categories: vec![],
+        })).collect::<Result<Vec<NewTransaction>, TrackerError>>()?;
+        for receipt in execute_transactions(conn, carried_over) {
+            receipt?;
+        }
+        event.closed = Some(input.sent);
+        event.active = false;
+        event = update_event(conn, event.clone())?;
+        Ok(())
+    })?;
+    Ok(EventUpdate::Closed(event, transfers, recorded.users))
+}
+
+fn event(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let update = update_events(conn, input)?;
+    Ok(Reply::Text(generate_event_response(&update, locale)))
+}
+
+/// "/remind", "/remind on|off", "/remind snooze [period]" and
+/// "/remind threshold <amount>|off" (only admins set the threshold)
+pub fn update_reminders(conn: &SqliteConnection, input: &CommandInput) -> Result<ReminderUpdate, TrackerError> {
+    get_registered_user(conn, &input.sender)?;
+    let words: Vec<String> = input.text.split_whitespace().skip(1).map(|w| w.to_lowercase()).collect();
+    let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+    let mut preference = get_reminder_preference(conn, &input.sender)?;
+    // a snooze that ran out is no snooze
+    if preference.snoozed_until.is_some_and(|until| until <= input.sent) {
+        preference.snoozed_until = None;
+    }
+    match words.as_slice() {
+        [] => Ok(ReminderUpdate::Status(preference, get_reminder_threshold(conn, &input.chat_id)?)),
+        ["on"] => {
+            preference.opted_out = false;
+            preference.snoozed_until = None;
+            Ok(ReminderUpdate::Preference(save_reminder_preference(conn, preference)?))
+        },
+        ["off"] => {
+            preference.opted_out = true;
+            Ok(ReminderUpdate::Preference(save_reminder_preference(conn, preference)?))
+        },
+        ["snooze"] | ["snooze", _] => {
+            let period = words.get(1).copied().unwrap_or("7d");
+            preference.snoozed_until = match message_validator::shift_by_period(input.sent, period, true) {
+                Some(until) => Some(until),
+                None => return Err(ValidationError::InvalidTimeRange.into()),
+            };
+            Ok(ReminderUpdate::Preference(save_reminder_preference(conn, preference)?))
+        },
+        // the threshold is the same for the whole chat
+        ["threshold", ..] if !member_role(conn, input)?.administers() => Err(TrackerError::Forbidden),
+        ["threshold", "off"] => {
+            set_reminder_threshold(conn, &input.chat_id, None)?;
+            Ok(ReminderUpdate::Threshold(None))
+        },
+        ["threshold", amount] => {
+            let threshold = match message_validator::parse_amount(amount).map(i32::try_from) {
+                Some(Ok(a)) if a > 0 => a,
+                _ => return Err(ValidationError::InvalidAmount.into()),
+            };
+            set_reminder_threshold(conn, &input.chat_id, Some(threshold))?;
+            Ok(ReminderUpdate::Threshold(Some(threshold)))
+        },
+        _ => Err(ValidationError::InvalidReminder.into()),
+    }
+}
+
+fn remind(conn: &SqliteConnection, input: &CommandInput, locale: &Locale) -> Result<Reply, TrackerError> {
+    let update = update_reminders(conn, input)?;
+    Ok(Reply::Text(generate_reminder_update_response(&update, locale)))
+}
+
+/// "/digest", "/digest off" and "/digest <schedule> <HH:MM> [timezone]".
+/// The timezone defaults to the one the digest used before, or the one of
+/// the chat. Everybody sees the digest, only admins change it.
+pub fn update_digest(conn: &SqliteConnection, input: &CommandInput, settings: &ChatSettings) -> Result<DigestUpdate, TrackerError> {
+    get_registered_user(conn, &input.sender)?;
+    let words: Vec<&str> = input.text.split_whitespace().skip(1).collect();
+    let current = get_digest(conn, &input.chat_id)?;
+    match words.as_slice() {
+        [] => return Ok(DigestUpdate::Status(current)),
+        // the digest goes to everybody, only admins set it up
+        _ if !member_role(conn, input)?.administers() => return Err(TrackerError::Forbidden),
+        [off] if off.eq_ignore_ascii_case("off") => {
+            delete_digest(conn, &input.chat_id)?;
+            return Ok(DigestUpdate::Stopped);
+        },
+        _ => (),
+    }
+    let (frequency, taken) = match Frequency::parse(&words) {
+        Some(parsed) => parsed,
+        None => return Err(ValidationError::InvalidDigest.into()),
+    };
+    let (time, timezone) = match &words[taken..] {
+        [time] => (*time, None),
+        [time, timezone] => (*time, Some(*timezone)),
+        _ => return Err(ValidationError::InvalidDigest.into()),
+    };
+    let time = match NaiveTime::parse_from_str(time, "%H:%M") {
+        Ok(t) => t,
+        Err(_) => return Err(ValidationError::InvalidDigest.into()),
+    };
+    let timezone = match timezone {
+        Some(name) => match name.parse::<Tz>() {
+            Ok(tz) => tz,
+            Err(_) => return Err(ValidationError::InvalidTimezone.into()),
+        },
+        None => current.as_ref().map_or(settings.timezone, |d| d.timezone),
+    };
+    let mut digest = Digest {
+        chat_id: input.chat_id,
+        frequency,
+        time,
+        timezone,
+        next_run: input.sent,
+        last_sent: current.as_ref().and_then(|d| d.last_sent),
+        created: current.as_ref().map_or(input.sent, |d| d.created),
+    };
+    digest.next_run = digest.next_after(input.sent);
+    Ok(DigestUpdate::Scheduled(save_digest(conn, digest)?))
+}
+
+fn digest(conn: &SqliteConnection, input: &CommandInput, settings: &ChatSettings, locale: &Locale) -> Result<Reply, TrackerError> {
+    let update = update_digest(conn, input, settings)?;
+    Ok(Reply::Text(generate_digest_update_response(&update, locale)))
+}
+
+/// Balance, transactions since the last digest and how to settle up
+pub fn digest_report(
+    conn: &SqliteConnection,
+    digest: Digest,
+    now: NaiveDateTime,
+    settings: &ChatSettings
+) -> Result<DigestReport, TrackerError> {
+    let since = digest.last_sent.unwrap_or(digest.created);
+    let (recorded, simplified) = debt_graphs(conn, &digest.chat_id, None)?;
+    let balance = match settings.auto_simplify {
+        true => simplified,
+        false => recorded,
+    };
+    let mut transactions = get_group_transactions(conn, &balance.users)?;
+    transactions.retain(|t| t.created > since && t.created <= now);
+    show_expenses_once(&mut transactions);
+    let tagged = get_transaction_categories(conn, &digest.chat_id)?;
+    let expenses = get_chat_shared_expenses(conn, &digest.chat_id)?;
+    let transfers = balance.settlement_transfers();
+    Ok(DigestReport { digest, since, balance, transactions, tagged, expenses, transfers })
+}
+
+/// "/settings" shows the options of the chat, "/settings <option> <value>"
+/// changes one (only admins can)
+pub fn update_settings(conn: &SqliteConnection, input: &CommandInput, mut settings: ChatSettings) -> Result<ChatSettings, TrackerError> {
+    let words: Vec<&str> = input.text.split_whitespace().skip(1).collect();
+    let (option, value) = match words.as_slice() {
+        [] => return Ok(settings),
+        [option, value] => (option.to_lowercase(), *value),
+        _ => return Err(ValidationError::InvalidSetting.into()),
+    };
+    if !member_role(conn, input)?.administers() {
+        return Err(TrackerError::Forbidden);
+    }
+    let switch = match value.to_lowercase().as_str() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    };
+    match (option.as_str(), switch) {
+        ("currency", _) if value.len() == 3 && value.chars().all(|c| c.is_ascii_alphabetic()) => {
+            settings.currency = value.to_uppercase();
+        },
+        ("timezone", _) => settings.timezone = match value.parse::<Tz>() {
+            Ok(tz) => tz,
+            Err(_) => return Err(ValidationError::InvalidTimezone.into()),
+        },
+        ("language", _) if value.eq_ignore_ascii_case("auto") => settings.language = None,
+        ("language", _) => settings.language = match Language::parse(value) {
+            Some(language) => Some(language),
+            None => return Err(ValidationError::InvalidLanguage.into()),
+        },
+        ("simplify", Some(on)) => settings.auto_simplify = on,
+        ("approval", Some(on)) => settings.approval_required = on,
+        ("reminders", _) => settings.reminders = match ReminderPolicy::parse(value) {
+            Some(policy) => policy,
+            None => return Err(ValidationError::InvalidSetting.into()),
+        },
+        ("adminonly", Some(on)) => settings.admin_only = on,
+        ("forothers", Some(on)) => settings.record_for_others = on,
+        _ => return Err(ValidationError::InvalidSetting.into()),
+    }
+    save_chat_settings(conn, settings)
+}
+
+fn settings_menu(conn: &SqliteConnection, input: &CommandInput, settings: ChatSettings) -> Result<Reply, TrackerError> {
+    let settings = update_settings(conn, input, settings)?;
+    // a new language of the chat applies to this reply already
+    let locale = reader_locale(conn, input, &settings)?;
+    Ok(Reply::Menu {
+        text: generate_settings_response(&settings, &locale),
+        buttons: generate_settings_keyboard(&settings, &locale),
+    })
+}
+
+/// "/language" tells the sender which language they get replies in,
+/// "/language en|sl" picks one and "/language auto" goes back to the one of
+/// the chat (or of the Telegram app)
+fn language(conn: &SqliteConnection, input: &CommandInput, settings: &ChatSettings, locale: &Locale) -> Result<Reply, TrackerError> {
+    get_registered_user(conn, &input.sender)?;
+    let chosen = match input.text.split_whitespace().nth(1) {
+        None => return Ok(Reply::Text(generate_language_status_response(locale.language, locale))),
+        Some(code) if code.eq_ignore_ascii_case("auto") => None,
+        Some(code) => match Language::parse(code) {
+            Some(language) => Some(language),
+            None => return Err(ValidationError::InvalidLanguage.into()),
+        },
+    };
+    set_user_language(conn, &input.sender, chosen)?;
+    let locale = reader_locale(conn, input, settings)?;
+    Ok(Reply::Text(generate_language_response(chosen, locale.language, &locale)))
+}
+
+/// Everything the scheduler has to post right now: recurring loans,
+/// reminders and digests, as (chat, text)
+pub fn run_scheduled_jobs(conn: &SqliteConnection, now: NaiveDateTime) -> Result<Vec<(ChatId, String)>, TrackerError> {
+    let mut posts = run_due_recurring(conn, now)?;
+    posts.append(&mut run_due_reminders(conn, now)?);
+    posts.append(&mut run_due_digests(conn, now)?);
+    delete_drafts_before(conn, now - Duration::days(DRAFT_DAYS))?;
+    Ok(posts)
+}
+
+/// Posts the digest of every chat that has one due. Missed digests are not
+/// caught up, the next one covers everything since the last one sent.
+pub fn run_due_digests(conn: &SqliteConnection, now: NaiveDateTime) -> Result<Vec<(ChatId, String)>, TrackerError> {
+    let mut posts = vec![];
+    for due in get_due_digests(conn, now)? {
+        let chat_id = due.chat_id;
+        let settings = get_chat_settings(conn, &chat_id)?;
+        let mut report = digest_report(conn, due, now, &settings)?;
+        report.digest.last_sent = Some(now);
+        report.digest.next_run = report.digest.next_after(now);
+        save_digest(conn, report.digest.clone())?;
+        posts.push((chat_id, generate_digest_response(&report, &chat_locale(&settings))));
+    }
+    Ok(posts)
+}
+
+/// Reminds borrowers of debt past its due date or over the chat threshold.
+/// The same debt is reminded about at most once a week; people who started
+/// a private chat with the bot get the reminder there.
+pub fn run_due_reminders(conn: &SqliteConnection, now: NaiveDateTime) -> Result<Vec<(ChatId, String)>, TrackerError> {
+    let mut posts = vec![];
+    for reminder in get_overdue_reminders(conn, now)? {
+        let debt = net_debt(conn, &reminder.borrower, &reminder.owes)?;
+        if debt <= 0 {
+            delete_reminders(conn, &reminder.borrower, &reminder.owes)?;
+            continue;
+        }
+        let reason = ReminderReason::Overdue(reminder.due.unwrap_or(now));
+        if let Some(post) = send_reminder(conn, reminder, debt, reason, now)? {
+            posts.push(post);
+        }
+    }
+    for (chat_id, threshold) in get_reminder_thresholds(conn)? {
+        let users = get_chat_users(conn, &chat_id)?;
+        let (ledgers, _) = detect_mutual_debt(users.clone(), get_group_ledgers(conn, &users, None)?);
+        for ledger in ledgers.iter().filter(|l| l.sum > threshold) {
+            let reminder = match get_reminder(conn, &chat_id, &ledger.borrower, &ledger.owes)? {
+                // overdue debt was handled above
+                Some(reminder) if reminder.due.is_some_and(|due| due <= now) => continue,
+                Some(reminder) => reminder,
+                None => insert_reminder(conn, NewReminder { chat_id, borrower: ledger.borrower, owes: ledger.owes, due: None })?,
+            };
+            let reason = ReminderReason::OverThreshold(threshold);
+            if let Some(post) = send_reminder(conn, reminder, ledger.sum as i64, reason, now)? {
+                posts.push(post);
+            }
+        }
+    }
+    Ok(posts)
+}
+
+fn send_reminder(
+    conn: &SqliteConnection,
+    mut reminder: Reminder,
+    debt: i64,
+    reason: ReminderReason,
+    now: NaiveDateTime
+) -> Result<Option<(ChatId, String)>, TrackerError> {
+    let settings = get_chat_settings(conn, &reminder.chat_id)?;
+    let recently_sent = reminder.last_sent.is_some_and(|sent| now - sent < Duration::days(REMIND_EVERY_DAYS));
+    if settings.reminders == ReminderPolicy::Off
+        || recently_sent
+        || !get_reminder_preference(conn, &reminder.borrower)?.wants_reminders(now) {
+        return Ok(None);
+    }
+    // a private chat with the bot has the id of the user
+    let private_chat = ChatId(reminder.borrower.0 as i64);
+    let private = is_user_in_chat(conn, reminder.borrower, private_chat)?;
+    if !private && settings.reminders == ReminderPolicy::Private {
+        return Ok(None);
+    }
+    let notice = ReminderNotice {
+        borrower: get_registered_user(conn, &reminder.borrower)?,
+        owes: get_registered_user(conn, &reminder.owes)?,
+        debt,
+        reason,
+        private,
+    };
+    // a DM is read by the borrower alone, so it is in their language
+    let (chat_id, locale) = match private {
+        true => {
+            let language = get_user_language(conn, &reminder.borrower)?.or(settings.language).unwrap_or_default();
+            (private_chat, Locale::new(&settings, language))
+        },
+        false => (reminder.chat_id, chat_locale(&settings)),
+    };
+    reminder.last_sent = Some(now);
+    update_reminder(conn, reminder)?;
+    Ok(Some((chat_id, generate_reminder_response(&notice, &locale))))
+}
+
+/// Executes every recurring loan that is due and returns the messages to
+/// post (chat and text). Runs missed while the bot was down are caught up
+/// one by one; each run is saved together with the move to the next one,
+/// so a run is never executed twice.
+pub fn run_due_recurring(conn: &SqliteConnection, now: NaiveDateTime) -> Result<Vec<(ChatId, String)>, TrackerError> {
+    let mut posts = vec![];
+    for mut due in get_due_recurring(conn, now)? {
+        while due.next_run <= now {
+            let run_at = due.next_run;
+            let settings = get_chat_settings(conn, &due.chat_id)?;
+            let run = conn.transaction::<_, TrackerError, _>(|| {
+                let input = recurring_input(due.creator, due.chat_id, &due.loan, run_at);
+                // a loan that stopped making sense is reported, but not retried
+                let receipts = message_validator::validate_loan_message(conn, &input)
+                    .map(|mut transactions| {
+                        // household costs stay out of whatever event is going on
+                        transactions.iter_mut().for_each(|t| t.event_id = None);
+                        execute_transactions(conn, transactions)
+                    });
+                due.next_run = due.frequency.next_run_after(run_at, settings.timezone);
+                update_recurring(conn, due.clone())?;
+                Ok(receipts)
+            });
+            match run {
+                Ok(receipts) => {
+                    posts.push((due.chat_id, generate_recurring_run_response(&due, &run_at, &receipts, &chat_locale(&settings))));
+                },
+                Err(e) => {
+                    error!("Recurring loan {} failed: {}", due.id, e);
+                    break;
+                }
+            }
+        }
+    }
+    Ok(posts)
+}
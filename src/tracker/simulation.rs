@@ -0,0 +1,464 @@
+//! Replays a scripted conversation against a fresh in-memory database and
+//! checks the replies and balances along the way. Script format, one
+//! statement per line:
+//!
+//! ```text
+//! # comment
+//! user ana 1              declare a user (username and telegram id)
+//! user maja 3 sl          ... whose Telegram app is in Slovenian
+//! admin ana               ana is a Telegram admin of every group
+//! owner ana               ana owns every group (everybody owns their
+//!                         private chat anyway)
+//! member ana              ana is no longer the owner or an admin
+//! chat -100               messages are sent to this chat from now on
+//! ana: /loan 20 @luka     send a message as ana
+//! > ana loaned 20€ to luka!
+//! ana: hi all             a message that is not a command (no reply)
+//! luka taps /settings     luka taps the button with this data ("approve
+//!                         {approval}" approves the last approval request,
+//!                         "amount {draft} 3" picks the third word as the
+//!                         amount the last question asked for)
+//! ana inline 12 @luka     ana types "@DebtBot 12 @luka" in inline mode, the
+//!                         reply is the title and message of each result
+//! ana picks 2             ana picks the second of them ("inline {draft}
+//!                         yes" confirms it)
+//! luka joins             luka joins the group (Telegram reports it)
+//! luka leaves            luka leaves the group
+//! balance ana 20          net balance of ana in the current chat (in its
+//!                         active event, if there is one)
+//! api /api/balances       ask the HTTP API with the last token a reply
+//!                         showed, the reply is the status and JSON body
+//! now 2026-11-01          messages are sent at noon of this day from now on
+//! tick                    run the scheduled jobs (recurring loans, reminders)
+//! ```
+//!
+//! Consecutive `>` lines after a message or `tick` form its expected
+//! (multi-line) reply, `{today}` in them stands for the current date
+//! (dd.mm.yyyy) and `{token}` for the API token of the last group chat, as
+//! the reply that issued it showed it. A
+//! reply sent by DM is the notice in the chat followed by the DM. A single
+//! empty `>` line expects no reply at all. Messages without `>` lines are
+//! not checked. Without a `now` statement the real time is used.
+
+use std::{collections::HashMap, fmt, fs, path::Path};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::SqliteConnection;
+use teloxide::{types::{ChatId, UserId}, utils::command::BotCommands};
+
+use crate::helpers::data_handler::{
+    api_token_operations::get_api_token,
+    chat_operations::get_chat_users,
+    event_operations::get_active_event,
+    ledger_operations::get_group_ledgers,
+    sqlite_operations::establish_memory_connection
+};
+use crate::server::api;
+use crate::types::{
+    api_token::hash_token,
+    command_input::{extract_mentions, CommandInput},
+    error::TrackerError,
+    graph::Graph,
+    role::Role
+};
+use super::{
+    callback_chat,
+    inline_loan,
+    member_left,
+    pick_inline_loan,
+    register_member,
+    respond,
+    respond_callback,
+    run_scheduled_jobs,
+    tracked_chats,
+    Command,
+    Reply
+};
+
+#[derive(Debug)]
+pub struct Failure {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+struct Simulation {
+    conn: SqliteConnection,
+    users: HashMap<String, UserId>,
+    /// Language of the Telegram app of users that declared one
+    languages: HashMap<String, String>,
+    /// Roles Telegram gives users in every group
+    roles: HashMap<String, Role>,
+    chat_id: ChatId,
+    /// The last group messages were sent to, the API answers about it
+    group_id: ChatId,
+    clock: Option<NaiveDateTime>,
+    /// Id of the last approval request and amount question, taps refer to
+    /// them as {approval} and {draft}
+    last_ids: HashMap<&'static str, String>,
+    /// Text of the last inline query and the ids of its results
+    inline_results: Option<(String, Vec<String>)>,
+    /// The last API token handed out, only replies show it in plain text
+    api_token: Option<String>,
+    failures: Vec<Failure>,
+}
+
+/// Message sent during the simulation whose reply still has to be checked
+struct PendingReply {
+    line: usize,
+    actual: String,
+    expected: Vec<String>,
+}
+
+pub fn run_file(path: &Path) -> Result<(), Vec<Failure>> {
+    match fs::read_to_string(path) {
+        Ok(script) => run(&script),
+        Err(e) => Err(vec![Failure { line: 0, message: format!("unable to read script: {}", e) }]),
+    }
+}
+
+pub fn run(script: &str) -> Result<(), Vec<Failure>> {
+    let conn = match establish_memory_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Err(vec![Failure { line: 0, message: format!("unable to set up database: {}", e) }]),
+    };
+    let mut simulation = Simulation {
+        conn,
+        users: HashMap::new(),
+        languages: HashMap::new(),
+        roles: HashMap::new(),
+        last_ids: HashMap::new(),
+        inline_results: None,
+        api_token: None,
+        chat_id: ChatId(-1),
+        group_id: ChatId(-1),
+        clock: None,
+        failures: vec![],
+    };
+    let mut pending: Option<PendingReply> = None;
+    for (i, raw_line) in script.lines().enumerate() {
+        let line = i + 1;
+        let statement = raw_line.trim();
+        if statement.is_empty() || statement.starts_with('#') {
+            continue;
+        }
+        // expected reply lines belong to the last message
+        if let Some(expected) = statement.strip_prefix('>') {
+            match pending.as_mut() {
+                Some(reply) => reply.expected.push(expected.trim().to_string()),
+                None => simulation.fail(line, "expected reply without a message".to_string()),
+            }
+            continue;
+        }
+        if let Some(reply) = pending.take() {
+            simulation.check_reply(reply);
+        }
+        pending = simulation.execute(line, statement);
+    }
+    if let Some(reply) = pending.take() {
+        simulation.check_reply(reply);
+    }
+    match simulation.failures.is_empty() {
+        true => Ok(()),
+        false => Err(simulation.failures),
+    }
+}
+
+impl Simulation {
+    fn execute(&mut self, line: usize, statement: &str) -> Option<PendingReply> {
+        let words: Vec<&str> = statement.split_whitespace().collect();
+        match words.as_slice() {
+            ["user", name, id] | ["user", name, id, _] => match id.parse::<u64>() {
+                Ok(id) => {
+                    self.users.insert(name.to_string(), UserId(id));
+                    if let Some(language) = words.get(3) {
+                        self.languages.insert(name.to_string(), language.to_string());
+                    }
+                },
+                Err(_) => self.fail(line, format!("invalid user id {:?}", id)),
+            },
+            ["admin", name] => { self.roles.insert(name.to_string(), Role::Admin); },
+            ["owner", name] => { self.roles.insert(name.to_string(), Role::Owner); },
+            ["member", name] => { self.roles.remove(*name); },
+            [name, "taps", ..] => {
+                let data = statement.splitn(3, ' ').nth(2).unwrap_or_default();
+                return self.tap(line, name, data);
+            },
+            [name, "inline", ..] => {
+                let query = statement.splitn(3, ' ').nth(2).unwrap_or_default();
+                return self.inline(line, name, query);
+            },
+            [name, "picks", number] => self.pick(line, name, number),
+            [name, "joins"] => self.join(line, name, true),
+            [name, "leaves"] => return self.leave(line, name),
+            ["chat", id] => match id.parse::<i64>() {
+                Ok(id) => {
+                    self.chat_id = ChatId(id);
+                    if id < 0 {
+                        self.group_id = ChatId(id);
+                    }
+                },
+                Err(_) => self.fail(line, format!("invalid chat id {:?}", id)),
+            },
+            ["balance", name, amount] => self.check_balance(line, name, amount),
+            ["api", path] => return self.api(line, path),
+            ["now", date] => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                Ok(date) => self.clock = date.and_hms_opt(12, 0, 0),
+                Err(_) => self.fail(line, format!("invalid date {:?}", date)),
+            },
+            ["tick"] => return self.tick(line),
+            _ => match statement.split_once(':') {
+                Some((name, text)) => return self.send(line, name.trim(), text.trim()),
+                None => self.fail(line, format!("unknown statement {:?}", statement)),
+            },
+        }
+        None
+    }
+
+    fn send(&mut self, line: usize, name: &str, text: &str) -> Option<PendingReply> {
+        if !text.starts_with('/') {
+            self.join(line, name, false);
+            return None;
+        }
+        let input = self.input(line, name, text)?;
+        let command = match Command::parse(text, "") {
+            Ok(command) => command,
+            Err(e) => {
+                self.fail(line, format!("unable to parse command: {}", e));
+                return None;
+            }
+        };
+        let reply = respond(&self.conn, &command, &input);
+        Some(self.pending(line, reply))
+    }
+
+    fn tap(&mut self, line: usize, name: &str, data: &str) -> Option<PendingReply> {
+        let mut data = data.to_string();
+        for placeholder in ["{approval}", "{draft}"] {
+            data = match (data.contains(placeholder), self.last_ids.get(placeholder)) {
+                (true, Some(id)) => data.replace(placeholder, id),
+                (true, None) => {
+                    self.fail(line, format!("no {} was asked for", placeholder));
+                    return None;
+                },
+                (false, _) => data,
+            };
+        }
+        let mut input = self.input(line, name, &data)?;
+        // as the bot does, buttons acting in another chat get the role there
+        match callback_chat(&self.conn, &input) {
+            Ok(chat_id) if chat_id != input.chat_id => input.role = self.roles.get(name).copied().unwrap_or_default(),
+            Ok(_) => (),
+            Err(e) => self.fail(line, format!("looking up the chat failed: {}", e)),
+        }
+        let reply = respond_callback(&self.conn, &input);
+        Some(self.pending(line, reply))
+    }
+
+    /// Inline results for every tracked chat of the user, titled by their id
+    fn inline(&mut self, line: usize, name: &str, query: &str) -> Option<PendingReply> {
+        let mut input = self.input(line, name, &format!("/loan {}", query))?;
+        input.chat_id = ChatId(input.sender.0 as i64);
+        let role = self.roles.get(name).copied().unwrap_or_default();
+        let loans = tracked_chats(&self.conn, &input.sender).and_then(|chat_ids| {
+            let chats: Vec<(ChatId, String, Role)> = chat_ids.into_iter().map(|id| (id, id.to_string(), role)).collect();
+            inline_loan(&self.conn, &input, &chats)
+        });
+        match loans {
+            Ok(loans) => {
+                let ids = loans.iter().map(|loan| loan.id.clone()).collect();
+                self.inline_results = Some((input.text, ids));
+                let actual = loans.into_iter().map(|loan| format!("{}\n{}", loan.title, loan.text)).collect::<Vec<String>>().join("\n");
+                Some(PendingReply { line, actual, expected: vec![] })
+            },
+            Err(e) => {
+                self.fail(line, format!("inline query failed: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Telegram reports the result the user picked, its draft is {draft}
+    fn pick(&mut self, line: usize, name: &str, number: &str) {
+        let (text, ids) = match self.inline_results.clone() {
+            Some(results) => results,
+            None => return self.fail(line, "no inline query was made".to_string()),
+        };
+        let result_id = match number.parse::<usize>().ok().and_then(|n| ids.get(n.wrapping_sub(1))) {
+            Some(id) => id.clone(),
+            None => return self.fail(line, format!("no result {:?}", number)),
+        };
+        let mut input = match self.input(line, name, &text) {
+            Some(input) => input,
+            None => return,
+        };
+        input.chat_id = ChatId(input.sender.0 as i64);
+        match pick_inline_loan(&self.conn, &input, &result_id) {
+            Ok(draft) => { self.last_ids.insert("{draft}", draft.id); },
+            Err(e) => self.fail(line, format!("picking failed: {}", e)),
+        }
+    }
+
+    /// Joining the group and writing in it both register the user, only
+    /// joining brings back somebody who left
+    fn join(&mut self, line: usize, name: &str, joined: bool) {
+        let user_id = match self.users.get(name) {
+            Some(id) => *id,
+            None => return self.fail(line, format!("unknown user {:?}", name)),
+        };
+        if let Err(e) = register_member(&self.conn, &self.chat_id, &user_id, Some(name), joined) {
+            self.fail(line, format!("registering failed: {}", e));
+        }
+    }
+
+    fn leave(&mut self, line: usize, name: &str) -> Option<PendingReply> {
+        let user_id = match self.users.get(name) {
+            Some(id) => *id,
+            None => {
+                self.fail(line, format!("unknown user {:?}", name));
+                return None;
+            }
+        };
+        match member_left(&self.conn, &self.chat_id, &user_id) {
+            Ok(post) => Some(PendingReply { line, actual: post.unwrap_or_default(), expected: vec![] }),
+            Err(e) => {
+                self.fail(line, format!("leaving failed: {}", e));
+                None
+            }
+        }
+    }
+
+    fn api(&mut self, line: usize, path: &str) -> Option<PendingReply> {
+        let token = self.api_token.clone();
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path, None),
+        };
+        let (status, body) = api::answer(&self.conn, token.as_deref(), path, query);
+        Some(PendingReply { line, actual: format!("{} {}", status.as_u16(), body), expected: vec![] })
+    }
+
+    fn input(&mut self, line: usize, name: &str, text: &str) -> Option<CommandInput> {
+        let sender = match self.users.get(name) {
+            Some(id) => *id,
+            None => {
+                self.fail(line, format!("unknown user {:?}", name));
+                return None;
+            }
+        };
+        Some(CommandInput {
+            sender,
+            sender_username: Some(name.to_string()),
+            chat_id: self.chat_id,
+            text: text.to_string(),
+            mentions: extract_mentions(text),
+            sent: self.now(),
+            role: match self.chat_id.0 == sender.0 as i64 {
+                true => Role::Owner,
+                false => self.roles.get(name).copied().unwrap_or_default(),
+            },
+            language_code: self.languages.get(name).cloned(),
+        })
+    }
+
+    /// Remembers the approval or amount a reply asks for, so taps can
+    /// refer to it
+    fn pending(&mut self, line: usize, reply: Reply) -> PendingReply {
+        let posted = match &reply {
+            Reply::Elsewhere { reply, .. } => reply,
+            reply => reply,
+        };
+        if let Reply::Menu { buttons, .. } = posted {
+            for button in buttons.iter().flatten() {
+                let asked = match button.data.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                    ["approve", id] => Some(("{approval}", id.to_string())),
+                    ["amount", id, _] => Some(("{draft}", id.to_string())),
+                    _ => None,
+                };
+                if let Some((placeholder, id)) = asked {
+                    self.last_ids.insert(placeholder, id);
+                }
+            }
+        }
+        let actual = match &reply {
+            Reply::Direct { text, notice, .. } => format!("{}\n{}", notice, text),
+            reply => reply.text().to_string(),
+        };
+        // the word of the reply that is the token the group has now
+        if let Ok(Some(stored)) = get_api_token(&self.conn, &self.group_id) {
+            if let Some(token) = actual.split_whitespace().find(|word| hash_token(word) == stored.token_hash) {
+                self.api_token = Some(token.to_string());
+            }
+        }
+        PendingReply { line, actual, expected: vec![] }
+    }
+
+    /// Everything the scheduler would post, one message after another
+    fn tick(&mut self, line: usize) -> Option<PendingReply> {
+        match run_scheduled_jobs(&self.conn, self.now()) {
+            Ok(posts) => {
+                let actual = posts.into_iter().map(|(_, text)| text).collect::<Vec<String>>().join("\n");
+                Some(PendingReply { line, actual, expected: vec![] })
+            },
+            Err(e) => {
+                self.fail(line, format!("scheduler failed: {}", e));
+                None
+            }
+        }
+    }
+
+    fn now(&self) -> NaiveDateTime {
+        self.clock.unwrap_or_else(|| Utc::now().naive_utc())
+    }
+
+    fn check_reply(&mut self, reply: PendingReply) {
+        if reply.expected.is_empty() {
+            return;
+        }
+        let today = self.now().format("%d.%m.%Y").to_string();
+        let token = self.api_token.clone().unwrap_or_default();
+        let expected = reply.expected.join("\n").replace("{today}", &today).replace("{token}", &token);
+        if reply.actual.trim() != expected {
+            self.fail(reply.line, format!("expected reply {:?}, got {:?}", expected, reply.actual.trim()));
+        }
+    }
+
+    fn check_balance(&mut self, line: usize, name: &str, amount: &str) {
+        let expected = match amount.parse::<f64>() {
+            Ok(a) => (a * 100.).round() as i64,
+            Err(_) => return self.fail(line, format!("invalid amount {:?}", amount)),
+        };
+        let user_id = match self.users.get(name) {
+            Some(id) => *id,
+            None => return self.fail(line, format!("unknown user {:?}", name)),
+        };
+        match self.net_balance(user_id) {
+            Ok(actual) if actual == expected => (),
+            Ok(actual) => self.fail(line, format!(
+                "expected balance of {} to be {:.2}, got {:.2}",
+                name,
+                expected as f64 / 100.,
+                actual as f64 / 100.
+            )),
+            Err(e) => self.fail(line, format!("unable to read balance: {}", e)),
+        }
+    }
+
+    /// What the user is owed minus what the user owes, in cents (in the
+    /// active event of the chat, if there is one)
+    fn net_balance(&self, user_id: UserId) -> Result<i64, TrackerError> {
+        let users = get_chat_users(&self.conn, &self.chat_id)?;
+        let event = get_active_event(&self.conn, &self.chat_id)?;
+        let ledgers = get_group_ledgers(&self.conn, &users, event.as_ref().map(|e| e.id.as_str()))?;
+        let graph = Graph::from(users, ledgers);
+        Ok(graph.net_balances().get(&user_id).copied().unwrap_or(0))
+    }
+
+    fn fail(&mut self, line: usize, message: String) {
+        self.failures.push(Failure { line, message });
+    }
+}
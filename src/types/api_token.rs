@@ -0,0 +1,67 @@
+use chrono::NaiveDateTime;
+use sha2::{Digest, Sha256};
+use teloxide::types::ChatId;
+use uuid::Uuid;
+use super::schema::api_tokens;
+
+/// Lets whoever holds it read the debt of one chat over the HTTP API,
+/// issued by an admin with /apitoken. Only the hash of the token is kept.
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    /// SHA-256, hex encoded
+    pub token_hash: String,
+    pub chat_id: ChatId,
+    pub issued: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable)]
+#[table_name = "api_tokens"]
+#[primary_key(chat_id)]
+pub struct SqliteApiToken {
+    pub chat_id: i64,
+    pub token_hash: String,
+    pub issued: NaiveDateTime,
+}
+
+/// Token being issued, the only time it is known in plain text
+pub struct NewApiToken {
+    pub chat_id: ChatId,
+    pub token: String,
+    pub issued: NaiveDateTime,
+}
+
+impl NewApiToken {
+    pub fn new(chat_id: ChatId, issued: NaiveDateTime) -> Self {
+        Self {
+            chat_id,
+            // random, it is the only thing the API asks for
+            token: Uuid::new_v4().simple().to_string(),
+            issued,
+        }
+    }
+}
+
+/// What is stored of a token and compared with the ones presented
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+impl From<SqliteApiToken> for ApiToken {
+    fn from(token: SqliteApiToken) -> Self {
+        Self {
+            chat_id: ChatId(token.chat_id),
+            token_hash: token.token_hash,
+            issued: token.issued,
+        }
+    }
+}
+
+impl From<&NewApiToken> for SqliteApiToken {
+    fn from(token: &NewApiToken) -> Self {
+        Self {
+            chat_id: token.chat_id.0,
+            token_hash: hash_token(&token.token),
+            issued: token.issued,
+        }
+    }
+}
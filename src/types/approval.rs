@@ -0,0 +1,60 @@
+use chrono::NaiveDateTime;
+use teloxide::types::{ChatId, UserId};
+use uuid::Uuid;
+use super::error::TrackerError;
+use super::schema::approvals;
+use super::user::user_id_from_sqlite;
+
+/// Loan or payment that waits for the counterparty to approve it
+#[derive(Debug, Clone)]
+pub struct Approval {
+    pub id: String,
+    pub chat_id: ChatId,
+    pub sender: UserId,
+    /// The command as it was sent ("/loan 20 @ana pizza")
+    pub text: String,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable)]
+#[table_name = "approvals"]
+pub struct SqliteApproval {
+    pub id: String,
+    pub chat_id: i64,
+    pub sender: i64,
+    pub text: String,
+    pub created: NaiveDateTime,
+}
+
+pub struct NewApproval {
+    pub chat_id: ChatId,
+    pub sender: UserId,
+    pub text: String,
+    pub created: NaiveDateTime,
+}
+
+impl TryFrom<SqliteApproval> for Approval {
+    type Error = TrackerError;
+
+    fn try_from(approval: SqliteApproval) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sender: user_id_from_sqlite(approval.sender)?,
+            chat_id: ChatId(approval.chat_id),
+            id: approval.id,
+            text: approval.text,
+            created: approval.created,
+        })
+    }
+}
+
+impl From<NewApproval> for SqliteApproval {
+    fn from(approval: NewApproval) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            chat_id: approval.chat_id.0,
+            sender: approval.sender.0 as i64,
+            text: approval.text,
+            created: approval.created,
+        }
+    }
+}
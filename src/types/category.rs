@@ -0,0 +1,81 @@
+use teloxide::types::ChatId;
+use uuid::Uuid;
+use super::schema::{categories, category_aliases, transaction_categories};
+
+/// Spending category of a chat, loans are tagged with it through hashtags
+#[derive(Debug, Clone, PartialEq)]
+pub struct Category {
+    pub id: String,
+    pub chat_id: ChatId,
+    pub name: String,
+}
+
+/// Another hashtag that means the same category (#hrana -> #food)
+#[derive(Debug, Clone)]
+pub struct CategoryAlias {
+    pub id: String,
+    pub category_id: String,
+    pub chat_id: ChatId,
+    pub alias: String,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable)]
+#[table_name = "categories"]
+pub struct SqliteCategory {
+    pub id: String,
+    pub chat_id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable)]
+#[table_name = "category_aliases"]
+pub struct SqliteCategoryAlias {
+    pub id: String,
+    pub category_id: String,
+    pub chat_id: i64,
+    pub alias: String,
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "transaction_categories"]
+pub struct SqliteTransactionCategory {
+    pub transaction_id: String,
+    pub category_id: String,
+    pub position: i32,
+}
+
+pub struct NewCategory {
+    pub chat_id: ChatId,
+    pub name: String,
+}
+
+impl From<SqliteCategory> for Category {
+    fn from(category: SqliteCategory) -> Self {
+        Self {
+            id: category.id,
+            chat_id: ChatId(category.chat_id),
+            name: category.name,
+        }
+    }
+}
+
+impl From<NewCategory> for SqliteCategory {
+    fn from(category: NewCategory) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            chat_id: category.chat_id.0,
+            name: category.name,
+        }
+    }
+}
+
+impl From<SqliteCategoryAlias> for CategoryAlias {
+    fn from(alias: SqliteCategoryAlias) -> Self {
+        Self {
+            id: alias.id,
+            category_id: alias.category_id,
+            chat_id: ChatId(alias.chat_id),
+            alias: alias.alias,
+        }
+    }
+}
@@ -1,36 +1,53 @@
 use teloxide::types::UserId;
 use teloxide::types::ChatId;
 use uuid::Uuid;
+use super::error::TrackerError;
+use super::role::Role;
 use super::schema::chats;
-use super::user::SqliteUser;
+use super::user::user_id_from_sqlite;
 
 #[derive(Debug)]
 pub struct Chat {
     pub id: String,
     pub user_id: UserId,
     pub chat_id: ChatId,
+    pub role: Role,
+    /// The role was given with /role rather than taken over from Telegram
+    pub role_assigned: bool,
 }
 
 #[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
 #[table_name = "chats"]
 pub struct SqliteChat {
     pub id: String,
-    pub user_id: String,
-    pub chat_id: String
+    pub user_id: i64,
+    pub chat_id: i64,
+    pub role: String,
+    pub role_assigned: bool,
+    pub has_left: bool,
 }
 
 pub struct NewChat {
     pub user_id: UserId,
-    pub chat_id: ChatId
+    pub chat_id: ChatId,
+    pub role: Role,
 }
 
-impl From<SqliteChat> for Chat {
-    fn from(chat: SqliteChat) -> Self {
-        Self { 
+impl TryFrom<SqliteChat> for Chat {
+    type Error = TrackerError;
+
+    fn try_from(chat: SqliteChat) -> Result<Self, Self::Error> {
+        let role = match Role::parse(&chat.role) {
+            Some(role) => role,
+            None => return Err(TrackerError::Consistency(format!("invalid role {:?} in chat {}", chat.role, chat.chat_id))),
+        };
+        Ok(Self { 
             id: chat.id, 
-            user_id: serde_json::from_str(&chat.user_id).unwrap(),
-            chat_id: serde_json::from_str(&chat.chat_id).unwrap(),
-        }
+            user_id: user_id_from_sqlite(chat.user_id)?,
+            chat_id: ChatId(chat.chat_id),
+            role,
+            role_assigned: chat.role_assigned,
+        })
     }
 }
 
@@ -38,8 +55,12 @@ impl From<Chat> for SqliteChat {
     fn from(chat: Chat) -> Self {
         Self { 
             id: chat.id, 
-            user_id: chat.user_id.to_string(),
-            chat_id: chat.chat_id.to_string(),
+            user_id: chat.user_id.0 as i64,
+            chat_id: chat.chat_id.0,
+            role: chat.role.to_string(),
+            role_assigned: chat.role_assigned,
+            // rows of people who left are never read as a Chat
+            has_left: false,
         }
     }
 }
@@ -48,8 +69,11 @@ impl From<NewChat> for SqliteChat {
     fn from(chat: NewChat) -> Self {
         Self { 
             id: Uuid::new_v4().to_string(), 
-            user_id: chat.user_id.to_string(),
-            chat_id: chat.chat_id.to_string(),
+            user_id: chat.user_id.0 as i64,
+            chat_id: chat.chat_id.0,
+            role: chat.role.to_string(),
+            role_assigned: false,
+            has_left: false,
         }
     }
 }
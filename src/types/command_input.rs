@@ -0,0 +1,139 @@
+use chrono::{NaiveDateTime, Utc};
+use teloxide::types::{CallbackQuery, Chat, ChatId, ChosenInlineResult, InlineQuery, Message, MessageEntityKind, UserId};
+use super::error::{TrackerError, ValidationError};
+use super::role::Role;
+
+/// Everything the tracker needs to know about an incoming command, without
+/// any Telegram specifics. Built from a `Message` by the bot and by hand by
+/// the simulation harness.
+#[derive(Debug, Clone)]
+pub struct CommandInput {
+    pub sender: UserId,
+    pub sender_username: Option<String>,
+    pub chat_id: ChatId,
+    pub text: String,
+    /// usernames (without the leading '@') mentioned in the text
+    pub mentions: Vec<String>,
+    /// When the message was sent (UTC)
+    pub sent: NaiveDateTime,
+    /// Role Telegram gives the sender in the chat. Private chats are owned by
+    /// their user, in groups the bot has to ask Telegram and fill it in. The
+    /// tracker may know a higher role (see `tracker::member_role`).
+    pub role: Role,
+    /// Language of the sender's Telegram app ("sl", "en-GB"), replies fall
+    /// back to it when neither the sender nor the chat picked one
+    pub language_code: Option<String>,
+}
+
+impl TryFrom<&Message> for CommandInput {
+    type Error = TrackerError;
+
+    fn try_from(message: &Message) -> Result<Self, Self::Error> {
+        let user = match message.from() {
+            Some(user) => user,
+            None => return Err(ValidationError::UnknownSender.into()),
+        };
+        let text = match message.text() {
+            Some(text) => text.to_string(),
+            None => return Err(ValidationError::NotText.into()),
+        };
+        // entity offsets are in UTF-16 code units, let teloxide do the math
+        let mentions = message.parse_entities()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entity| *entity.kind() == MessageEntityKind::Mention)
+            .map(|entity| entity.text().trim_start_matches('@').to_string())
+            .collect();
+        Ok(Self {
+            sender: user.id,
+            sender_username: user.username.clone(),
+            chat_id: message.chat.id,
+            text,
+            mentions,
+            sent: message.date.naive_utc(),
+            role: telegram_role(&message.chat),
+            language_code: user.language_code.clone(),
+        })
+    }
+}
+
+/// A tapped button, its data takes the place of the text
+impl TryFrom<&CallbackQuery> for CommandInput {
+    type Error = TrackerError;
+
+    fn try_from(query: &CallbackQuery) -> Result<Self, Self::Error> {
+        let data = match query.data.as_ref() {
+            Some(data) => data,
+            None => return Err(ValidationError::NotText.into()),
+        };
+        // buttons of messages sent in inline mode belong to no chat, they
+        // are answered as if in the private chat with the user
+        let (chat_id, role) = match query.message.as_ref() {
+            Some(message) => (message.chat.id, telegram_role(&message.chat)),
+            None => (ChatId(query.from.id.0 as i64), Role::Owner),
+        };
+        Ok(Self {
+            sender: query.from.id,
+            sender_username: query.from.username.clone(),
+            chat_id,
+            text: data.clone(),
+            mentions: extract_mentions(data),
+            sent: Utc::now().naive_utc(),
+            role,
+            language_code: query.from.language_code.clone(),
+        })
+    }
+}
+
+/// Inline queries are loans ("@DebtBot 12.50 @ana pizza"), typed from the
+/// private chat with the bot wherever the user is
+impl From<&InlineQuery> for CommandInput {
+    fn from(query: &InlineQuery) -> Self {
+        let text = format!("/loan {}", query.query.trim());
+        Self {
+            sender: query.from.id,
+            sender_username: query.from.username.clone(),
+            chat_id: ChatId(query.from.id.0 as i64),
+            mentions: extract_mentions(&text),
+            text,
+            sent: Utc::now().naive_utc(),
+            role: Role::Owner,
+            language_code: query.from.language_code.clone(),
+        }
+    }
+}
+
+/// The inline result the user picked, with the loan they typed for it
+impl From<&ChosenInlineResult> for CommandInput {
+    fn from(result: &ChosenInlineResult) -> Self {
+        let text = format!("/loan {}", result.query.trim());
+        Self {
+            sender: result.from.id,
+            sender_username: result.from.username.clone(),
+            chat_id: ChatId(result.from.id.0 as i64),
+            mentions: extract_mentions(&text),
+            text,
+            sent: Utc::now().naive_utc(),
+            role: Role::Owner,
+            language_code: result.from.language_code.clone(),
+        }
+    }
+}
+
+/// Role known without asking Telegram
+fn telegram_role(chat: &Chat) -> Role {
+    match chat.is_private() {
+        true => Role::Owner,
+        false => Role::Member,
+    }
+}
+
+/// Stand-in for Telegram's mention entities: every "@name" in the text. Used
+/// for messages the tracker replays itself.
+pub fn extract_mentions(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|name| name.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect::<String>())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
@@ -0,0 +1,90 @@
+use chrono::{Duration, NaiveDateTime, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+use teloxide::types::ChatId;
+use super::error::TrackerError;
+use super::recurring::Frequency;
+use super::schema::digests;
+
+/// Periodic balance summary of a chat, posted at a local time of day
+#[derive(Debug, Clone)]
+pub struct Digest {
+    pub chat_id: ChatId,
+    pub frequency: Frequency,
+    pub time: NaiveTime,
+    pub timezone: Tz,
+    /// UTC
+    pub next_run: NaiveDateTime,
+    pub last_sent: Option<NaiveDateTime>,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
+#[table_name = "digests"]
+#[primary_key(chat_id)]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct SqliteDigest {
+    pub chat_id: i64,
+    pub frequency: String,
+    pub day: i32,
+    pub minute: i32,
+    pub timezone: String,
+    pub next_run: NaiveDateTime,
+    pub last_sent: Option<NaiveDateTime>,
+    pub created: NaiveDateTime,
+}
+
+impl Digest {
+    /// First run (UTC) strictly after the given moment (UTC)
+    pub fn next_after(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let local_now = self.timezone.from_utc_datetime(&now).naive_local();
+        // the day after which the time of day is still ahead
+        let since_midnight = self.time - NaiveTime::MIN;
+        let date = self.frequency.next_after(local_now - since_midnight).date();
+        let local_run = date.and_time(self.time);
+        // clocks skipped that time (summer time), run an hour later
+        let run = self.timezone.from_local_datetime(&local_run).earliest()
+            .or_else(|| self.timezone.from_local_datetime(&(local_run + Duration::hours(1))).earliest());
+        match run {
+            Some(run) => run.naive_utc(),
+            None => local_run,
+        }
+    }
+}
+
+impl TryFrom<SqliteDigest> for Digest {
+    type Error = TrackerError;
+
+    fn try_from(digest: SqliteDigest) -> Result<Self, Self::Error> {
+        let invalid = || TrackerError::Consistency(format!("invalid digest schedule of chat {}", digest.chat_id));
+        let frequency = Frequency::from_sqlite(&digest.frequency, digest.day).ok_or_else(invalid)?;
+        let time = u32::try_from(digest.minute).ok()
+            .and_then(|minute| NaiveTime::from_num_seconds_from_midnight_opt(minute * 60, 0))
+            .ok_or_else(invalid)?;
+        let timezone = digest.timezone.parse::<Tz>().map_err(|_| invalid())?;
+        Ok(Self {
+            chat_id: ChatId(digest.chat_id),
+            frequency,
+            time,
+            timezone,
+            next_run: digest.next_run,
+            last_sent: digest.last_sent,
+            created: digest.created,
+        })
+    }
+}
+
+impl From<Digest> for SqliteDigest {
+    fn from(digest: Digest) -> Self {
+        let (frequency, day) = digest.frequency.to_sqlite();
+        Self {
+            chat_id: digest.chat_id.0,
+            frequency,
+            day,
+            minute: ((digest.time - NaiveTime::MIN).num_minutes()) as i32,
+            timezone: digest.timezone.name().to_string(),
+            next_run: digest.next_run,
+            last_sent: digest.last_sent,
+            created: digest.created,
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::NaiveDateTime;
+use teloxide::types::{ChatId, UserId};
+use uuid::Uuid;
+use super::error::TrackerError;
+use super::schema::drafts;
+use super::user::user_id_from_sqlite;
+
+/// Loan or payment that waits for its sender to say which number is the
+/// amount, or a loan typed in inline mode that waits for its sender to
+/// confirm it
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub id: String,
+    pub chat_id: ChatId,
+    pub sender: UserId,
+    /// The command as it was sent ("/loan 2 pizzas 23,40 @ana")
+    pub text: String,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable)]
+#[table_name = "drafts"]
+pub struct SqliteDraft {
+    pub id: String,
+    pub chat_id: i64,
+    pub sender: i64,
+    pub text: String,
+    pub created: NaiveDateTime,
+}
+
+pub struct NewDraft {
+    /// Id the draft was known by before it was saved (the buttons of a loan
+    /// typed in inline mode carry it), a new one when `None`
+    pub id: Option<String>,
+    pub chat_id: ChatId,
+    pub sender: UserId,
+    pub text: String,
+    pub created: NaiveDateTime,
+}
+
+impl TryFrom<SqliteDraft> for Draft {
+    type Error = TrackerError;
+
+    fn try_from(draft: SqliteDraft) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sender: user_id_from_sqlite(draft.sender)?,
+            chat_id: ChatId(draft.chat_id),
+            id: draft.id,
+            text: draft.text,
+            created: draft.created,
+        })
+    }
+}
+
+impl From<NewDraft> for SqliteDraft {
+    fn from(draft: NewDraft) -> Self {
+        Self {
+            id: draft.id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            chat_id: draft.chat_id.0,
+            sender: draft.sender.0 as i64,
+            text: draft.text,
+            created: draft.created,
+        }
+    }
+}
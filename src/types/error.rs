@@ -0,0 +1,160 @@
+use std::fmt;
+use diesel::{ConnectionError, result::Error as QueryError};
+use diesel_migrations::RunMigrationsError;
+use log::{error, info};
+
+/// Every way a command can fail. User facing text for each variant is
+/// generated by `text_helper::generate_error_response`, the `Display`
+/// implementation is meant for the logs.
+#[derive(Debug)]
+pub enum TrackerError {
+    /// The message could not be turned into a valid command
+    Validation(ValidationError),
+    /// The user (sender or counterparty) is not registered with the tracker
+    NotRegistered,
+    /// The chat does not allow the sender to do that (admin-only mode, ...)
+    Forbidden,
+    /// Only the owner of the chat can do that (make or unmake admins)
+    OwnerOnly,
+    /// The sender's role in the chat is read-only
+    ReadOnly,
+    /// Members only record transactions they take part in
+    NotParty,
+    /// The user still owes or is owed money (leaving a chat, being
+    /// forgotten)
+    OpenBalances,
+    /// The database could not be reached or a query failed
+    Storage(StorageError),
+    /// Stored data does not add up (malformed rows, duplicated users, ...)
+    Consistency(String),
+    /// An image (graph, chart) could not be drawn or encoded
+    Rendering(String),
+}
+
+#[derive(Debug)]
+pub enum ValidationError {
+    UnknownSender,
+    MissingUsername,
+    NotText,
+    InvalidAmount,
+    /// The amount is a calculation that does not work out, `token` is where
+    /// it goes wrong
+    InvalidExpression { expression: String, token: String },
+    /// Several numbers of the message could be the amount
+    AmbiguousAmount,
+    /// The amount is in another currency than the one the chat keeps
+    /// accounts in
+    OtherCurrency { given: String, expected: String },
+    NoRecievers,
+    InvalidTimeRange,
+    InvalidCount,
+    UnknownCategory,
+    InvalidCategory,
+    InvalidSchedule,
+    UnknownRecurring,
+    InvalidDueDate,
+    InvalidReminder,
+    InvalidDigest,
+    InvalidTimezone,
+    InvalidSetting,
+    InvalidLanguage,
+    UnknownApproval,
+    UnknownDraft,
+    InvalidEvent,
+    UnknownEvent,
+    /// The chat already has an open event with that name
+    EventExists,
+    /// A shared expense needs two people and somebody who paid
+    InvalidExpense,
+    /// Somebody mentioned has never used the bot
+    UnregisteredUser { username: String },
+    InvalidRole,
+    /// Somebody mentioned is not a member of the chat
+    NotMember { username: String },
+    /// The owner's role does not change, nobody else becomes the owner
+    OwnerRole,
+    /// The button was meant for somebody else than who tapped it
+    OtherUser,
+    InvalidApiToken,
+    /// The command is about a group, it was sent in a private chat
+    GroupOnly,
+    /// Recurring loans run in the name of who added them, never "by:" others
+    RecurringForOther,
+}
+
+#[derive(Debug)]
+pub enum StorageError {
+    Connection(ConnectionError),
+    Query(QueryError),
+    Migration(RunMigrationsError),
+}
+
+impl TrackerError {
+    /// Logs the error with as much detail as we have. Mistakes made by users
+    /// are expected and only logged as info.
+    pub fn log(&self) {
+        match self {
+            TrackerError::Validation(_)
+            | TrackerError::NotRegistered
+            | TrackerError::Forbidden
+            | TrackerError::OwnerOnly
+            | TrackerError::ReadOnly
+            | TrackerError::NotParty
+            | TrackerError::OpenBalances => info!("Rejected command: {}", self),
+            _ => error!("Command failed: {}", self),
+        }
+    }
+}
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackerError::Validation(e) => write!(f, "validation error: {:?}", e),
+            TrackerError::NotRegistered => write!(f, "user is not registered"),
+            TrackerError::Forbidden => write!(f, "not allowed in this chat"),
+            TrackerError::OwnerOnly => write!(f, "only the owner of the chat can do that"),
+            TrackerError::ReadOnly => write!(f, "read-only in this chat"),
+            TrackerError::NotParty => write!(f, "not taking part in the transaction"),
+            TrackerError::OpenBalances => write!(f, "balances are still open"),
+            TrackerError::Storage(e) => write!(f, "storage error: {}", e),
+            TrackerError::Consistency(e) => write!(f, "consistency error: {}", e),
+            TrackerError::Rendering(e) => write!(f, "rendering error: {}", e),
+        }
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Connection(e) => write!(f, "unable to connect: {}", e),
+            StorageError::Query(e) => write!(f, "query failed: {}", e),
+            StorageError::Migration(e) => write!(f, "migration failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TrackerError {}
+
+impl From<ValidationError> for TrackerError {
+    fn from(e: ValidationError) -> Self {
+        TrackerError::Validation(e)
+    }
+}
+
+impl From<ConnectionError> for TrackerError {
+    fn from(e: ConnectionError) -> Self {
+        TrackerError::Storage(StorageError::Connection(e))
+    }
+}
+
+impl From<QueryError> for TrackerError {
+    fn from(e: QueryError) -> Self {
+        TrackerError::Storage(StorageError::Query(e))
+    }
+}
+
+impl From<RunMigrationsError> for TrackerError {
+    fn from(e: RunMigrationsError) -> Self {
+        TrackerError::Storage(StorageError::Migration(e))
+    }
+}
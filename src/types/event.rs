@@ -0,0 +1,75 @@
+use chrono::NaiveDateTime;
+use teloxide::types::ChatId;
+use uuid::Uuid;
+use super::schema::events;
+
+/// Trip, party or anything else whose debt is kept apart from the everyday
+/// debt of the chat ("/event start Ski2026")
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: String,
+    pub chat_id: ChatId,
+    pub name: String,
+    pub created: NaiveDateTime,
+    /// When it was settled and archived
+    pub closed: Option<NaiveDateTime>,
+    /// New transactions of the chat go to it
+    pub active: bool,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
+#[table_name = "events"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct SqliteEvent {
+    pub id: String,
+    pub chat_id: i64,
+    pub name: String,
+    pub created: NaiveDateTime,
+    pub closed: Option<NaiveDateTime>,
+    pub active: bool,
+}
+
+pub struct NewEvent {
+    pub chat_id: ChatId,
+    pub name: String,
+    pub created: NaiveDateTime,
+}
+
+impl From<SqliteEvent> for Event {
+    fn from(event: SqliteEvent) -> Self {
+        Self {
+            id: event.id,
+            chat_id: ChatId(event.chat_id),
+            name: event.name,
+            created: event.created,
+            closed: event.closed,
+            active: event.active,
+        }
+    }
+}
+
+impl From<Event> for SqliteEvent {
+    fn from(event: Event) -> Self {
+        Self {
+            id: event.id,
+            chat_id: event.chat_id.0,
+            name: event.name,
+            created: event.created,
+            closed: event.closed,
+            active: event.active,
+        }
+    }
+}
+
+impl From<NewEvent> for SqliteEvent {
+    fn from(event: NewEvent) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            chat_id: event.chat_id.0,
+            name: event.name,
+            created: event.created,
+            closed: None,
+            active: true,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::NaiveDateTime;
+use teloxide::types::{ChatId, UserId};
+use uuid::Uuid;
+use super::category::Category;
+use super::schema::{expense_shares, shared_expenses};
+use super::user::user_id_from_sqlite;
+use super::error::TrackerError;
+
+/// Bill paid by one or more people and shared equally by one or more people
+/// ("/expense @ana=60 @luka=40 @maja @jan @eva dinner")
+#[derive(Debug, Clone)]
+pub struct SharedExpense {
+    pub id: String,
+    pub chat_id: ChatId,
+    pub description: String,
+    pub created: NaiveDateTime,
+    /// Everybody involved, in the order they were mentioned
+    pub shares: Vec<ExpenseShare>,
+}
+
+/// Part of a shared expense one person paid and the part that is theirs,
+/// in cents
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpenseShare {
+    pub user_id: UserId,
+    pub paid: i32,
+    pub share: i32,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable)]
+#[table_name = "shared_expenses"]
+pub struct SqliteSharedExpense {
+    pub id: String,
+    pub chat_id: i64,
+    pub description: String,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "expense_shares"]
+pub struct SqliteExpenseShare {
+    pub expense_id: String,
+    pub user_id: i64,
+    pub paid: i32,
+    pub share: i32,
+    pub position: i32,
+}
+
+pub struct NewSharedExpense {
+    pub chat_id: ChatId,
+    pub description: String,
+    pub created: NaiveDateTime,
+    pub shares: Vec<ExpenseShare>,
+    /// Categories of the hashtags, the loans of the expense get them
+    pub categories: Vec<Category>,
+}
+
+impl SharedExpense {
+    /// Everything that was paid, in cents
+    pub fn total(&self) -> i64 {
+        self.shares.iter().map(|s| s.paid as i64).sum()
+    }
+
+    /// Loans (lender, borrower, cents) that even out what everybody paid
+    /// and what their share is. Those who paid too little borrow from those
+    /// who paid too much, in the order they were mentioned.
+    pub fn loans(&self) -> Vec<(UserId, UserId, i32)> {
+        let mut lenders: Vec<(UserId, i32)> = self.shares.iter()
+            .filter(|s| s.paid > s.share)
+            .map(|s| (s.user_id, s.paid - s.share))
+            .collect();
+        let mut loans = vec![];
+        let mut lender = 0;
+        for borrower in self.shares.iter().filter(|s| s.share > s.paid) {
+            let mut missing = borrower.share - borrower.paid;
+            while missing > 0 && lender < lenders.len() {
+                let sum = missing.min(lenders[lender].1);
+                loans.push((lenders[lender].0, borrower.user_id, sum));
+                missing -= sum;
+                lenders[lender].1 -= sum;
+                if lenders[lender].1 == 0 {
+                    lender += 1;
+                }
+            }
+        }
+        loans
+    }
+}
+
+impl SqliteSharedExpense {
+    pub fn into_expense(self, shares: Vec<SqliteExpenseShare>) -> Result<SharedExpense, TrackerError> {
+        let shares = shares.into_iter()
+            .map(|s| Ok(ExpenseShare { user_id: user_id_from_sqlite(s.user_id)?, paid: s.paid, share: s.share }))
+            .collect::<Result<Vec<ExpenseShare>, TrackerError>>()?;
+        Ok(SharedExpense {
+            id: self.id,
+            chat_id: ChatId(self.chat_id),
+            description: self.description,
+            created: self.created,
+            shares,
+        })
+    }
+}
+
+impl From<&NewSharedExpense> for SqliteSharedExpense {
+    fn from(expense: &NewSharedExpense) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            chat_id: expense.chat_id.0,
+            description: expense.description.clone(),
+            created: expense.created,
+        }
+    }
+}
@@ -1,311 +1,136 @@
 use std::collections::HashMap;
-use std::collections::LinkedList;
-use std::vec;
-use std::cmp;
-use std::error::Error;
 use teloxide::types::UserId;
 
-use crate::helpers::data_handler::ledger_operations::update_ledger;
-
 use super::{ledger::Ledger, user::User};
 
+/// Debt graph of a group: users are the nodes, every ledger with a positive
+/// sum is an edge from the borrower to the person they owe. Purely in memory,
+/// nothing in here touches the database.
+#[derive(Debug, Clone)]
 pub struct Graph {
     pub ledgers: Vec<Ledger>,
     pub users: Vec<User>,
-    nodes: Vec<Node>
 }
 
-#[derive(Debug, Clone)]
-struct Node {
-    pub id: i32,
-    pub user_id: UserId,
-    pub connections: Vec<String>,
-    pub visited: bool
-}
-
-impl Node {
-    pub fn new(user_id: UserId, id: i32) -> Node {
-        Node { id, user_id, connections: vec![], visited: false }
-    }
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    OnPath,
+    Done,
 }
 
 impl Graph {
     pub fn from(users: Vec<User>, ledgers: Vec<Ledger>) -> Self {
-        let mut graph = Graph {
-            users,
-            ledgers,
-            nodes: vec![],
-        };
-        graph.create_nodes();
-        graph
-    }
-
-    fn create_nodes(&mut self) {
-        let mut i = 0;
-        for user in self.users.clone().into_iter() {
-            let mut node = Node::new(user.user_id, i);
-            let ledgers: Vec<String> = self.ledgers.clone().into_iter()
-                .filter(|l| l.sum > 0)
-                .filter(|l| l.borrower == user.user_id)
-                .map(|l| l.id)
-                .collect();
-            // save the node if it has connections to other nodes
-            // connection == non zero sum ledger
-            if !ledgers.is_empty() {
-                node.connections = ledgers;
-                self.nodes.push(node);
-                i += 1;
-            }
-        }
-    }
-
-    pub fn resolve_bidirectional_debt(&mut self) -> Result<(), Box<dyn Error>> {
-        for node in self.nodes.clone().into_iter() {
-            match self.resolve_bidirectional_debt_node(node.clone()) {
-                Ok(_) => continue,
-                Err(e) => return Err(e),
-            }
-        }
-        Ok(())
-    }
-
-    pub fn resolve_cyclic_debt(&mut self) -> Result<(), Box<dyn Error>> {
-        for node in self.nodes.clone().into_iter() {
-            match self.resolve_cyclic_debt_node(node.clone()) {
-                Ok(_) => continue,
-                Err(e) => return Err(e),
-            }
-        }
-        Ok(())
-    }
-    fn resolve_cyclic_debt_node(&mut self, node_to_resolve: Node) -> Result<(), Box<dyn Error>> {
-        let mut result = LinkedList::new();
-        let mut stack = LinkedList::new();
-        let mut visited = HashMap::new();
-        let to_resolve_uid = node_to_resolve.user_id.to_string();
-        let mut found = false;
-        
-        stack.push_front(node_to_resolve);
-        visited.insert(to_resolve_uid.clone(), true);
-        
-        while !stack.is_empty() {
-            found = false;
-            let front = match stack.pop_front() {
-                Some(n) => n,
-                None => break,
-            };
-            let current = match self.find_node(front.id) {
-                Some(n) => n,
-                None => continue,
-            };
-            result.push_front(current.user_id);
-
-            for neighbour_id in current.connections.iter() {
-                let neighbour = match self.find_node_by_user_string(neighbour_id) {
-                    Some(n) => n,
-                    None => continue,
-                };
-                //if loop closed
-                if current.connections.contains(&to_resolve_uid) {
-                    result.push_front(neighbour.user_id);
-                    found = true;
-                    break;
-                }
-                if !visited.contains_key(neighbour_id) {
-                    visited.insert(neighbour_id.to_string(), true);
-                    stack.push_front(neighbour);
-                }
-                if found  {
-                    break;
-                }
-            }
-        }
-        if found && result.len() > 2 {
-            match self.handle_cyclic_debt(result) {
-                Ok(_) => (),
-                Err(e) => return Err(e),
-            }
-            self.reset_nodes();
-        }
-        Ok(())
-    }
-
-    fn resolve_bidirectional_debt_node(&mut self, node_to_resolve: Node) -> Result<(), Box<dyn Error>> {
-        for ledger_id in node_to_resolve.connections.into_iter() {
-            // find ledger (connection) to neighbour
-            let ledger = match self.find_ledger(&ledger_id) {
-                Some(l) => l,
-                None => continue,
-            };
-            // find neighbour node (to be able to reference back to self)
-            let neighbour = match self.find_node_by_user(&ledger.owes) {
-                Some(n) => n,
-                None => continue,
-            };
-            for neighbour_ledger_id in neighbour.connections.into_iter() {
-                // get neighbour ledger (connection) one by one
-                let neighbour_ledger = match self.find_ledger(&neighbour_ledger_id) {
-                    Some(l) => l,
-                    None => continue,
-                };
-                // if the ledger points back to original node, we have a
-                // bidirectional debt
-                if neighbour_ledger.owes == ledger.borrower {
-                    match handle_bidirectional_ledgers(ledger, neighbour_ledger) {
-                        Ok((l1, l2)) => {
-                            self.update_ledger(l1);
-                            self.update_ledger(l2);
-                        },
-                        Err(e) => return Err(e),
+        Graph { users, ledgers }
+    }
+
+    /// Cancels out debt between pairs of people that owe each other
+    pub fn resolve_bidirectional_debt(&mut self) {
+        for i in 0..self.ledgers.len() {
+            for j in (i + 1)..self.ledgers.len() {
+                let (l1, l2) = (&self.ledgers[i], &self.ledgers[j]);
+                if l1.borrower == l2.owes && l2.borrower == l1.owes {
+                    let sum = l1.sum.min(l2.sum);
+                    if sum > 0 {
+                        self.ledgers[i].sum -= sum;
+                        self.ledgers[j].sum -= sum;
                     }
-                    // continue with next neighbour of the node to resolve
-                    break;
                 }
             }
-            self.ledgers.retain(|l| l.sum != 0);
         }
-        Ok(())
     }
 
-    fn update_ledger(&mut self, l: Ledger) {
-        for mut ledger in self.ledgers.iter_mut() {
-            if ledger.id == l.id {
-                ledger.sum = l.sum;
-                break;
+    /// Cancels out debt going around in circles (a -> b -> c -> a) until the
+    /// graph has no cycles left. Every round removes at least one edge.
+    pub fn resolve_cyclic_debt(&mut self) {
+        while let Some(cycle) = self.find_cycle() {
+            let sum = cycle.iter().map(|i| self.ledgers[*i].sum).min().unwrap_or(0);
+            for i in cycle.into_iter() {
+                self.ledgers[i].sum -= sum;
             }
         }
     }
 
-    fn find_node(&mut self, id: i32) -> Option<Node> {
-        for node in self.nodes.clone().into_iter() {
-            if node.id == id {
-                return Some(node)
+    /// Indices of the ledgers forming a cycle of positive debt, if any
+    pub fn find_cycle(&self) -> Option<Vec<usize>> {
+        let mut visits = HashMap::new();
+        let mut path = vec![];
+        for ledger in self.ledgers.iter().filter(|l| l.sum > 0) {
+            if visits.contains_key(&ledger.borrower) {
+                continue;
             }
-        }
-        None
-    }
-    fn find_node_by_user(&mut self, user_id: &UserId) -> Option<Node> {
-        for node in self.nodes.clone().into_iter() {
-            if node.user_id == *user_id {
-                return Some(node)
-            }
-        }
-        None
-    }
-    fn find_node_by_user_string(&mut self, user_id: &String) -> Option<Node> {
-        for node in self.nodes.clone().into_iter() {
-            if node.user_id.to_string().eq(user_id) {
-                return Some(node)
-            }
-        }
-        None
-    }
-    fn find_ledger(&mut self, id: &String) -> Option<Ledger> {
-        for ledger in self.ledgers.clone().into_iter() {
-            if ledger.id.eq(id) {
-                return Some(ledger)
+            if let Some(cycle) = self.find_cycle_from(ledger.borrower, &mut visits, &mut path) {
+                return Some(cycle);
             }
         }
         None
     }
 
-    fn find_ledger_by_users(&mut self, borrower: UserId, owes: UserId) -> Option<Ledger> {
-        for ledger in self.ledgers.clone().into_iter() {
-            if ledger.borrower == borrower && ledger.owes == owes {
-                return Some(ledger)
+    fn find_cycle_from(
+        &self,
+        node: UserId,
+        visits: &mut HashMap<UserId, Visit>,
+        path: &mut Vec<usize>
+    ) -> Option<Vec<usize>> {
+        visits.insert(node, Visit::OnPath);
+        for (i, ledger) in self.ledgers.iter().enumerate() {
+            if ledger.sum <= 0 || ledger.borrower != node {
+                continue;
             }
-        }
-        None
-    }
-
-    fn find_user(self, id: &String) -> Option<User> {
-        for user in self.users.into_iter() {
-            if user.id.eq(id) {
-                return Some(user)
+            match visits.get(&ledger.owes) {
+                // back to a node on the current path -> the loop closed
+                Some(Visit::OnPath) => {
+                    let start = path.iter()
+                        .position(|e| self.ledgers[*e].borrower == ledger.owes)
+                        .unwrap_or(path.len());
+                    let mut cycle = path[start..].to_vec();
+                    cycle.push(i);
+                    return Some(cycle);
+                },
+                Some(Visit::Done) => continue,
+                None => {
+                    path.push(i);
+                    if let Some(cycle) = self.find_cycle_from(ledger.owes, visits, path) {
+                        return Some(cycle);
+                    }
+                    path.pop();
+                }
             }
         }
+        visits.insert(node, Visit::Done);
         None
     }
 
-    fn handle_cyclic_debt(&mut self, mut result: LinkedList<UserId>) -> Result<(), Box<dyn Error>> {
-        let mut current_option = None;
-        let mut next_option = None;
-        let mut sum = std::i32::MAX;
-        while !result.is_empty() {
-            current_option = next_option;
-            next_option = result.pop_front();
-
-            let current = match current_option {
-                Some(uid) => uid,
-                None => continue,
-            };
-            let next = match next_option {
-                Some(uid) => uid,
-                None => continue,
-            };
-            let ledger = match self.find_ledger_by_users(current,next) {
-                Some(l) => l,
-                None => continue,
-            };
-            sum = cmp::min(sum, ledger.sum);
-            
-        }
-        while !result.is_empty() {
-            current_option = next_option;
-            next_option = result.pop_front();
-
-            let current = match current_option {
-                Some(uid) => uid,
-                None => continue,
-            };
-            let next = match next_option {
-                Some(uid) => uid,
-                None => continue,
+    /// Transfers (from, to, cents) that settle everybody's net balance.
+    /// Whoever owes the most pays whoever is owed the most until everyone is
+    /// even, so there are at most n - 1 transfers.
+    pub fn settlement_transfers(&self) -> Vec<(UserId, UserId, i64)> {
+        let mut balances: Vec<(UserId, i64)> = self.net_balances().into_iter()
+            .filter(|(_, balance)| *balance != 0)
+            .collect();
+        let mut transfers = vec![];
+        loop {
+            balances.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+            let (debtor, creditor) = match (balances.first(), balances.last()) {
+                (Some(debtor), Some(creditor)) if debtor.1 < 0 && creditor.1 > 0 => (*debtor, *creditor),
+                _ => break,
             };
-            let ledger = match self.find_ledger_by_users(current,next) {
-                Some(l) => l,
-                None => continue,
-            };
-            let updated = match reduce_ledger(ledger, sum) {
-                Ok(l) => l,
-                Err(e) => return Err(e),
-            };
-            self.update_ledger(updated);    
+            let amount = (-debtor.1).min(creditor.1);
+            transfers.push((debtor.0, creditor.0, amount));
+            let last = balances.len() - 1;
+            balances[0].1 += amount;
+            balances[last].1 -= amount;
+            balances.retain(|(_, balance)| *balance != 0);
         }
-        
-        Ok(())
+        transfers
     }
 
-    pub fn reset_nodes(&mut self) {
-        self.ledgers.retain(|l| l.sum != 0);
-        self.nodes = vec![];
-        self.create_nodes();
+    /// What each person is owed minus what they owe, in cents
+    pub fn net_balances(&self) -> HashMap<UserId, i64> {
+        let mut balances = HashMap::new();
+        for ledger in self.ledgers.iter() {
+            *balances.entry(ledger.owes).or_insert(0) += ledger.sum as i64;
+            *balances.entry(ledger.borrower).or_insert(0) -= ledger.sum as i64;
+        }
+        balances
     }
-    
-}
-
-
-fn handle_bidirectional_ledgers(l1: Ledger, l2: Ledger) -> Result<(Ledger, Ledger), Box<dyn Error>> {
-    // should always be, just double check
-    if l1.borrower == l2.owes && l2.borrower == l1.owes {
-        let sum = cmp::min(l1.sum, l2.sum);
-        let l1_updated = match reduce_ledger(l1, sum) {
-            Ok(l) => l,
-            Err(e) => return Err(e),
-        };
-        let l2_updated = match reduce_ledger(l2, sum) {
-            Ok(l) => l,
-            Err(e) => return Err(e),
-        };
-        return Ok((l1_updated, l2_updated))
-    }   
-    Err("ledgers not bidirectioal".into())
 }
-
-fn reduce_ledger(mut l: Ledger, sum: i32) -> Result<Ledger, Box<dyn Error>> {
-    l.sum -= sum;
-    match update_ledger(l) {
-        Ok(l) => Ok(l),
-        Err(e) => Err(e.into()),
-    }
-}
\ No newline at end of file
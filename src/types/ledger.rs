@@ -1,6 +1,8 @@
 use teloxide::types::UserId;
 use uuid::Uuid;
+use super::error::TrackerError;
 use super::schema::ledgers;
+use super::user::user_id_from_sqlite;
 
 #[derive(Debug, Clone)]
 pub struct Ledger {
@@ -8,32 +10,40 @@ pub struct Ledger {
     pub borrower: UserId,
     pub owes: UserId,
     pub sum: i32,
+    /// Event the debt belongs to, `None` for everyday debt
+    pub event_id: Option<String>,
 }
 
 #[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
 #[table_name = "ledgers"]
 pub struct SqliteLedger {
     pub id: String,
-    pub borrower: String,
-    pub owes: String,
+    pub borrower: i64,
+    pub owes: i64,
     pub sum: i32,
+    pub event_id: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct NewLedger {
-    pub borrower: String,
-    pub owes: String,
+    pub borrower: UserId,
+    pub owes: UserId,
     pub sum: i32,
+    /// Event the debt belongs to, `None` for everyday debt
+    pub event_id: Option<String>,
 }
 
-impl From<SqliteLedger> for Ledger {
-    fn from(ledger: SqliteLedger) -> Self {
-        Self { 
+impl TryFrom<SqliteLedger> for Ledger {
+    type Error = TrackerError;
+
+    fn try_from(ledger: SqliteLedger) -> Result<Self, Self::Error> {
+        Ok(Self { 
             id: ledger.id,
-            borrower: serde_json::from_str(&ledger.borrower).unwrap(), 
-            owes: serde_json::from_str(&ledger.owes).unwrap(), 
-            sum: ledger.sum 
-        }
+            borrower: user_id_from_sqlite(ledger.borrower)?, 
+            owes: user_id_from_sqlite(ledger.owes)?, 
+            sum: ledger.sum,
+            event_id: ledger.event_id,
+        })
     }
 }
 
@@ -41,10 +51,11 @@ impl From<Ledger> for SqliteLedger {
     fn from(ledger: Ledger) -> Self {
         Self { 
             id: ledger.id,
-            borrower: serde_json::to_string(&ledger.borrower).unwrap(),
-            owes: serde_json::to_string(&ledger.owes).unwrap(),
-            sum: ledger.sum 
-        }   
+            borrower: ledger.borrower.0 as i64,
+            owes: ledger.owes.0 as i64,
+            sum: ledger.sum,
+            event_id: ledger.event_id,
+        }
     }
 }
 
@@ -52,9 +63,10 @@ impl From<NewLedger> for SqliteLedger {
     fn from(ledger: NewLedger) -> Self {
         Self { 
             id: Uuid::new_v4().to_string(),
-            borrower: ledger.borrower,
-            owes: ledger.owes,
-            sum: ledger.sum 
-        }   
+            borrower: ledger.borrower.0 as i64,
+            owes: ledger.owes.0 as i64,
+            sum: ledger.sum,
+            event_id: ledger.event_id,
+        }
     }
 }
\ No newline at end of file
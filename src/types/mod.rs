@@ -4,4 +4,18 @@ pub mod transaction_type;
 pub mod schema;
 pub mod user;
 pub mod chat;
-pub mod graph;
\ No newline at end of file
+pub mod graph;
+pub mod error;
+pub mod command_input;
+pub mod stats;
+pub mod category;
+pub mod recurring;
+pub mod reminder;
+pub mod digest;
+pub mod settings;
+pub mod approval;
+pub mod draft;
+pub mod event;
+pub mod expense;
+pub mod role;
+pub mod api_token;
@@ -0,0 +1,228 @@
+use std::fmt;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use teloxide::types::{ChatId, UserId};
+use uuid::Uuid;
+use super::error::TrackerError;
+use super::schema::recurring;
+use super::user::user_id_from_sqlite;
+
+/// When a recurring loan is due. Runs happen at midnight of the day in the
+/// timezone of the chat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly(Weekday),
+    /// Day of the month, shorter months run on their last day instead
+    Monthly(u32),
+}
+
+/// Loan that is repeated on a schedule ("/recurring add monthly 1st 45 @ana")
+#[derive(Debug, Clone)]
+pub struct Recurring {
+    pub id: String,
+    pub chat_id: ChatId,
+    pub creator: UserId,
+    pub frequency: Frequency,
+    /// Loan message without the command ("45.00 @ana @luka #internet")
+    pub loan: String,
+    pub paused: bool,
+    pub next_run: NaiveDateTime,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
+#[table_name = "recurring"]
+pub struct SqliteRecurring {
+    pub id: String,
+    pub chat_id: i64,
+    pub creator: i64,
+    pub frequency: String,
+    pub day: i32,
+    pub loan: String,
+    pub paused: bool,
+    pub next_run: NaiveDateTime,
+    pub created: NaiveDateTime,
+}
+
+pub struct NewRecurring {
+    pub chat_id: ChatId,
+    pub creator: UserId,
+    pub frequency: Frequency,
+    pub loan: String,
+    /// Of the chat, the first run is at its midnight
+    pub timezone: Tz,
+    pub created: NaiveDateTime,
+}
+
+impl Frequency {
+    /// Reads the schedule at the start of the words ("monthly 1st", "weekly
+    /// mon", "daily") and returns it with the number of words it took
+    pub fn parse(words: &[&str]) -> Option<(Frequency, usize)> {
+        match words {
+            [frequency, ..] if frequency.eq_ignore_ascii_case("daily") => Some((Frequency::Daily, 1)),
+            [frequency, day, ..] if frequency.eq_ignore_ascii_case("weekly") => {
+                day.parse::<Weekday>().ok().map(|day| (Frequency::Weekly(day), 2))
+            },
+            [frequency, day, ..] if frequency.eq_ignore_ascii_case("monthly") => {
+                let digits = day.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+                match digits.parse::<u32>() {
+                    Ok(day) if (1..=31).contains(&day) => Some((Frequency::Monthly(day), 2)),
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+
+    /// First run strictly after the given moment
+    pub fn next_after(&self, after: NaiveDateTime) -> NaiveDateTime {
+        let tomorrow = after.date() + Duration::days(1);
+        let date = match *self {
+            Frequency::Daily => tomorrow,
+            Frequency::Weekly(weekday) => {
+                let days_ahead = (7 + weekday.num_days_from_monday() - tomorrow.weekday().num_days_from_monday()) % 7;
+                tomorrow + Duration::days(days_ahead as i64)
+            },
+            Frequency::Monthly(day) => {
+                let this_month = day_of_month(after.year(), after.month(), day);
+                if this_month > after.date() {
+                    this_month
+                } else if after.month() == 12 {
+                    day_of_month(after.year() + 1, 1, day)
+                } else {
+                    day_of_month(after.year(), after.month() + 1, day)
+                }
+            },
+        };
+        date.and_hms_opt(0, 0, 0).unwrap_or(after)
+    }
+
+    /// First run (UTC) strictly after the given moment (UTC), at midnight in
+    /// the given timezone
+    pub fn next_run_after(&self, after: NaiveDateTime, timezone: Tz) -> NaiveDateTime {
+        let local_run = self.next_after(timezone.from_utc_datetime(&after).naive_local());
+        // clocks skipped midnight (summer time), run an hour later
+        let run = timezone.from_local_datetime(&local_run).earliest()
+            .or_else(|| timezone.from_local_datetime(&(local_run + Duration::hours(1))).earliest());
+        match run {
+            Some(run) => run.naive_utc(),
+            None => local_run,
+        }
+    }
+
+    pub(crate) fn to_sqlite(self) -> (String, i32) {
+        match self {
+            Frequency::Daily => ("daily".to_string(), 0),
+            Frequency::Weekly(day) => ("weekly".to_string(), day.number_from_monday() as i32),
+            Frequency::Monthly(day) => ("monthly".to_string(), day as i32),
+        }
+    }
+
+    pub(crate) fn from_sqlite(frequency: &str, day: i32) -> Option<Frequency> {
+        match (frequency, day) {
+            ("daily", _) => Some(Frequency::Daily),
+            ("weekly", 1..=7) => Weekday::try_from(day as u8 - 1).ok().map(Frequency::Weekly),
+            ("monthly", 1..=31) => Some(Frequency::Monthly(day as u32)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frequency::Daily => write!(f, "every day"),
+            Frequency::Weekly(day) => write!(f, "every {}", weekday_name(*day)),
+            Frequency::Monthly(day) => {
+                let suffix = match (day % 10, day % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+                write!(f, "monthly on the {}{}", day, suffix)
+            },
+        }
+    }
+}
+
+/// The given day of the month, or the last day of shorter months
+fn day_of_month(year: i32, month: u32, day: u32) -> NaiveDate {
+    (1..=day).rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+        .unwrap_or(NaiveDate::MIN)
+}
+
+fn weekday_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
+
+impl TryFrom<SqliteRecurring> for Recurring {
+    type Error = TrackerError;
+
+    fn try_from(recurring: SqliteRecurring) -> Result<Self, Self::Error> {
+        let frequency = match Frequency::from_sqlite(&recurring.frequency, recurring.day) {
+            Some(f) => f,
+            None => return Err(TrackerError::Consistency(format!(
+                "invalid schedule {:?} (day {}) of recurring loan {}",
+                recurring.frequency,
+                recurring.day,
+                recurring.id
+            ))),
+        };
+        Ok(Self {
+            creator: user_id_from_sqlite(recurring.creator)?,
+            chat_id: ChatId(recurring.chat_id),
+            id: recurring.id,
+            frequency,
+            loan: recurring.loan,
+            paused: recurring.paused,
+            next_run: recurring.next_run,
+            created: recurring.created,
+        })
+    }
+}
+
+impl From<Recurring> for SqliteRecurring {
+    fn from(recurring: Recurring) -> Self {
+        let (frequency, day) = recurring.frequency.to_sqlite();
+        Self {
+            id: recurring.id,
+            chat_id: recurring.chat_id.0,
+            creator: recurring.creator.0 as i64,
+            frequency,
+            day,
+            loan: recurring.loan,
+            paused: recurring.paused,
+            next_run: recurring.next_run,
+            created: recurring.created,
+        }
+    }
+}
+
+impl From<NewRecurring> for SqliteRecurring {
+    fn from(recurring: NewRecurring) -> Self {
+        let (frequency, day) = recurring.frequency.to_sqlite();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            chat_id: recurring.chat_id.0,
+            creator: recurring.creator.0 as i64,
+            frequency,
+            day,
+            loan: recurring.loan,
+            paused: false,
+            next_run: recurring.frequency.next_run_after(recurring.created, recurring.timezone),
+            created: recurring.created,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::NaiveDateTime;
+use teloxide::types::{ChatId, UserId};
+use uuid::Uuid;
+use super::error::TrackerError;
+use super::schema::{reminder_preferences, reminder_thresholds, reminders};
+use super::user::user_id_from_sqlite;
+
+/// Debt of `borrower` to `owes` that the chat gets reminded about
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    pub id: String,
+    pub chat_id: ChatId,
+    pub borrower: UserId,
+    pub owes: UserId,
+    /// Set for loans with a due date, threshold reminders have none
+    pub due: Option<NaiveDateTime>,
+    pub last_sent: Option<NaiveDateTime>,
+}
+
+/// Whether (and from when on) a user wants to be reminded of their debt
+#[derive(Debug, Clone)]
+pub struct ReminderPreference {
+    pub user_id: UserId,
+    pub opted_out: bool,
+    pub snoozed_until: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
+#[table_name = "reminders"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct SqliteReminder {
+    pub id: String,
+    pub chat_id: i64,
+    pub borrower: i64,
+    pub owes: i64,
+    pub due: Option<NaiveDateTime>,
+    pub last_sent: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "reminder_preferences"]
+pub struct SqliteReminderPreference {
+    pub user_id: i64,
+    pub opted_out: bool,
+    pub snoozed_until: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "reminder_thresholds"]
+pub struct SqliteReminderThreshold {
+    pub chat_id: i64,
+    pub threshold: i32,
+}
+
+pub struct NewReminder {
+    pub chat_id: ChatId,
+    pub borrower: UserId,
+    pub owes: UserId,
+    pub due: Option<NaiveDateTime>,
+}
+
+impl ReminderPreference {
+    pub fn wants_reminders(&self, now: NaiveDateTime) -> bool {
+        !self.opted_out && self.snoozed_until.is_none_or(|until| until <= now)
+    }
+}
+
+impl TryFrom<SqliteReminder> for Reminder {
+    type Error = TrackerError;
+
+    fn try_from(reminder: SqliteReminder) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chat_id: ChatId(reminder.chat_id),
+            borrower: user_id_from_sqlite(reminder.borrower)?,
+            owes: user_id_from_sqlite(reminder.owes)?,
+            id: reminder.id,
+            due: reminder.due,
+            last_sent: reminder.last_sent,
+        })
+    }
+}
+
+impl From<Reminder> for SqliteReminder {
+    fn from(reminder: Reminder) -> Self {
+        Self {
+            id: reminder.id,
+            chat_id: reminder.chat_id.0,
+            borrower: reminder.borrower.0 as i64,
+            owes: reminder.owes.0 as i64,
+            due: reminder.due,
+            last_sent: reminder.last_sent,
+        }
+    }
+}
+
+impl From<NewReminder> for SqliteReminder {
+    fn from(reminder: NewReminder) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            chat_id: reminder.chat_id.0,
+            borrower: reminder.borrower.0 as i64,
+            owes: reminder.owes.0 as i64,
+            due: reminder.due,
+            last_sent: None,
+        }
+    }
+}
+
+impl TryFrom<SqliteReminderPreference> for ReminderPreference {
+    type Error = TrackerError;
+
+    fn try_from(preference: SqliteReminderPreference) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: user_id_from_sqlite(preference.user_id)?,
+            opted_out: preference.opted_out,
+            snoozed_until: preference.snoozed_until,
+        })
+    }
+}
+
+impl From<ReminderPreference> for SqliteReminderPreference {
+    fn from(preference: ReminderPreference) -> Self {
+        Self {
+            user_id: preference.user_id.0 as i64,
+            opted_out: preference.opted_out,
+            snoozed_until: preference.snoozed_until,
+        }
+    }
+}
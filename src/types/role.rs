@@ -0,0 +1,49 @@
+use std::fmt;
+
+/// What a member may do in a chat, from least to most. Telegram's owner and
+/// admins of a group have at least their Telegram role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Role {
+    /// Can look at the debt, but not record anything
+    ReadOnly,
+    /// Records transactions they take part in
+    #[default]
+    Member,
+    /// Changes settings and roles, records anything
+    Admin,
+    /// The one who created the group (of a private chat, its user)
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Owner, Role::Admin, Role::Member, Role::ReadOnly];
+
+    pub fn parse(role: &str) -> Option<Role> {
+        match role.to_lowercase().as_str() {
+            "owner" => Some(Role::Owner),
+            "admin" => Some(Role::Admin),
+            "member" => Some(Role::Member),
+            "readonly" | "read-only" => Some(Role::ReadOnly),
+            _ => None,
+        }
+    }
+
+    pub fn administers(&self) -> bool {
+        *self >= Role::Admin
+    }
+
+    pub fn records(&self) -> bool {
+        *self >= Role::Member
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Owner => write!(f, "owner"),
+            Role::Admin => write!(f, "admin"),
+            Role::Member => write!(f, "member"),
+            Role::ReadOnly => write!(f, "readonly"),
+        }
+    }
+}
@@ -1,9 +1,10 @@
 table! {
     ledgers (id) {
         id -> Text,
-        borrower -> Text,
-        owes -> Text,
+        borrower -> BigInt,
+        owes -> BigInt,
         sum -> Integer,
+        event_id -> Nullable<Text>,
     }
 }
 
@@ -11,18 +12,21 @@ table! {
     transactions (id) {
         id -> Text,
         transaction_type -> Text,
-        initiator -> Text,
-        reciever -> Text,
+        initiator -> BigInt,
+        reciever -> BigInt,
         sum -> Integer,
         description -> Text,
-        created -> Text,
+        created -> Timestamp,
+        event_id -> Nullable<Text>,
+        expense_id -> Nullable<Text>,
+        recorded_by -> Nullable<BigInt>,
     }
 }
 
 table! {
     users (id) {
         id -> Text,
-        user_id -> Text,
+        user_id -> BigInt,
         name -> Text,
     }
 }
@@ -30,16 +34,203 @@ table! {
 table! {
     chats (id) {
         id -> Text,
-        user_id -> Text,
-        chat_id -> Text,
+        user_id -> BigInt,
+        chat_id -> BigInt,
+        role -> Text,
+        role_assigned -> Bool,
+        has_left -> Bool,
     }
 }
 
+table! {
+    categories (id) {
+        id -> Text,
+        chat_id -> BigInt,
+        name -> Text,
+    }
+}
+
+table! {
+    category_aliases (id) {
+        id -> Text,
+        category_id -> Text,
+        chat_id -> BigInt,
+        alias -> Text,
+    }
+}
+
+table! {
+    transaction_categories (transaction_id, category_id) {
+        transaction_id -> Text,
+        category_id -> Text,
+        position -> Integer,
+    }
+}
+
+table! {
+    recurring (id) {
+        id -> Text,
+        chat_id -> BigInt,
+        creator -> BigInt,
+        frequency -> Text,
+        day -> Integer,
+        loan -> Text,
+        paused -> Bool,
+        next_run -> Timestamp,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    reminders (id) {
+        id -> Text,
+        chat_id -> BigInt,
+        borrower -> BigInt,
+        owes -> BigInt,
+        due -> Nullable<Timestamp>,
+        last_sent -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    reminder_preferences (user_id) {
+        user_id -> BigInt,
+        opted_out -> Bool,
+        snoozed_until -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    reminder_thresholds (chat_id) {
+        chat_id -> BigInt,
+        threshold -> Integer,
+    }
+}
+
+table! {
+    digests (chat_id) {
+        chat_id -> BigInt,
+        frequency -> Text,
+        day -> Integer,
+        minute -> Integer,
+        timezone -> Text,
+        next_run -> Timestamp,
+        last_sent -> Nullable<Timestamp>,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    user_settings (user_id) {
+        user_id -> BigInt,
+        language -> Text,
+    }
+}
+
+table! {
+    chat_settings (chat_id) {
+        chat_id -> BigInt,
+        currency -> Text,
+        timezone -> Text,
+        language -> Text,
+        auto_simplify -> Bool,
+        approval_required -> Bool,
+        reminders -> Text,
+        admin_only -> Bool,
+        record_for_others -> Bool,
+    }
+}
+
+table! {
+    approvals (id) {
+        id -> Text,
+        chat_id -> BigInt,
+        sender -> BigInt,
+        text -> Text,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    drafts (id) {
+        id -> Text,
+        chat_id -> BigInt,
+        sender -> BigInt,
+        text -> Text,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    events (id) {
+        id -> Text,
+        chat_id -> BigInt,
+        name -> Text,
+        created -> Timestamp,
+        closed -> Nullable<Timestamp>,
+        active -> Bool,
+    }
+}
+
+table! {
+    shared_expenses (id) {
+        id -> Text,
+        chat_id -> BigInt,
+        description -> Text,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    expense_shares (expense_id, user_id) {
+        expense_id -> Text,
+        user_id -> BigInt,
+        paid -> Integer,
+        share -> Integer,
+        position -> Integer,
+    }
+}
+
+table! {
+    api_tokens (chat_id) {
+        chat_id -> BigInt,
+        token_hash -> Text,
+        issued -> Timestamp,
+    }
+}
+
+table! {
+    forgotten_users (user_id) {
+        user_id -> BigInt,
+    }
+}
+
+joinable!(category_aliases -> categories (category_id));
+joinable!(transaction_categories -> categories (category_id));
+joinable!(expense_shares -> shared_expenses (expense_id));
+joinable!(transaction_categories -> transactions (transaction_id));
+
 allow_tables_to_appear_in_same_query!(
     ledgers,
     transactions,
     users,
     chats,
+    categories,
+    category_aliases,
+    transaction_categories,
+    recurring,
+    reminders,
+    reminder_preferences,
+    reminder_thresholds,
+    digests,
+    chat_settings,
+    user_settings,
+    approvals,
+    drafts,
+    events,
+    shared_expenses,
+    expense_shares,
+    api_tokens,
+    forgotten_users,
 );
 
-joinable!(users -> chats (user_id));
\ No newline at end of file
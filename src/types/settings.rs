@@ -0,0 +1,180 @@
+use std::fmt;
+use chrono_tz::Tz;
+use teloxide::types::ChatId;
+use super::error::TrackerError;
+use super::schema::{chat_settings, user_settings};
+
+/// Options of a chat, changed with /settings
+#[derive(Debug, Clone)]
+pub struct ChatSettings {
+    pub chat_id: ChatId,
+    /// ISO 4217 code (EUR, USD, ...)
+    pub currency: String,
+    /// Dates are shown and digests scheduled in this timezone
+    pub timezone: Tz,
+    /// `None` leaves the language to each user's Telegram app
+    pub language: Option<Language>,
+    /// /balance replaces the recorded debt with the simplified one
+    pub auto_simplify: bool,
+    /// Loans and payments wait for the counterparty to approve them
+    pub approval_required: bool,
+    pub reminders: ReminderPolicy,
+    /// Only admins of the chat can record expenses and change them
+    pub admin_only: bool,
+    /// Members can record transactions they don't take part in
+    pub record_for_others: bool,
+}
+
+/// Languages the bot speaks, see the catalogues in locales/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Slovenian,
+}
+
+/// Where reminders about debt of a chat are sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReminderPolicy {
+    /// By DM to people who started a chat with the bot, to the chat otherwise
+    Auto,
+    /// Only by DM, people without a private chat are not reminded
+    Private,
+    Off,
+}
+
+#[derive(Debug, Insertable, Queryable, Identifiable, AsChangeset)]
+#[table_name = "chat_settings"]
+#[primary_key(chat_id)]
+pub struct SqliteChatSettings {
+    pub chat_id: i64,
+    pub currency: String,
+    pub timezone: String,
+    pub language: String,
+    pub auto_simplify: bool,
+    pub approval_required: bool,
+    pub reminders: String,
+    pub admin_only: bool,
+    pub record_for_others: bool,
+}
+
+#[derive(Debug, Insertable, Queryable)]
+#[table_name = "user_settings"]
+pub struct SqliteUserSettings {
+    pub user_id: i64,
+    pub language: String,
+}
+
+impl ChatSettings {
+    /// Settings of a chat that never changed them
+    pub fn default_for(chat_id: ChatId) -> Self {
+        Self {
+            chat_id,
+            currency: "EUR".to_string(),
+            timezone: Tz::UTC,
+            language: None,
+            auto_simplify: true,
+            approval_required: false,
+            reminders: ReminderPolicy::Auto,
+            admin_only: false,
+            record_for_others: false,
+        }
+    }
+}
+
+impl ReminderPolicy {
+    pub fn parse(policy: &str) -> Option<ReminderPolicy> {
+        match policy.to_lowercase().as_str() {
+            "auto" => Some(ReminderPolicy::Auto),
+            "private" => Some(ReminderPolicy::Private),
+            "off" => Some(ReminderPolicy::Off),
+            _ => None,
+        }
+    }
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Slovenian];
+
+    /// Reads a language code as Telegram sends it ("sl", "en-GB", ...)
+    pub fn parse(code: &str) -> Option<Language> {
+        let primary = code.split(['-', '_']).next().unwrap_or_default();
+        match primary.to_lowercase().as_str() {
+            "en" => Some(Language::English),
+            "sl" => Some(Language::Slovenian),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Slovenian => "sl",
+        }
+    }
+}
+
+impl fmt::Display for ReminderPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReminderPolicy::Auto => write!(f, "auto"),
+            ReminderPolicy::Private => write!(f, "private"),
+            ReminderPolicy::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl TryFrom<SqliteChatSettings> for ChatSettings {
+    type Error = TrackerError;
+
+    fn try_from(settings: SqliteChatSettings) -> Result<Self, Self::Error> {
+        let invalid = |option: &str, value: &str| TrackerError::Consistency(format!(
+            "invalid {} {:?} in settings of chat {}",
+            option,
+            value,
+            settings.chat_id
+        ));
+        let timezone = match settings.timezone.parse::<Tz>() {
+            Ok(tz) => tz,
+            Err(_) => return Err(invalid("timezone", &settings.timezone)),
+        };
+        let language = match settings.language.as_str() {
+            "auto" => None,
+            code => match Language::parse(code) {
+                Some(language) => Some(language),
+                None => return Err(invalid("language", code)),
+            },
+        };
+        let reminders = match ReminderPolicy::parse(&settings.reminders) {
+            Some(policy) => policy,
+            None => return Err(invalid("reminder policy", &settings.reminders)),
+        };
+        Ok(Self {
+            chat_id: ChatId(settings.chat_id),
+            currency: settings.currency,
+            timezone,
+            language,
+            auto_simplify: settings.auto_simplify,
+            approval_required: settings.approval_required,
+            reminders,
+            admin_only: settings.admin_only,
+            record_for_others: settings.record_for_others,
+        })
+    }
+}
+
+impl From<ChatSettings> for SqliteChatSettings {
+    fn from(settings: ChatSettings) -> Self {
+        Self {
+            chat_id: settings.chat_id.0,
+            currency: settings.currency,
+            timezone: settings.timezone.name().to_string(),
+            language: settings.language.map_or("auto", |l| l.code()).to_string(),
+            auto_simplify: settings.auto_simplify,
+            approval_required: settings.approval_required,
+            reminders: settings.reminders.to_string(),
+            admin_only: settings.admin_only,
+            record_for_others: settings.record_for_others,
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::{BTreeMap, HashMap};
+use chrono::{Datelike, NaiveDateTime};
+
+use super::{category::Category, transaction::Transaction, transaction_type::TransactionType, user::User};
+
+/// Category of loans that are not tagged with a #hashtag
+pub const UNCATEGORISED: &str = "other";
+
+/// Spending statistics of a chat over a time range. Purely in memory, built
+/// from the chat's transactions.
+#[derive(Debug)]
+pub struct Stats {
+    /// Start of the range, `None` covers the whole history
+    pub since: Option<NaiveDateTime>,
+    /// Category the transactions were filtered by
+    pub category: Option<String>,
+    pub loan_count: usize,
+    pub payment_count: usize,
+    /// Months in the range, oldest first, including months without activity
+    pub monthly: Vec<MonthlyTotal>,
+    /// One series per user that had any activity in the range
+    pub balances: Vec<BalanceHistory>,
+    /// Loaned amount per category, largest first
+    pub categories: Vec<CategoryTotal>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MonthlyTotal {
+    pub year: i32,
+    pub month: u32,
+    pub loaned: i64,
+    pub payed: i64,
+}
+
+/// Net balance of a user (what they are owed minus what they owe, in cents)
+/// after every transaction they took part in
+#[derive(Debug)]
+pub struct BalanceHistory {
+    pub username: String,
+    pub points: Vec<(NaiveDateTime, i64)>,
+}
+
+#[derive(Debug)]
+pub struct CategoryTotal {
+    pub category: String,
+    pub sum: i64,
+}
+
+impl CategoryTotal {
+    /// "#food", untagged spending stays "other"
+    pub fn label(&self) -> String {
+        match self.category.as_str() {
+            UNCATEGORISED => self.category.clone(),
+            category => format!("#{}", category),
+        }
+    }
+}
+
+impl Stats {
+    /// Transactions have to be sorted by creation time. Balances take the
+    /// whole history into account, everything else only the range. Loans
+    /// count towards their main (first) category.
+    pub fn from(
+        users: &[User],
+        transactions: &[Transaction],
+        tagged: &HashMap<String, Vec<Category>>,
+        since: Option<NaiveDateTime>
+    ) -> Self {
+        let in_range = |t: &&Transaction| since.is_none_or(|s| t.created >= s);
+        let mut stats = Stats {
+            since,
+            category: None,
+            loan_count: 0,
+            payment_count: 0,
+            monthly: vec![],
+            balances: vec![],
+            categories: vec![],
+        };
+        // monthly totals and categories
+        let mut months: BTreeMap<(i32, u32), (i64, i64)> = BTreeMap::new();
+        let mut categories: HashMap<String, i64> = HashMap::new();
+        for transaction in transactions.iter().filter(in_range) {
+            let month = months.entry((transaction.created.year(), transaction.created.month())).or_default();
+            match transaction.transaction_type {
+                TransactionType::Loan => {
+                    stats.loan_count += 1;
+                    month.0 += transaction.sum as i64;
+                    let category = tagged.get(&transaction.id)
+                        .and_then(|c| c.first())
+                        .map_or(UNCATEGORISED.to_string(), |c| c.name.clone());
+                    *categories.entry(category).or_default() += transaction.sum as i64;
+                },
+                TransactionType::Payment => {
+                    stats.payment_count += 1;
+                    month.1 += transaction.sum as i64;
+                },
+            }
+        }
+        stats.monthly = fill_months(&months);
+        stats.categories = categories.into_iter()
+            .map(|(category, sum)| CategoryTotal { category, sum })
+            .collect();
+        stats.categories.sort_by(|a, b| b.sum.cmp(&a.sum).then(a.category.cmp(&b.category)));
+        // cumulative balances, both loans and payments move money from the
+        // reciever's side to the initiator's
+        for user in users.iter() {
+            let mut balance = 0;
+            let mut history = BalanceHistory { username: user.username.clone(), points: vec![] };
+            for transaction in transactions.iter() {
+                let change = if transaction.initiator == user.user_id {
+                    transaction.sum as i64
+                } else if transaction.reciever == user.user_id {
+                    -(transaction.sum as i64)
+                } else {
+                    continue;
+                };
+                // the balance carried into the range is its first point
+                if !in_range(&transaction) {
+                    balance += change;
+                    continue;
+                }
+                if history.points.is_empty() {
+                    if let Some(since) = since {
+                        history.points.push((since, balance));
+                    }
+                }
+                balance += change;
+                history.points.push((transaction.created, balance));
+            }
+            if !history.points.is_empty() {
+                stats.balances.push(history);
+            }
+        }
+        stats
+    }
+
+    pub fn loaned(&self) -> i64 {
+        self.monthly.iter().map(|m| m.loaned).sum()
+    }
+
+    pub fn payed(&self) -> i64 {
+        self.monthly.iter().map(|m| m.payed).sum()
+    }
+}
+
+fn fill_months(months: &BTreeMap<(i32, u32), (i64, i64)>) -> Vec<MonthlyTotal> {
+    let (first, last) = match (months.keys().next(), months.keys().next_back()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return vec![],
+    };
+    let mut filled = vec![];
+    let (mut year, mut month) = first;
+    while (year, month) <= last {
+        let (loaned, payed) = months.get(&(year, month)).copied().unwrap_or_default();
+        filled.push(MonthlyTotal { year, month, loaned, payed });
+        (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    }
+    filled
+}
@@ -4,7 +4,10 @@ use chrono::NaiveDateTime;
 use teloxide::types::UserId;
 use super::{transaction_type::TransactionType};
 use uuid::Uuid;
+use super::category::Category;
+use super::error::TrackerError;
 use super::schema::transactions;
+use super::user::{User, user_id_from_sqlite};
 
 #[derive(Debug)]
 pub struct Transaction {
@@ -15,6 +18,22 @@ pub struct Transaction {
     pub sum: i32,
     pub description: String,
     pub created: NaiveDateTime,
+    /// Event it was recorded in, `None` for everyday transactions
+    pub event_id: Option<String>,
+    /// Shared expense it is one of the loans of
+    pub expense_id: Option<String>,
+    /// Who entered it, if somebody else than the initiator may have
+    /// (`None` for transactions recorded before this was kept)
+    pub recorded_by: Option<UserId>,
+}
+
+/// Outcome of an executed transaction with both parties resolved
+#[derive(Debug)]
+pub struct TransactionReceipt {
+    pub transaction_type: TransactionType,
+    pub sender: User,
+    pub reciever: User,
+    pub sum: i32,
 }
 
 #[derive(Queryable, Debug, Insertable)]
@@ -22,11 +41,14 @@ pub struct Transaction {
 pub struct SqliteTransaction {
     pub id: String,
     pub transaction_type: String,
-    pub initiator: String,
-    pub reciever: String,
+    pub initiator: i64,
+    pub reciever: i64,
     pub sum: i32,
     pub description: String,
-    pub created: String,
+    pub created: NaiveDateTime,
+    pub event_id: Option<String>,
+    pub expense_id: Option<String>,
+    pub recorded_by: Option<i64>,
 }
 
 #[derive(Debug)]
@@ -37,19 +59,37 @@ pub struct NewTransaction {
     pub sum: i32,
     pub description: String,
     pub created: NaiveDateTime,
+    pub event_id: Option<String>,
+    pub expense_id: Option<String>,
+    pub recorded_by: Option<UserId>,
+    /// Categories of the hashtags in the message, main category first
+    pub categories: Vec<Category>,
 }
 
-impl From<SqliteTransaction> for Transaction {
-    fn from(transaction: SqliteTransaction) -> Self {
-        Self { 
+impl TryFrom<SqliteTransaction> for Transaction {
+    type Error = TrackerError;
+
+    fn try_from(transaction: SqliteTransaction) -> Result<Self, Self::Error> {
+        let transaction_type = match transaction.transaction_type.parse() {
+            Ok(t) => t,
+            Err(_) => return Err(TrackerError::Consistency(format!(
+                "invalid transaction type {:?} in transaction {}",
+                transaction.transaction_type,
+                transaction.id
+            ))),
+        };
+        Ok(Self { 
+            transaction_type,
+            initiator: user_id_from_sqlite(transaction.initiator)?, 
+            reciever: user_id_from_sqlite(transaction.reciever)?, 
             id: transaction.id, 
-            transaction_type: transaction.transaction_type.parse().unwrap(),
-            initiator: serde_json::from_str(&transaction.initiator).unwrap(), 
-            reciever: serde_json::from_str(&transaction.reciever).unwrap(), 
             sum: transaction.sum, 
             description: transaction.description, 
-            created: NaiveDateTime::parse_from_str(&transaction.created, "%Y-%m-%d %H:%M:%S%.f").unwrap(), 
-        }
+            created: transaction.created, 
+            event_id: transaction.event_id,
+            expense_id: transaction.expense_id,
+            recorded_by: transaction.recorded_by.map(user_id_from_sqlite).transpose()?,
+        })
     }
 }
 
@@ -58,11 +98,14 @@ impl From<Transaction> for SqliteTransaction {
         Self { 
             id: transaction.id, 
             transaction_type: transaction.transaction_type.to_string(), 
-            initiator: transaction.initiator.to_string(), 
-            reciever: transaction.reciever.to_string(), 
+            initiator: transaction.initiator.0 as i64, 
+            reciever: transaction.reciever.0 as i64, 
             sum: transaction.sum, 
             description: transaction.description, 
-            created: transaction.created.to_string() 
+            created: transaction.created,
+            event_id: transaction.event_id,
+            expense_id: transaction.expense_id,
+            recorded_by: transaction.recorded_by.map(|id| id.0 as i64),
         }
     }
 }
@@ -72,11 +115,14 @@ impl From<NewTransaction> for SqliteTransaction {
         Self { 
             id: Uuid::new_v4().to_string(), 
             transaction_type: transaction.transaction_type.to_string(), 
-            initiator: transaction.initiator.to_string(), 
-            reciever: transaction.reciever.to_string(), 
+            initiator: transaction.initiator.0 as i64, 
+            reciever: transaction.reciever.0 as i64, 
             sum: transaction.sum, 
             description: transaction.description, 
-            created: transaction.created.to_string() 
+            created: transaction.created,
+            event_id: transaction.event_id,
+            expense_id: transaction.expense_id,
+            recorded_by: transaction.recorded_by.map(|id| id.0 as i64),
         }
     }
 }
@@ -1,5 +1,7 @@
 use teloxide::types::UserId;
 use uuid::Uuid;
+use super::command_input::CommandInput;
+use super::error::{TrackerError, ValidationError};
 use super::schema::users;
 
 #[derive(Debug, Clone)]
@@ -13,30 +15,41 @@ pub struct User {
 #[table_name = "users"]
 pub struct SqliteUser {
     pub id: String,
-    pub user_id: String,
+    pub user_id: i64,
     pub name: String
 }
 
+/// Users who asked to be forgotten get ids from here on, above any id
+/// Telegram gives out
+pub const FORGOTTEN_USER_IDS: i64 = 1 << 62;
+
 pub struct NewUser {
     pub user_id: UserId,
     pub username: String
 }
 
-impl From<SqliteUser> for User {
-    fn from(user: SqliteUser) -> Self {
-        Self { 
+impl TryFrom<SqliteUser> for User {
+    type Error = TrackerError;
+
+    fn try_from(user: SqliteUser) -> Result<Self, Self::Error> {
+        Ok(Self { 
+            user_id: user_id_from_sqlite(user.user_id)?,
             id: user.id, 
-            user_id: serde_json::from_str(&user.user_id).unwrap(),
             username: user.name
-        }
+        })
     }
 }
 
-impl From<&teloxide::types::User> for NewUser {
-    fn from(user: &teloxide::types::User) -> Self {
-        Self {
-            user_id: user.id,
-            username: user.username.as_ref().expect("Unable to determine username").to_string()
+impl TryFrom<&CommandInput> for NewUser {
+    type Error = TrackerError;
+
+    fn try_from(input: &CommandInput) -> Result<Self, Self::Error> {
+        match &input.sender_username {
+            Some(username) => Ok(Self {
+                user_id: input.sender,
+                username: username.to_string()
+            }),
+            None => Err(ValidationError::MissingUsername.into()),
         }
     }
 }
@@ -45,7 +58,7 @@ impl From<User> for SqliteUser {
     fn from(user: User) -> Self {
         Self { 
             id: user.id, 
-            user_id: user.user_id.to_string(),
+            user_id: user.user_id.0 as i64,
             name: user.username
         }
     }
@@ -55,8 +68,17 @@ impl From<NewUser> for SqliteUser {
     fn from(user: NewUser) -> Self {
         Self { 
             id: Uuid::new_v4().to_string(), 
-            user_id: user.user_id.to_string(),
+            user_id: user.user_id.0 as i64,
             name: user.username
         }
     }
 }
+
+/// Telegram user ids are stored as signed integers, anything negative means
+/// the row was not written by us.
+pub(crate) fn user_id_from_sqlite(id: i64) -> Result<UserId, TrackerError> {
+    match u64::try_from(id) {
+        Ok(id) => Ok(UserId(id)),
+        Err(_) => Err(TrackerError::Consistency(format!("invalid stored user id {}", id))),
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::NaiveDate;
+use diesel::SqliteConnection;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use teloxide::types::{ChatId, UserId};
+use teloxide::utils::command::BotCommands;
+
+use debt_tracket_telegram_bot_v2::helpers::data_handler::{
+    api_token_operations::get_api_token,
+    sqlite_operations::establish_memory_connection
+};
+use debt_tracket_telegram_bot_v2::server::api::{answer, listen, ApiConfig};
+use debt_tracket_telegram_bot_v2::tracker::{respond, update_api_token, Command};
+use debt_tracket_telegram_bot_v2::types::{command_input::{extract_mentions, CommandInput}, role::Role};
+
+const CHAT: ChatId = ChatId(-100);
+
+fn input(sender: u64, name: &str, text: &str, day: u32) -> CommandInput {
+    CommandInput {
+        sender: UserId(sender),
+        sender_username: Some(name.to_string()),
+        chat_id: CHAT,
+        text: text.to_string(),
+        mentions: extract_mentions(text),
+        sent: NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+        role: Role::Admin,
+        language_code: None,
+    }
+}
+
+fn send(conn: &SqliteConnection, sender: u64, name: &str, text: &str, day: u32) {
+    let command = Command::parse(text, "").unwrap();
+    respond(conn, &command, &input(sender, name, text, day));
+}
+
+fn descriptions(conn: &SqliteConnection, token: &str, query: &str) -> Vec<String> {
+    let (status, body) = answer(conn, Some(token), "/api/transactions", Some(query));
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total"], 3);
+    body["transactions"].as_array().unwrap().iter()
+        .map(|t| t["description"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn transactions_are_paged_newest_first() {
+    let conn = establish_memory_connection().unwrap();
+    send(&conn, 1, "ana", "/register", 1);
+    send(&conn, 2, "luka", "/register", 1);
+    send(&conn, 1, "ana", "/loan 10 @luka first", 2);
+    send(&conn, 1, "ana", "/loan 20 @luka second", 3);
+    send(&conn, 2, "luka", "/pay 5 @ana third", 4);
+    let token = update_api_token(&conn, &input(1, "ana", "/apitoken", 5)).unwrap().unwrap().token;
+    assert_eq!(descriptions(&conn, &token, "limit=2"), ["third", "second"]);
+    assert_eq!(descriptions(&conn, &token, "offset=2&limit=2"), ["first"]);
+    assert!(descriptions(&conn, &token, "offset=3").is_empty());
+}
+
+#[test]
+fn tokens_are_kept_only_as_hashes() {
+    let conn = establish_memory_connection().unwrap();
+    send(&conn, 1, "ana", "/register", 1);
+    let token = update_api_token(&conn, &input(1, "ana", "/apitoken", 2)).unwrap().unwrap().token;
+    let stored = get_api_token(&conn, &CHAT).unwrap().unwrap();
+    assert_ne!(stored.token_hash, token);
+    assert!(!stored.token_hash.contains(&token));
+    assert_eq!(answer(&conn, Some(&token), "/api/chat", None).0, StatusCode::OK);
+    assert_eq!(answer(&conn, Some(&stored.token_hash), "/api/chat", None).0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn requests_need_a_token() {
+    let config = ApiConfig { address: "127.0.0.1:0".parse().unwrap() };
+    let address = listen(&config, establish_memory_connection).await.unwrap();
+    let uri = format!("http://{}/api/balances", address);
+    let request = Request::get(&uri).header("Authorization", "Bearer guess").body(Body::empty()).unwrap();
+    let response = Client::new().request(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["Content-Type"], "application/json");
+    let request = Request::builder().method(Method::POST).uri(&uri).body(Body::empty()).unwrap();
+    let response = Client::new().request(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
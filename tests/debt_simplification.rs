@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use proptest::prelude::*;
+use teloxide::types::UserId;
+use debt_tracket_telegram_bot_v2::helpers::cycle_detector::detect_debt;
+use debt_tracket_telegram_bot_v2::types::{graph::Graph, ledger::Ledger};
+
+/// At most one ledger per (borrower, owes) pair, like the database enforces
+fn ledgers_from(sums: &[(u64, u64, i32)]) -> Vec<Ledger> {
+    let mut seen = HashMap::new();
+    for (borrower, owes, sum) in sums.iter() {
+        if borrower != owes {
+            seen.entry((*borrower, *owes)).or_insert(*sum);
+        }
+    }
+    let mut pairs: Vec<_> = seen.into_iter().collect();
+    pairs.sort();
+    pairs.into_iter()
+        .enumerate()
+        .map(|(i, ((borrower, owes), sum))| Ledger {
+            id: i.to_string(),
+            borrower: UserId(borrower),
+            owes: UserId(owes),
+            sum,
+            event_id: None,
+        })
+        .collect()
+}
+
+fn non_zero(balances: HashMap<UserId, i64>) -> HashMap<UserId, i64> {
+    balances.into_iter().filter(|(_, b)| *b != 0).collect()
+}
+
+fn edges(ledgers: &[Ledger]) -> usize {
+    ledgers.iter().filter(|l| l.sum != 0).count()
+}
+
+fn check_simplification(ledgers: Vec<Ledger>) -> Result<(), String> {
+    let before = Graph::from(vec![], ledgers.clone());
+    let (simplified, _) = detect_debt(vec![], ledgers.clone());
+    let after = Graph::from(vec![], simplified.clone());
+    if non_zero(before.net_balances()) != non_zero(after.net_balances()) {
+        return Err(format!("net balances changed: {:?} -> {:?}", ledgers, simplified));
+    }
+    if let Some(l) = simplified.iter().find(|l| l.sum < 0) {
+        return Err(format!("negative ledger {:?}", l));
+    }
+    if edges(&simplified) > edges(&ledgers) {
+        return Err(format!("edges increased: {:?} -> {:?}", ledgers, simplified));
+    }
+    if let Some(cycle) = after.find_cycle() {
+        return Err(format!("cycle left: {:?} in {:?}", cycle, simplified));
+    }
+    let ids: Vec<&String> = simplified.iter().map(|l| &l.id).collect();
+    if ids != ledgers.iter().map(|l| &l.id).collect::<Vec<&String>>() {
+        return Err("ledgers were added, removed or reordered".to_string());
+    }
+    Ok(())
+}
+
+/// Paying the suggested transfers leaves everybody even
+fn check_settlement(ledgers: Vec<Ledger>) -> Result<(), String> {
+    let graph = Graph::from(vec![], ledgers.clone());
+    let balances = non_zero(graph.net_balances());
+    let transfers = graph.settlement_transfers();
+    let mut settled = balances.clone();
+    for (from, to, amount) in transfers.iter() {
+        if *amount <= 0 || from == to {
+            return Err(format!("invalid transfer {:?}", (from, to, amount)));
+        }
+        *settled.entry(*from).or_insert(0) += amount;
+        *settled.entry(*to).or_insert(0) -= amount;
+    }
+    if !non_zero(settled).is_empty() {
+        return Err(format!("{:?} does not settle {:?}", transfers, ledgers));
+    }
+    if transfers.len() > balances.len().saturating_sub(1) {
+        return Err(format!("{} transfers for {} people", transfers.len(), balances.len()));
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn simplification_keeps_invariants(sums in prop::collection::vec((0u64..8, 0u64..8, 0i32..100_000), 0..40)) {
+        let result = check_simplification(ledgers_from(&sums));
+        prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+    }
+
+    #[test]
+    fn settlement_evens_everybody_out(sums in prop::collection::vec((0u64..8, 0u64..8, 0i32..100_000), 0..40)) {
+        let result = check_settlement(ledgers_from(&sums));
+        prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+    }
+}
+
+/// Every possible debt state of three people with small sums
+#[test]
+fn simplification_is_correct_for_all_small_groups() {
+    let pairs = [(1, 2), (2, 1), (1, 3), (3, 1), (2, 3), (3, 2)];
+    for state in 0..3usize.pow(pairs.len() as u32) {
+        let mut rest = state;
+        let sums: Vec<(u64, u64, i32)> = pairs.iter()
+            .map(|(b, o)| {
+                let sum = (rest % 3) as i32;
+                rest /= 3;
+                (*b, *o, sum)
+            })
+            .collect();
+        if let Err(e) = check_simplification(ledgers_from(&sums)) {
+            panic!("state {}: {}", state, e);
+        }
+    }
+}
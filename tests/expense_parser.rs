@@ -0,0 +1,34 @@
+use std::fs;
+use debt_tracket_telegram_bot_v2::helpers::expense_parser::{parse_expense, Expense};
+
+/// "23,40 EUR | groceries", "? 2 / 23,40 | pizzas" for ambiguous messages
+fn describe(expense: &Expense) -> String {
+    let amount = match &expense.amount {
+        Some(amount) => [Some(amount.number.clone()), amount.currency.clone()].into_iter().flatten().collect::<Vec<String>>().join(" "),
+        None => {
+            let candidates: Vec<&str> = expense.candidates.iter().map(|c| c.number.as_str()).collect();
+            format!("? {}", candidates.join(" / ")).trim_end().to_string()
+        },
+    };
+    format!("{} | {}", amount, expense.description)
+}
+
+#[test]
+fn reads_the_corpus() {
+    let corpus = fs::read_to_string("tests/corpus/expenses.txt").expect("corpus is readable");
+    let lines: Vec<&str> = corpus.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .collect();
+    let mut problems = vec![];
+    for pair in lines.chunks(2) {
+        let (message, expected) = match pair {
+            [message, expected] => (message, expected.strip_prefix("> ").unwrap_or(expected)),
+            _ => panic!("{:?} has no expected reading", pair),
+        };
+        let actual = describe(&parse_expense(message));
+        if actual.trim_end() != expected.trim_end() {
+            problems.push(format!("{}\n  expected: {}\n  actual:   {}", message, expected, actual));
+        }
+    }
+    assert!(problems.is_empty(), "{} of {} messages read wrong:\n{}", problems.len(), lines.len() / 2, problems.join("\n"));
+}
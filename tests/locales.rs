@@ -0,0 +1,29 @@
+use std::collections::BTreeSet;
+use debt_tracket_telegram_bot_v2::helpers::i18n::catalogue;
+use debt_tracket_telegram_bot_v2::types::settings::Language;
+
+/// "{sum} to {owes}" -> {"owes", "sum"}
+fn placeholders(text: &str) -> BTreeSet<&str> {
+    text.split('{').skip(1).filter_map(|part| part.split_once('}').map(|(name, _)| name)).collect()
+}
+
+#[test]
+fn catalogues_have_the_same_messages() {
+    let english = catalogue(Language::English);
+    for language in Language::ALL {
+        let translated = catalogue(language);
+        let mut problems = vec![];
+        for (key, text) in english.iter() {
+            match translated.get(key) {
+                Some(translation) if placeholders(translation) != placeholders(text) => {
+                    problems.push(format!("{}: placeholders {:?}, expected {:?}", key, placeholders(translation), placeholders(text)));
+                },
+                Some(_) => (),
+                None => problems.push(format!("{}: missing", key)),
+            }
+        }
+        problems.extend(translated.keys().filter(|key| !english.contains_key(*key)).map(|key| format!("{}: unknown", key)));
+        problems.sort();
+        assert!(problems.is_empty(), "{} catalogue:\n{}", language.code(), problems.join("\n"));
+    }
+}
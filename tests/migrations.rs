@@ -0,0 +1,85 @@
+use diesel::connection::SimpleConnection;
+use diesel::{Connection, SqliteConnection};
+use teloxide::types::{ChatId, UserId};
+
+use debt_tracket_telegram_bot_v2::helpers::data_handler::{
+    chat_operations::get_chat_users,
+    ledger_operations::get_ledger,
+    migration_operations::run_pending_migrations,
+    sqlite_operations::establish_connection,
+    transaction_operations::get_user_transactions,
+    user_operations::get_user_by_user_id
+};
+
+/// Database as the first version of the bot left it: ids stored as
+/// strings, some of them JSON quoted, duplicated users, ledgers and chat
+/// memberships, and debt of somebody who never registered
+fn legacy_database() -> SqliteConnection {
+    let conn = SqliteConnection::establish(":memory:").unwrap();
+    conn.batch_execute(include_str!("../migrations/2022-07-01-102900_initial_setup/up.sql")).unwrap();
+    conn.batch_execute(r#"
+        create table __diesel_schema_migrations (
+            version varchar(50) primary key not null,
+            run_on timestamp not null default current_timestamp
+        );
+        insert into __diesel_schema_migrations (version) values ('20220701102900');
+
+        insert into users values ('u1', '"1"', 'ana');
+        insert into users values ('u1-again', '1', 'ana_again');
+        insert into users values ('u2', '2', 'luka');
+
+        insert into ledgers values ('l1', '"2"', '1', 1000);
+        insert into ledgers values ('l2', '2', '"1"', 500);
+        insert into ledgers values ('l3', '3', '1', 200);
+
+        insert into chats values ('c1', '"1"', '"-100"');
+        insert into chats values ('c2', '1', '-100');
+        insert into chats values ('c3', '2', '-100');
+
+        insert into transactions values ('t1', 'Loan', '"1"', '2', 1000, 'pizza', '2022-07-01 10:00:00');
+        insert into transactions values ('t2', 'Loan', '1', '"2"', 500, 'beer', '2022-07-02 20:30:00');
+    "#).unwrap();
+    conn
+}
+
+#[test]
+fn legacy_rows_are_converted() {
+    let conn = legacy_database();
+    let applied = run_pending_migrations(&conn).unwrap();
+    assert!(applied.contains(&"2026-10-19-090000_typed_schema".to_string()));
+
+    // the first registration of a telegram id wins
+    let ana = get_user_by_user_id(&conn, &UserId(1)).unwrap();
+    assert_eq!(ana.len(), 1);
+    assert_eq!((ana[0].id.as_str(), ana[0].username.as_str()), ("u1", "ana"));
+    // who only shows up in a ledger gets a nameless placeholder
+    let unknown = get_user_by_user_id(&conn, &UserId(3)).unwrap();
+    assert_eq!(unknown.len(), 1);
+    assert_eq!(unknown[0].username, "");
+
+    // ledgers of the same pair are added up
+    let ledgers = get_ledger(&conn, &UserId(2), &UserId(1), None).unwrap();
+    assert_eq!(ledgers.iter().map(|l| l.sum).collect::<Vec<i32>>(), [1500]);
+    let ledgers = get_ledger(&conn, &UserId(3), &UserId(1), None).unwrap();
+    assert_eq!(ledgers.iter().map(|l| l.sum).collect::<Vec<i32>>(), [200]);
+
+    // every membership once
+    let mut members: Vec<u64> = get_chat_users(&conn, &ChatId(-100)).unwrap().iter().map(|u| u.user_id.0).collect();
+    members.sort();
+    assert_eq!(members, [1, 2]);
+
+    let transactions = get_user_transactions(&conn, &UserId(1)).unwrap();
+    let converted: Vec<(&str, u64, u64, i32, String)> = transactions.iter()
+        .map(|t| (t.description.as_str(), t.initiator.0, t.reciever.0, t.sum, t.created.to_string()))
+        .collect();
+    assert_eq!(converted, [
+        ("pizza", 1, 2, 1000, "2022-07-01 10:00:00".to_string()),
+        ("beer", 1, 2, 500, "2022-07-02 20:30:00".to_string()),
+    ]);
+}
+
+#[test]
+fn a_missing_database_url_is_an_error() {
+    std::env::remove_var("DATABASE_URL");
+    assert!(establish_connection().is_err());
+}
@@ -0,0 +1,18 @@
+use std::{fs, path::Path};
+use debt_tracket_telegram_bot_v2::tracker::simulation;
+
+#[test]
+fn conversations_replay_as_expected() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/simulations");
+    let mut failures = vec![];
+    for entry in fs::read_dir(dir).expect("simulation directory") {
+        let path = entry.expect("simulation file").path();
+        if path.extension().is_none_or(|ext| ext != "sim") {
+            continue;
+        }
+        if let Err(errors) = simulation::run_file(&path) {
+            failures.extend(errors.into_iter().map(|e| format!("{}: {}", path.display(), e)));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
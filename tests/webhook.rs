@@ -0,0 +1,68 @@
+use hyper::{Body, Client, Method, Request, StatusCode};
+use teloxide::dispatching::update_listeners::AsUpdateStream;
+use teloxide::types::UpdateKind;
+use tokio_stream::StreamExt;
+
+use debt_tracket_telegram_bot_v2::server::webhook::{listen, WebhookConfig, SECRET_HEADER};
+
+const LOAN: &str = include_str!("updates/loan.json");
+
+fn config() -> WebhookConfig {
+    WebhookConfig {
+        address: "127.0.0.1:0".parse().unwrap(),
+        url: None,
+        secret: "s3cret".to_string(),
+    }
+}
+
+async fn post(address: std::net::SocketAddr, secret: Option<&str>, body: &'static str) -> StatusCode {
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://{}/", address))
+        .header("Content-Type", "application/json");
+    if let Some(secret) = secret {
+        request = request.header(SECRET_HEADER, secret);
+    }
+    let response = Client::new().request(request.body(Body::from(body)).unwrap()).await.unwrap();
+    response.status()
+}
+
+#[tokio::test]
+async fn posted_updates_reach_the_listener() {
+    let (address, mut listener) = listen(&config()).await.unwrap();
+    assert_eq!(post(address, Some("s3cret"), LOAN).await, StatusCode::OK);
+    let updates = listener.as_stream();
+    tokio::pin!(updates);
+    let update = updates.next().await.unwrap().unwrap();
+    assert_eq!(update.id, 10001);
+    match update.kind {
+        UpdateKind::Message(message) => assert_eq!(message.text(), Some("/loan 20 @luka pizza")),
+        kind => panic!("expected a message, got {:?}", kind),
+    }
+}
+
+#[tokio::test]
+async fn updates_without_the_secret_are_refused() {
+    let (address, _listener) = listen(&config()).await.unwrap();
+    assert_eq!(post(address, None, LOAN).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(post(address, Some("guess"), LOAN).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(post(address, Some("s3cre"), LOAN).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(post(address, Some("s3cret1"), LOAN).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn anything_but_an_update_is_refused() {
+    let (address, _listener) = listen(&config()).await.unwrap();
+    assert_eq!(post(address, Some("s3cret"), "{\"hello\": 1}").await, StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn webhook_mode_needs_a_secret() {
+    std::env::set_var("WEBHOOK_LISTEN", "127.0.0.1:8443");
+    std::env::remove_var("WEBHOOK_SECRET");
+    assert!(WebhookConfig::from_env().is_err());
+    std::env::set_var("WEBHOOK_SECRET", "s3cret");
+    assert_eq!(WebhookConfig::from_env().unwrap().map(|config| config.secret), Some("s3cret".to_string()));
+    std::env::remove_var("WEBHOOK_LISTEN");
+    std::env::remove_var("WEBHOOK_SECRET");
+}